@@ -0,0 +1,292 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Stress test of metric updates under heavy label churn.
+//!
+//! Runs many refresh cycles through `ForecastMetrics` with randomly chosen stations, names,
+//! and values (including hostile strings) while another thread continuously encodes the
+//! registry. Metrics are occasionally reset and must be empty afterwards. Every encoded payload
+//! must be valid exposition format and each station must never have more than one metadata
+//! series. Exits non-zero on the first failure.
+//!
+//! ```text
+//! cargo run --example label_churn -- 5000
+//! ```
+
+use nws_exporter::client::{Observation, Station, StationProperties};
+use nws_exporter::config::{ColdRiskConfig, OutputConfig, StationSource};
+use nws_exporter::metrics::ForecastMetrics;
+use nws_exporter::sink::{OutputSink, StationObservation};
+use prometheus_client::encoding::text;
+use prometheus_client::registry::Registry;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+const DEFAULT_CYCLES: usize = 2000;
+const STATION_POOL: usize = 50;
+const RESET_ODDS: usize = 100;
+const FIXTURE: &str = include_str!("../ext/fixtures/KBOS.json");
+const HOSTILE: &[&str] = &[
+    "plain",
+    "quote \" in the middle",
+    "back\\slash",
+    "new\nline",
+    "carriage\rreturn\ttab",
+    "emoji \u{1F327}\u{FE0F} rain",
+    "} 1.0\nfake_metric{a=\"b\"} 2",
+    "",
+];
+const SOURCES: &[StationSource] = &[
+    StationSource::Cli,
+    StationSource::Env,
+    StationSource::Config,
+    StationSource::Fixture,
+];
+
+/// Small xorshift generator so runs are repeatable without extra dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cycles = std::env::args()
+        .nth(1)
+        .map(|s| s.parse().expect("cycles must be a number"))
+        .unwrap_or(DEFAULT_CYCLES);
+
+    let template: Observation = serde_json::from_str(FIXTURE).expect("unable to parse fixture");
+    let mut registry = <Registry>::default();
+    let metrics = ForecastMetrics::new(
+        &mut registry,
+        &OutputConfig {
+            value_precision: Some(2),
+            compute_derived: true,
+            clamp_visibility: false,
+            reject_inconsistent: false,
+            ..OutputConfig::default()
+        },
+        &ColdRiskConfig::default(),
+    );
+
+    let registry = Arc::new(registry);
+    let done = Arc::new(AtomicBool::new(false));
+    let encodes = Arc::new(AtomicUsize::new(0));
+    let encoder = {
+        let registry = registry.clone();
+        let done = done.clone();
+        let encodes = encodes.clone();
+        thread::spawn(move || {
+            while !done.load(Ordering::Acquire) {
+                let mut buf = String::new();
+                text::encode(&mut buf, &registry).expect("unable to encode registry");
+                if let Err(e) = validate(&buf) {
+                    eprintln!("FAIL: invalid exposition: {}", e);
+                    process::exit(1);
+                }
+                encodes.fetch_add(1, Ordering::AcqRel);
+            }
+        })
+    };
+
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let mut resets = 0;
+    for _ in 0..cycles {
+        if rng.below(RESET_ODDS) == 0 {
+            metrics.reset();
+            resets += 1;
+
+            let mut buf = String::new();
+            text::encode(&mut buf, &registry).expect("unable to encode registry");
+            let remaining = series(&buf, "nws_station") + series(&buf, "nws_temperature_degrees");
+            if remaining > 0 {
+                eprintln!("FAIL: {} series after reset", remaining);
+                process::exit(1);
+            }
+        }
+
+        let mut batch = Vec::new();
+        for _ in 0..rng.below(STATION_POOL) {
+            let id = format!("K{:03}", rng.below(STATION_POOL));
+            let url = format!("https://api.weather.gov/stations/{}", id);
+            let name = format!("{} {}", HOSTILE[rng.below(HOSTILE.len())], rng.below(4));
+            let station = station(&url, &id, &name, &template);
+            metrics
+                .on_station(&station, &url, SOURCES[rng.below(SOURCES.len())])
+                .await;
+
+            let mut obs = template.clone();
+            obs.properties.station = url.clone();
+            obs.properties.temperature.value = match rng.below(4) {
+                0 => None,
+                1 => Some(f64::NAN),
+                _ => Some(rng.below(8000) as f64 / 100.0 - 40.0),
+            };
+            batch.push(StationObservation {
+                station: id,
+                label: url,
+                observation: obs,
+                changed: true,
+            });
+        }
+
+        metrics.on_observations(&batch).await;
+        metrics.on_cycle_end().await;
+    }
+
+    done.store(true, Ordering::Release);
+    encoder.join().expect("encoder thread panicked");
+
+    let mut buf = String::new();
+    text::encode(&mut buf, &registry).expect("unable to encode registry");
+    if let Err(e) = validate(&buf) {
+        eprintln!("FAIL: invalid exposition: {}", e);
+        process::exit(1);
+    }
+
+    let info = series(&buf, "nws_station");
+    let sources = series(&buf, "nws_exporter_station_source");
+    if info > STATION_POOL || sources > STATION_POOL {
+        eprintln!(
+            "FAIL: {} station and {} source series for at most {} stations",
+            info, sources, STATION_POOL
+        );
+        process::exit(1);
+    }
+
+    println!(
+        "OK: {} cycles, {} resets, {} concurrent encodes, {} station series, {} bytes",
+        cycles,
+        resets,
+        encodes.load(Ordering::Acquire),
+        info,
+        buf.len()
+    );
+}
+
+fn station(url: &str, id: &str, name: &str, template: &Observation) -> Station {
+    Station {
+        id: url.to_owned(),
+        type_: "Feature".to_owned(),
+        geometry: template.geometry.clone(),
+        properties: StationProperties {
+            id: url.to_owned(),
+            type_: "wx:ObservationStation".to_owned(),
+            elevation: template.properties.elevation.clone(),
+            station_identifier: id.to_owned(),
+            name: name.to_owned(),
+            timezone: None,
+            forecast_zone: None,
+            county: None,
+        },
+    }
+}
+
+/// Number of samples for the given metric name.
+fn series(buf: &str, name: &str) -> usize {
+    buf.lines()
+        .filter(|l| {
+            l.strip_prefix(name)
+                .map(|r| r.starts_with('{') || r.starts_with(' '))
+                .unwrap_or(false)
+        })
+        .count()
+}
+
+/// Check that every line of the payload is a comment or a well-formed sample.
+fn validate(buf: &str) -> Result<(), String> {
+    if !buf.ends_with("# EOF\n") {
+        return Err("missing # EOF".to_owned());
+    }
+
+    for line in buf.lines() {
+        if line.starts_with("# ") {
+            continue;
+        }
+
+        sample(line).map_err(|e| format!("{}: {:?}", e, line))?;
+    }
+
+    Ok(())
+}
+
+fn sample(line: &str) -> Result<(), &'static str> {
+    let name_end = line
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == ':'))
+        .ok_or("no value")?;
+    if name_end == 0 {
+        return Err("empty metric name");
+    }
+
+    let mut rest = &line[name_end..];
+    if let Some(labels) = rest.strip_prefix('{') {
+        rest = label_set(labels)?;
+    }
+
+    let value = rest.strip_prefix(' ').ok_or("no space before value")?;
+    match value {
+        "NaN" | "+Inf" | "-Inf" => Ok(()),
+        v => v.parse::<f64>().map(|_| ()).map_err(|_| "invalid value"),
+    }
+}
+
+/// Parse `name="value",...}` returning whatever follows the closing brace.
+fn label_set(mut s: &str) -> Result<&str, &'static str> {
+    loop {
+        if let Some(rest) = s.strip_prefix('}') {
+            return Ok(rest);
+        }
+
+        let eq = s.find('=').ok_or("label without value")?;
+        if eq == 0 || !s[..eq].chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err("invalid label name");
+        }
+
+        let mut chars = s[eq + 1..]
+            .strip_prefix('"')
+            .ok_or("unquoted label value")?
+            .char_indices();
+        let end = loop {
+            match chars.next().ok_or("unterminated label value")? {
+                (_, '\\') => match chars.next() {
+                    Some((_, '\\' | '"' | 'n')) => {}
+                    _ => return Err("invalid escape"),
+                },
+                (i, '"') => break i,
+                (_, '\n') => return Err("newline in label value"),
+                _ => {}
+            }
+        };
+
+        s = &s[eq + 2 + end + 1..];
+        s = s.strip_prefix(',').unwrap_or(s);
+    }
+}
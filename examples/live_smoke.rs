@@ -0,0 +1,123 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Smoke test against the live api.weather.gov API.
+//!
+//! Makes a single request to each supported endpoint for a station and checks that the
+//! responses can be parsed and are structurally sane. Values aren't checked since they
+//! change constantly. The station may be given as an argument or via `NWS_SMOKE_STATION`.
+//!
+//! ```text
+//! cargo run --example live_smoke -- KBOS
+//! ```
+
+use nws_exporter::client::{Measurement, NwsClient, StationsQuery, Trigger};
+use nws_exporter::units;
+use reqwest::Client;
+use std::process;
+use std::time::Duration;
+
+const DEFAULT_STATION: &str = "KBOS";
+const STATION_ENV_VAR: &str = "NWS_SMOKE_STATION";
+const API_URL: &str = "https://api.weather.gov/";
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[tokio::main]
+async fn main() {
+    let station = std::env::args()
+        .nth(1)
+        .or_else(|| std::env::var(STATION_ENV_VAR).ok())
+        .unwrap_or_else(|| DEFAULT_STATION.to_owned());
+
+    let http_client = Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .expect("unable to build HTTP client");
+    let client = NwsClient::new(http_client, API_URL).expect("unable to build NWS client");
+    let mut failures = Vec::new();
+
+    match client.station(&station, Trigger::Manual).await {
+        Ok(s) => {
+            check(&mut failures, "station id", !s.properties.id.is_empty());
+            check(
+                &mut failures,
+                "station identifier",
+                !s.properties.station_identifier.is_empty(),
+            );
+            check(
+                &mut failures,
+                "station elevation unit",
+                unit_recognized(&s.properties.elevation),
+            );
+        }
+        Err(e) => failures.push(format!("station request failed: {}", e)),
+    }
+
+    match client.observation(&station, Trigger::Manual).await {
+        Ok(o) => {
+            let p = &o.properties;
+            check(&mut failures, "observation id", !o.id.is_empty());
+            check(&mut failures, "observation station", !p.station.is_empty());
+            for (name, m) in [
+                ("temperature", &p.temperature),
+                ("dewpoint", &p.dewpoint),
+                ("barometric pressure", &p.barometric_pressure),
+                ("visibility", &p.visibility),
+                ("relative humidity", &p.relative_humidity),
+            ] {
+                check(&mut failures, name, unit_recognized(m));
+            }
+        }
+        Err(e) => failures.push(format!("observation request failed: {}", e)),
+    }
+
+    let query = StationsQuery {
+        id: vec![station.clone()],
+        limit: Some(1),
+        ..StationsQuery::default()
+    };
+    match client.stations_page(&query, Trigger::Manual).await {
+        Ok(page) => {
+            check(
+                &mut failures,
+                "station list",
+                page.features.iter().any(|s| s.properties.station_identifier == station),
+            );
+        }
+        Err(e) => failures.push(format!("station list request failed: {}", e)),
+    }
+
+    if failures.is_empty() {
+        println!("{}: ok", station);
+    } else {
+        for f in failures.iter() {
+            eprintln!("{}: {}", station, f);
+        }
+        process::exit(1);
+    }
+}
+
+fn check(failures: &mut Vec<String>, what: &str, ok: bool) {
+    if !ok {
+        failures.push(format!("unexpected {}", what));
+    }
+}
+
+fn unit_recognized(m: &Measurement) -> bool {
+    units::is_supported(m)
+}
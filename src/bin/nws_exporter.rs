@@ -16,39 +16,79 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 //
 
-use axum::routing::get;
+use axum::middleware;
+use axum::routing::{get, post};
 use axum::Router;
 use clap::Parser;
-use nws_exporter::client::{ClientError, NwsClient};
-use nws_exporter::http::RequestState;
-use nws_exporter::metrics::ForecastMetrics;
+use nws_exporter::client::{ClientError, NwsClient, RetryPolicy, Trigger, WeatherClient};
+use nws_exporter::clock::{Clock, SystemClock};
+use nws_exporter::config::{
+    ApiConfig, Config, FileConfig, OutputConfig, ReportingConfig, StationConfig, StationLabelFormat, StationSource,
+    UpdateConfig, WebConfig, DEFAULT_ALERT_REFRESH_SECS, DEFAULT_API_URL, DEFAULT_BIND_ADDR, DEFAULT_FLATLINE_SECS,
+    DEFAULT_FORECAST_REFRESH_SECS, DEFAULT_FUTURE_TOLERANCE_SECS, DEFAULT_MAX_ATTEMPTS, DEFAULT_MAX_BODY_BYTES,
+    DEFAULT_MAX_REDIRECTS, DEFAULT_METADATA_REFRESH_SECS, DEFAULT_QC_ACCEPT, DEFAULT_REFRESH_COOLDOWN_SECS,
+    DEFAULT_REFRESH_SECS, DEFAULT_STALE_AFTER_FAILURES, DEFAULT_TIMEOUT_MILLIS, MAX_VALUE_PRECISION,
+};
+use nws_exporter::fixture::FixtureClient;
+use nws_exporter::geo::Coordinates;
+use nws_exporter::http::{RefreshTrigger, RequestState};
+use nws_exporter::metrics::{
+    register_config_info, AlertMetrics, ClientMetrics, ExporterMetrics, ForecastMetrics, ForecastPeriodMetrics,
+    HttpMetrics, SeriesCountSink, SeriesMetrics,
+};
+use nws_exporter::process::ProcessCollector;
+use nws_exporter::reporting::ReportingSink;
+use nws_exporter::runtime::RuntimeMetrics;
+use nws_exporter::sink::OutputSink;
+use nws_exporter::status::{StationStatus, StatusSink};
+use nws_exporter::update::UpdateTask;
+use nws_exporter::warn::WarnOnce;
 use prometheus_client::registry::Registry;
+use reqwest::redirect::Policy;
 use reqwest::Client;
 use std::error::Error;
 use std::io;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::process;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 use tower_http::trace::TraceLayer;
-use tracing::{Instrument, Level};
+use tracing::Level;
 
 const DEFAULT_LOG_LEVEL: Level = Level::INFO;
-const DEFAULT_BIND_ADDR: ([u8; 4], u16) = ([0, 0, 0, 0], 9782);
-const DEFAULT_REFERSH_SECS: u64 = 300;
-const DEFAULT_TIMEOUT_MILLIS: u64 = 5000;
-const DEFAULT_API_URL: &str = "https://api.weather.gov/";
+const WARN_SUMMARY_INTERVAL: Duration = Duration::from_secs(3600);
+const RUNTIME_METRICS_INTERVAL: Duration = Duration::from_secs(15);
+const STATIONS_ENV_VAR: &str = "NWS_EXPORTER_STATIONS";
 
 /// Export National Weather Service forecasts as Prometheus metrics
 #[derive(Debug, Parser)]
 #[clap(name = "nws_exporter", version = clap::crate_version!())]
 struct NwsExporterApplication {
-    /// NWS weather station ID to fetch forecasts for. Must be specified at least once and
-    /// may be used multiple times (separated by spaces) to fetch forecasts for multiple NWS
-    /// stations
-    #[arg(required = true)]
+    /// NWS weather station ID to fetch forecasts for. May be used multiple times (separated
+    /// by spaces) to fetch forecasts for multiple NWS stations. Additional stations may be
+    /// set with the NWS_EXPORTER_STATIONS environment variable (separated by commas or spaces)
+    /// or in a config file. At least one station must be specified between them unless
+    /// --latitude and --longitude are used. IDs are case-insensitive and stations specified
+    /// more than once are only fetched once
     station: Vec<String>,
 
+    /// Latitude, in degrees, to find the nearest station for at startup. The station is
+    /// fetched in addition to any other stations. Requires --longitude
+    #[arg(long, requires = "longitude", allow_hyphen_values = true)]
+    latitude: Option<f64>,
+
+    /// Longitude, in degrees, to find the nearest station for at startup. Requires --latitude
+    #[arg(long, requires = "latitude", allow_hyphen_values = true)]
+    longitude: Option<f64>,
+
+    /// Path to a TOML config file with additional stations and cold risk thresholds
+    #[arg(long)]
+    config: Option<PathBuf>,
+
     /// Base URL for the Weather.gov API
     #[arg(long, default_value_t = DEFAULT_API_URL.into())]
     api_url: String,
@@ -59,18 +99,218 @@ struct NwsExporterApplication {
     log_level: Level,
 
     /// Fetch weather forecasts from the Weather.gov API at this interval, in seconds
-    #[arg(long, default_value_t = DEFAULT_REFERSH_SECS)]
+    #[arg(long, default_value_t = DEFAULT_REFRESH_SECS)]
     refresh_secs: u64,
 
+    /// Minimum time between refreshes requested via the HTTP API, in seconds
+    #[arg(long, default_value_t = DEFAULT_REFRESH_COOLDOWN_SECS)]
+    refresh_cooldown_secs: u64,
+
+    /// Stop exporting weather metrics for a station after fetching its observations fails
+    /// this many times in a row. Metrics return once fetching succeeds again. Use 0 to
+    /// keep exporting the last observation forever
+    #[arg(long, default_value_t = DEFAULT_STALE_AFTER_FAILURES)]
+    stale_after_failures: u64,
+
+    /// Skip observations older than this many seconds, treating them like a failure to fetch
+    /// an observation for the station. Use 0 to allow observations of any age
+    #[arg(long, default_value_t = 0)]
+    max_observation_age_secs: u64,
+
+    /// Accept observations timestamped up to this many seconds in the future (due to clock
+    /// skew in the API) without counting them as future observations. Their age is reported
+    /// as zero
+    #[arg(long, default_value_t = DEFAULT_FUTURE_TOLERANCE_SECS)]
+    future_tolerance_secs: u64,
+
+    /// Skip observations timestamped further in the future than `--future-tolerance-secs`,
+    /// treating them like a failure to fetch an observation for the station, instead of
+    /// exporting them with an age of zero
+    #[arg(long)]
+    reject_future_observations: bool,
+
+    /// How stations are identified by the `station` label of metrics, either `url` for the
+    /// full URL of the station (https://api.weather.gov/stations/KBOS) or `id` for only its
+    /// identifier (KBOS)
+    #[arg(long, default_value_t = StationLabelFormat::Url)]
+    station_label_format: StationLabelFormat,
+
+    /// Fetch metadata for each station again at this interval, in seconds, so that changes
+    /// to station names or locations are picked up. Use 0 to only fetch it at startup
+    #[arg(long, default_value_t = DEFAULT_METADATA_REFRESH_SECS)]
+    metadata_refresh_secs: u64,
+
+    /// Export the ID of the most recent observation for each station as a label, for debugging.
+    /// The ID includes the time of the observation so this creates a new series for every
+    /// observation
+    #[arg(long)]
+    expose_observation_id: bool,
+
+    /// Export forecasts for this many periods (usually 12 hours each) for the location of each
+    /// station, up to 6. The hourly forecast is fetched as well when this is set. Use 0 to not
+    /// fetch forecasts
+    #[arg(long, default_value_t = 0)]
+    forecast_periods: u32,
+
+    /// Fetch forecasts at this interval, in seconds. Forecasts are only updated by the NWS a few
+    /// times a day
+    #[arg(long, default_value_t = DEFAULT_FORECAST_REFRESH_SECS)]
+    forecast_refresh_secs: u64,
+
+    /// Export active alerts for a forecast or county zone (e.g. MAZ015 or MAC025). May be
+    /// given multiple times. Zones for a location are listed by the /points API endpoint
+    #[arg(long = "alert-zone")]
+    alert_zones: Vec<String>,
+
+    /// Export active alerts for the location of each station. This makes an extra request
+    /// for each station every time alerts are fetched
+    #[arg(long)]
+    alerts: bool,
+
+    /// Fetch alerts at this interval, in seconds
+    #[arg(long, default_value_t = DEFAULT_ALERT_REFRESH_SECS)]
+    alert_refresh_secs: u64,
+
+    /// Replay observations from this many hours before startup, up to 24, so that today's
+    /// highest and lowest temperature and the pressure tendency survive a restart. Use 0 to
+    /// start without any history
+    #[arg(long, default_value_t = 0)]
+    backfill_hours: u32,
+
     /// Timeout for fetching weather forecasts from the Weather.gov API, in milliseconds
     #[arg(long, default_value_t = DEFAULT_TIMEOUT_MILLIS)]
     timeout_millis: u64,
 
-    /// Address to bind to. By default, nws_exporter will bind to public address since
-    /// the purpose is to expose metrics to an external system (Prometheus or another
-    /// agent for ingestion)
-    #[arg(long, default_value_t = DEFAULT_BIND_ADDR.into())]
-    bind: SocketAddr,
+    /// Follow at most this many redirects from the Weather.gov API. Redirects that are
+    /// followed are logged and counted since they usually mean a station ID is stale
+    #[arg(long, default_value_t = DEFAULT_MAX_REDIRECTS)]
+    max_redirects: usize,
+
+    /// Treat redirects from the Weather.gov API as errors instead of following them
+    #[arg(long)]
+    no_follow_redirects: bool,
+
+    /// Treat responses from the Weather.gov API larger than this many bytes as errors
+    /// instead of reading them into memory
+    #[arg(long, default_value_t = DEFAULT_MAX_BODY_BYTES)]
+    max_body_bytes: u64,
+
+    /// Make requests to the Weather.gov API that time out, can't connect, or fail with a 429
+    /// or 5xx status up to this many times in total, waiting longer before each retry. Use 1
+    /// to never retry
+    #[arg(long, default_value_t = DEFAULT_MAX_ATTEMPTS)]
+    max_attempts: u32,
+
+    /// Skip all requests to the Weather.gov API and export the observation in this file
+    /// on every refresh instead. May be used multiple times, one file per station. Cannot
+    /// be combined with station IDs
+    #[arg(long)]
+    fixture_file: Vec<PathBuf>,
+
+    /// Round exported values to this many decimal places. By default, values are exported
+    /// exactly as returned by the Weather.gov API
+    #[arg(long, value_parser = clap::value_parser!(u32).range(0..=i64::from(MAX_VALUE_PRECISION)))]
+    value_precision: Option<u32>,
+
+    /// Compute values the Weather.gov API didn't report (such as relative humidity, heat
+    /// index, and wind chill) from other measurements
+    #[arg(long)]
+    compute_derived: bool,
+
+    /// Export visibility at or above the 10 mile maximum reported by stations as exactly
+    /// 16093 meters instead of the slightly different values some stations report
+    #[arg(long)]
+    clamp_visibility: bool,
+
+    /// Don't export measurements that are physically impossible given the rest of the
+    /// observation, such as a dewpoint above the temperature. Inconsistent observations are
+    /// counted either way
+    #[arg(long)]
+    reject_inconsistent: bool,
+
+    /// Quality control codes of measurements to export, separated by commas. Measurements
+    /// with other known codes (such as 'Z' for unchecked or 'X' for failed checks) are counted
+    /// and not exported. Measurements with unknown codes are always exported
+    #[arg(long, value_delimiter = ',', default_values = DEFAULT_QC_ACCEPT)]
+    qc_accept: Vec<String>,
+
+    /// Consider a station to have stopped reporting when its latest observation hasn't
+    /// changed for this many seconds
+    #[arg(long, default_value_t = DEFAULT_FLATLINE_SECS)]
+    flatline_secs: u64,
+
+    /// Persist the latest observation time for each station to this file so that stations
+    /// that have stopped reporting are detected across restarts
+    #[arg(long)]
+    state_file: Option<PathBuf>,
+
+    /// Address to bind to. May be used multiple times to listen on several addresses. By
+    /// default, nws_exporter will bind to public address since the purpose is to expose
+    /// metrics to an external system (Prometheus or another agent for ingestion)
+    #[arg(long, default_values_t = [SocketAddr::from(DEFAULT_BIND_ADDR)])]
+    bind: Vec<SocketAddr>,
+
+    /// Export metrics about the async runtime used by the exporter, such as the number of
+    /// worker threads and tasks
+    #[arg(long)]
+    runtime_metrics: bool,
+}
+
+impl NwsExporterApplication {
+    /// Build the exporter config from command line arguments, stations set in the environment,
+    /// and the contents of the config file
+    fn config(&self, env: Option<&str>, file: FileConfig) -> Config {
+        Config {
+            stations: configured_stations(&self.station, env, &file.stations),
+            location: self
+                .latitude
+                .zip(self.longitude)
+                .map(|(lat, lon)| Coordinates::new(lon, lat)),
+            api: ApiConfig {
+                url: self.api_url.clone(),
+                timeout_millis: self.timeout_millis,
+                max_redirects: self.max_redirects,
+                follow_redirects: !self.no_follow_redirects,
+                max_body_bytes: self.max_body_bytes,
+                max_attempts: self.max_attempts,
+                fixture_files: self.fixture_file.clone(),
+            },
+            update: UpdateConfig {
+                refresh_secs: self.refresh_secs,
+                refresh_cooldown_secs: self.refresh_cooldown_secs,
+                stale_after_failures: self.stale_after_failures,
+                max_observation_age_secs: self.max_observation_age_secs,
+                future_tolerance_secs: self.future_tolerance_secs,
+                reject_future_observations: self.reject_future_observations,
+                station_label: self.station_label_format,
+                metadata_refresh_secs: self.metadata_refresh_secs,
+                expose_observation_id: self.expose_observation_id,
+                forecast_periods: self.forecast_periods,
+                forecast_refresh_secs: self.forecast_refresh_secs,
+                alert_zones: self.alert_zones.iter().map(|z| z.trim().to_uppercase()).collect(),
+                alert_refresh_secs: self.alert_refresh_secs,
+                station_alerts: self.alerts,
+                backfill_hours: self.backfill_hours,
+            },
+            web: WebConfig {
+                bind: self.bind.clone(),
+                runtime_metrics: self.runtime_metrics,
+            },
+            output: OutputConfig {
+                value_precision: self.value_precision,
+                compute_derived: self.compute_derived,
+                clamp_visibility: self.clamp_visibility,
+                reject_inconsistent: self.reject_inconsistent,
+                qc_accept: self.qc_accept.clone(),
+                limits: file.limits,
+            },
+            reporting: ReportingConfig {
+                flatline_secs: self.flatline_secs,
+                state_file: self.state_file.clone(),
+            },
+            cold_risk: file.cold_risk,
+        }
+    }
 }
 
 #[tokio::main]
@@ -83,57 +323,271 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     )
     .expect("failed to set tracing subscriber");
 
-    let timeout = Duration::from_millis(opts.timeout_millis);
-    let http_client = Client::builder().timeout(timeout).build().unwrap_or_else(|e| {
-        tracing::error!(message = "unable to initialize HTTP client", error = %e);
-        process::exit(1)
-    });
+    let file = opts
+        .config
+        .as_ref()
+        .map(FileConfig::load)
+        .transpose()
+        .unwrap_or_else(|e| {
+            tracing::error!(message = "unable to load config file", error = %e);
+            process::exit(1)
+        })
+        .unwrap_or_default();
 
-    let client = NwsClient::new(http_client, &opts.api_url).unwrap_or_else(|e| {
-        tracing::error!(message = "unable to initialize NWS client", error = %e);
-        process::exit(1)
-    });
+    let config = opts.config(std::env::var(STATIONS_ENV_VAR).ok().as_deref(), file);
+    if let Err(e) = config.validate() {
+        tracing::error!(message = "invalid configuration", error = %e, env_var = STATIONS_ENV_VAR);
+        process::exit(1);
+    }
+
+    let redirects = if config.api.follow_redirects {
+        Policy::limited(config.api.max_redirects)
+    } else {
+        Policy::none()
+    };
+
+    let http_client = Client::builder()
+        .timeout(config.api.timeout())
+        .redirect(redirects)
+        .build()
+        .unwrap_or_else(|e| {
+            tracing::error!(message = "unable to initialize HTTP client", error = %e);
+            process::exit(1)
+        });
 
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
     let mut registry = <Registry>::default();
-    let metrics = ForecastMetrics::new(&mut registry);
-    let update = UpdateTask::new(opts.station, metrics, client, Duration::from_secs(opts.refresh_secs));
+    let (stations, client): (Vec<StationConfig>, Box<dyn WeatherClient>) = if config.api.fixture_files.is_empty() {
+        let client = NwsClient::new(http_client, &config.api.url)
+            .unwrap_or_else(|e| {
+                tracing::error!(message = "unable to initialize NWS client", error = %e);
+                process::exit(1)
+            })
+            .with_max_body_bytes(config.api.max_body_bytes)
+            .with_retry(RetryPolicy::new(config.api.max_attempts, config.api.timeout()))
+            .with_metrics(ClientMetrics::new(&mut registry, config.api.timeout()));
+        let mut stations = config.stations.clone();
+        if let Some(location) = config.location {
+            // Discovery failing is treated the same as an invalid station since the user
+            // asked for observations for the location.
+            let station = match discover_station(&client, location).await {
+                Ok(Some(s)) => s,
+                Ok(None) => {
+                    tracing::error!(
+                        message = "no stations found for location",
+                        latitude = location.latitude,
+                        longitude = location.longitude
+                    );
+                    process::exit(1)
+                }
+                Err(e) => {
+                    tracing::error!(message = "failed to find station for location", error = %e);
+                    process::exit(1)
+                }
+            };
+            tracing::info!(message = "discovered nearest station", station_id = %station.id, latitude = location.latitude, longitude = location.longitude);
+            stations = StationConfig::normalize(stations.into_iter().chain(Some(station)).collect());
+        }
+        (stations, Box::new(client))
+    } else {
+        let client = FixtureClient::load(&config.api.fixture_files).unwrap_or_else(|e| {
+            tracing::error!(message = "unable to load fixture files", error = %e);
+            process::exit(1)
+        });
+        let stations = client
+            .stations()
+            .iter()
+            .map(|id| StationConfig::new(id.as_str(), StationSource::Fixture))
+            .collect();
+        (stations, Box::new(client))
+    };
+
+    for s in stations.iter() {
+        tracing::info!(message = "configured station", station_id = %s.id, source = %s.source);
+    }
+
+    let metrics = ForecastMetrics::new(&mut registry, &config.output, &config.cold_risk);
+    let exporter_metrics = ExporterMetrics::new(&mut registry);
+    let warnings = WarnOnce::new(&mut registry, WARN_SUMMARY_INTERVAL).with_clock(clock.clone());
+    let series = SeriesMetrics::new(&mut registry);
+    let reporting = ReportingSink::new(&mut registry, &config.reporting).with_clock(clock.clone());
+    let http_metrics = HttpMetrics::new(&mut registry);
+    let alert_metrics = config.update.alerts_enabled().then(|| AlertMetrics::new(&mut registry));
+    let forecast_metrics = (config.update.forecast_periods > 0)
+        .then(|| ForecastPeriodMetrics::new(&mut registry, config.update.forecast_periods, &config.output));
+    registry.register_collector(Box::new(ProcessCollector::new()));
+    register_config_info(&mut registry, &config, stations.len());
+    let runtime_metrics = config
+        .web
+        .runtime_metrics
+        .then(|| RuntimeMetrics::new(&mut registry, Handle::current()));
+
+    // All metrics must be registered before this point since the registry can't be
+    // modified once it's shared between the HTTP server and update task.
+    let registry = Arc::new(registry);
+    let mut sinks: Vec<Box<dyn OutputSink>> = vec![
+        Box::new(metrics),
+        Box::new(SeriesCountSink::new(series, registry.clone())),
+        Box::new(reporting),
+    ];
+    if let Some(m) = forecast_metrics {
+        sinks.push(Box::new(m));
+    }
+    if let Some(m) = alert_metrics {
+        sinks.push(Box::new(m));
+    }
+
+    let mut update =
+        UpdateTask::new(&config.update, stations, sinks, exporter_metrics, warnings, client).with_clock(clock.clone());
 
     // Make an initial request to fetch station information. This allows us to verify that the
     // station the user provided is valid and the API is available before starting the HTTP server
     // and running indefinitely.
     if let Err(e) = update.initialize().await {
-        tracing::error!(message = "failed to fetch initial station information", error = %e);
+        tracing::error!(message = "failed to fetch initial station or alert zone information", error = %e);
         process::exit(1);
     }
 
-    tokio::spawn(update.run());
+    let (refresh, refresh_rx) = RefreshTrigger::new(&config.update);
+    let refresh = refresh.with_clock(clock.clone());
+    let state = request_state(&mut update, registry, refresh, http_metrics, clock.clone());
+    update.backfill().await;
+
+    tokio::spawn(update.run(refresh_rx));
+    if let Some(m) = runtime_metrics {
+        tokio::spawn(m.run(RUNTIME_METRICS_INTERVAL));
+    }
+
+    let app = app(state);
 
-    let state = Arc::new(RequestState { registry });
-    let app = Router::new()
+    // Every server shares the same router and shuts down when the exporter receives
+    // SIGTERM or SIGINT. Failing to bind to any of the addresses is fatal.
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let servers = start_servers(&config.web.bind, app, shutdown_rx).unwrap_or_else(|(addr, e)| {
+        tracing::error!(message = "error starting server", address = %addr, err = %e);
+        process::exit(1)
+    });
+
+    // Wait for either SIGTERM or SIGINT to shutdown
+    tokio::select! {
+        _ = sigterm() => {}
+        _ = sigint() => {}
+    }
+
+    let _ = shutdown_tx.send(());
+    for (_, server) in servers {
+        server.await??;
+    }
+
+    tracing::info!("server shutdown");
+    Ok(())
+}
+
+/// Create the state shared by HTTP handlers for an update task that has been initialized.
+///
+/// Stations are identified the same way the update task identifies them, by the ID the API
+/// uses for each station rather than how it was configured. A sink that records successful
+/// fetches for each station is added to the update task for the freshness headers.
+fn request_state(
+    update: &mut UpdateTask,
+    registry: Arc<Registry>,
+    refresh: RefreshTrigger,
+    metrics: HttpMetrics,
+    clock: Arc<dyn Clock>,
+) -> Arc<RequestState> {
+    let stations = update.stations().to_vec();
+    let status = Arc::new(StationStatus::new(&stations));
+    update.add_sink(Box::new(StatusSink::new(status.clone()).with_clock(clock)));
+
+    Arc::new(RequestState {
+        registry,
+        stations,
+        refresh,
+        status,
+        metrics,
+    })
+}
+
+/// Routes served by the exporter, all sharing `state`.
+fn app(state: Arc<RequestState>) -> Router {
+    Router::new()
         .route("/metrics", get(nws_exporter::http::text_metrics_handler))
+        .route("/status", get(nws_exporter::http::status_handler))
+        .route("/api/v1/refresh", post(nws_exporter::http::refresh_handler))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            nws_exporter::http::track_metrics,
+        ))
         .layer(TraceLayer::new_for_http())
-        .with_state(state.clone());
-
-    let server = axum::Server::try_bind(&opts.bind)
-        .map(|s| {
-            s.serve(app.into_make_service()).with_graceful_shutdown(async {
-                // Wait for either SIGTERM or SIGINT to shutdown
-                tokio::select! {
-                    _ = sigterm() => {}
-                    _ = sigint() => {}
-                }
-            })
-        })
-        .unwrap_or_else(|e| {
-            tracing::error!(message = "error starting server", address = %opts.bind, err = %e);
-            process::exit(1)
+        .with_state(state)
+}
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// HTTP server started by `start_servers`, running until shutdown.
+type ServerHandle = JoinHandle<Result<(), BoxError>>;
+
+/// Start a server for each address, all sharing the same router and stopping once `shutdown`
+/// changes. Every address is bound before any server starts so that nothing is served if one of
+/// them can't be bound, in which case that address is returned with the error. Each server is
+/// returned with the address it's listening on, which differs from the configured address when
+/// the port is 0.
+fn start_servers(
+    addrs: &[SocketAddr],
+    app: Router,
+    shutdown: watch::Receiver<()>,
+) -> Result<Vec<(SocketAddr, ServerHandle)>, (SocketAddr, BoxError)> {
+    let mut servers = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        let mut shutdown = shutdown.clone();
+        let server = axum::Server::try_bind(addr)
+            .map_err(|e| (*addr, e.into()))?
+            .serve(app.clone().into_make_service());
+        let local = server.local_addr();
+        let server = server.with_graceful_shutdown(async move {
+            let _ = shutdown.changed().await;
         });
 
-    tracing::info!(message = "starting server", address = %opts.bind);
-    server.await.unwrap();
+        servers.push((local, server));
+    }
 
-    tracing::info!("server shutdown");
-    Ok(())
+    Ok(servers
+        .into_iter()
+        .map(|(addr, server)| {
+            tracing::info!(message = "starting server", address = %addr);
+            let handle: ServerHandle = tokio::spawn(async move { server.await.map_err(Into::into) });
+            (addr, handle)
+        })
+        .collect())
+}
+
+/// Find the station nearest to a location, or `None` if the API doesn't know of any.
+async fn discover_station(client: &NwsClient, location: Coordinates) -> Result<Option<StationConfig>, ClientError> {
+    let stations = client
+        .stations_for_point(location.latitude, location.longitude, Trigger::Scheduled)
+        .await?;
+    Ok(stations
+        .first()
+        .map(|s| StationConfig::new(s.properties.station_identifier.as_str(), StationSource::Discovered)))
+}
+
+/// Combine stations from command line arguments, the environment, and the config file, tagging
+/// each with its source. Stations are normalized and duplicates are removed, preferring the
+/// source with the highest precedence.
+fn configured_stations(args: &[String], env: Option<&str>, file: &[String]) -> Vec<StationConfig> {
+    let from_args = args
+        .iter()
+        .map(|id| StationConfig::new(id.as_str(), StationSource::Cli));
+    let from_env = env
+        .unwrap_or_default()
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|id| !id.is_empty())
+        .map(|id| StationConfig::new(id, StationSource::Env));
+    let from_file = file
+        .iter()
+        .map(|id| StationConfig::new(id.as_str(), StationSource::Config));
+
+    StationConfig::normalize(from_args.chain(from_env).chain(from_file).collect())
 }
 
 async fn sigint() -> io::Result<()> {
@@ -154,64 +608,327 @@ async fn sigterm() -> io::Result<()> {
     std::future::pending::<io::Result<()>>().await
 }
 
-/// Task for periodically updating forecast metrics for multiple stations
-///
-/// Perform one-time initialization of station metadata metrics and periodically
-/// update the forecast metrics for a list of stations until this exporter is
-/// stopped.
-struct UpdateTask {
-    stations: Vec<String>,
-    metrics: ForecastMetrics,
-    client: NwsClient,
-    interval: Duration,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_stations_sources() {
+        let stations = configured_stations(&["kbos".to_owned()], Some("KJFK, KLGA"), &["KORD".to_owned()]);
+
+        assert_eq!(
+            vec![
+                StationConfig::new("KBOS", StationSource::Cli),
+                StationConfig::new("KJFK", StationSource::Env),
+                StationConfig::new("KLGA", StationSource::Env),
+                StationConfig::new("KORD", StationSource::Config),
+            ],
+            stations
+        );
+    }
+
+    #[test]
+    fn test_configured_stations_precedence() {
+        let stations = configured_stations(
+            &["KBOS".to_owned()],
+            Some("kbos,KJFK"),
+            &["KJFK".to_owned(), "KBOS".to_owned()],
+        );
+
+        assert_eq!(
+            vec![
+                StationConfig::new("KBOS", StationSource::Cli),
+                StationConfig::new("KJFK", StationSource::Env),
+            ],
+            stations
+        );
+    }
+
+    #[test]
+    fn test_configured_stations_empty_env() {
+        let stations = configured_stations(&[], Some(" , "), &["KBOS".to_owned()]);
+        assert_eq!(vec![StationConfig::new("KBOS", StationSource::Config)], stations);
+
+        let stations = configured_stations(&[], None, &[]);
+        assert!(stations.is_empty());
+    }
+
+    fn metrics_app() -> Router {
+        Router::new().route("/metrics", get(|| async { "nws_up 1\n" }))
+    }
+
+    #[tokio::test]
+    async fn test_start_servers_multiple() {
+        let addrs: Vec<SocketAddr> = vec!["127.0.0.1:0".parse().unwrap(), "127.0.0.1:0".parse().unwrap()];
+        let (shutdown_tx, shutdown_rx) = watch::channel(());
+        let servers = start_servers(&addrs, metrics_app(), shutdown_rx).unwrap();
+        assert_eq!(2, servers.len());
+        assert_ne!(servers[0].0, servers[1].0);
 
-impl UpdateTask {
-    fn new(stations: Vec<String>, metrics: ForecastMetrics, client: NwsClient, interval: Duration) -> Self {
-        Self {
-            stations,
-            metrics,
-            client,
-            interval,
+        for (addr, _) in servers.iter() {
+            let body = reqwest::get(format!("http://{}/metrics", addr))
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+            assert_eq!("nws_up 1\n", body);
+        }
+
+        // Every server stops on shutdown
+        shutdown_tx.send(()).unwrap();
+        for (_, server) in servers {
+            server.await.unwrap().unwrap();
         }
     }
 
-    /// Set station metadata metrics or return an error if station metadata could not be fetched
-    async fn initialize(&self) -> Result<(), ClientError> {
-        for id in self.stations.iter() {
-            let station = self
-                .client
-                .station(id)
-                .instrument(tracing::span!(Level::DEBUG, "nws_station"))
-                .await?;
-            self.metrics.station(&station);
+    #[tokio::test]
+    async fn test_start_servers_bind_failure() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let taken_addr = taken.local_addr().unwrap();
+        let addrs = vec!["127.0.0.1:0".parse().unwrap(), taken_addr];
+        let (_shutdown_tx, shutdown_rx) = watch::channel(());
+
+        match start_servers(&addrs, metrics_app(), shutdown_rx) {
+            Err((addr, _)) => assert_eq!(taken_addr, addr),
+            Ok(_) => panic!("expected {} to fail to bind", taken_addr),
         }
+    }
 
-        Ok(())
-    }
-
-    /// Update station forecast metrics for all stations in a loop forever, logging any errors
-    async fn run(self) -> ! {
-        let mut interval = tokio::time::interval(self.interval);
-
-        loop {
-            let _ = interval.tick().await;
-            for id in self.stations.iter() {
-                match self
-                    .client
-                    .observation(id)
-                    .instrument(tracing::span!(Level::DEBUG, "nws_observation"))
-                    .await
-                {
-                    Ok(obs) => {
-                        self.metrics.observation(&obs);
-                        tracing::info!(message = "fetched new forecast", station_id = %id, observation = %obs.id);
-                    }
-                    Err(e) => {
-                        tracing::error!(message = "failed to fetch forecast", station_id = %id, error = %e);
+    #[test]
+    fn test_location_args() {
+        let app = NwsExporterApplication::try_parse_from([
+            "nws_exporter",
+            "--latitude",
+            "42.36",
+            "--longitude",
+            "-71.01",
+            "KJFK",
+        ])
+        .unwrap();
+        let config = app.config(None, FileConfig::default());
+        assert_eq!(Some(Coordinates::new(-71.01, 42.36)), config.location);
+        assert_eq!(vec![StationConfig::new("KJFK", StationSource::Cli)], config.stations);
+
+        // Without any stations the location is enough on its own
+        let app =
+            NwsExporterApplication::try_parse_from(["nws_exporter", "--latitude", "42.36", "--longitude", "-71.01"])
+                .unwrap();
+        let config = app.config(None, FileConfig::default());
+        assert!(config.stations.is_empty());
+        config.validate().unwrap();
+
+        // Both coordinates are required
+        assert!(NwsExporterApplication::try_parse_from(["nws_exporter", "--latitude", "42.36"]).is_err());
+        assert!(NwsExporterApplication::try_parse_from(["nws_exporter", "--longitude", "-71.01"]).is_err());
+    }
+
+    /// API with a single point whose list of stations is `stations`.
+    fn point_app(stations: serde_json::Value) -> Router {
+        Router::new()
+            .route(
+                "/points/42.36,-71.01",
+                get(|axum::extract::Host(host): axum::extract::Host| async move {
+                    axum::Json(serde_json::json!({
+                        "id": "https://api.weather.gov/points/42.36,-71.01",
+                        "type": "Feature",
+                        "geometry": null,
+                        "properties": {
+                            "observationStations": format!("http://{}/gridpoints/BOX/71,90/stations", host),
+                        },
+                    }))
+                }),
+            )
+            .route(
+                "/gridpoints/BOX/71,90/stations",
+                get(move || async move { axum::Json(stations) }),
+            )
+    }
+
+    /// Client for an API served from `app`.
+    fn point_client(app: Router) -> NwsClient {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+        NwsClient::new(Client::new(), &format!("http://{}", addr)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_discover_station() {
+        let location = Coordinates::new(-71.01, 42.36);
+        let stations: serde_json::Value =
+            serde_json::from_str(include_str!("../../ext/fixtures/stations_two.json")).unwrap();
+
+        // The first station listed is the closest
+        let client = point_client(point_app(stations));
+        let station = discover_station(&client, location).await.unwrap();
+        assert_eq!(Some(StationConfig::new("KBOS", StationSource::Discovered)), station);
+
+        let client = point_client(point_app(
+            serde_json::json!({"type": "FeatureCollection", "features": []}),
+        ));
+        assert_eq!(None, discover_station(&client, location).await.unwrap());
+
+        // Locations the API doesn't know about are errors rather than having no stations
+        let client = point_client(Router::new());
+        let err = discover_station(&client, location).await.unwrap_err();
+        assert!(
+            matches!(err, ClientError::InvalidPoint(ref p) if p == "42.36,-71.01"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_alert_zone_args() {
+        let app = NwsExporterApplication::try_parse_from([
+            "nws_exporter",
+            "--alert-zone",
+            "maz015",
+            "--alert-zone",
+            " MAC025 ",
+            "KBOS",
+        ])
+        .unwrap();
+        let config = app.config(None, FileConfig::default());
+        assert_eq!(
+            vec!["MAZ015".to_owned(), "MAC025".to_owned()],
+            config.update.alert_zones
+        );
+        assert!(config.update.alert_interval().is_some());
+        config.validate().unwrap();
+
+        let app = NwsExporterApplication::try_parse_from(["nws_exporter", "--alert-zone", "Boston", "KBOS"]).unwrap();
+        assert!(app.config(None, FileConfig::default()).validate().is_err());
+    }
+
+    #[test]
+    fn test_backfill_args() {
+        let app = NwsExporterApplication::try_parse_from(["nws_exporter", "KBOS"]).unwrap();
+        assert_eq!(None, app.config(None, FileConfig::default()).update.backfill());
+
+        let app = NwsExporterApplication::try_parse_from(["nws_exporter", "--backfill-hours", "6", "KBOS"]).unwrap();
+        let config = app.config(None, FileConfig::default());
+        assert_eq!(Some(chrono::Duration::hours(6)), config.update.backfill());
+        config.validate().unwrap();
+
+        let app = NwsExporterApplication::try_parse_from(["nws_exporter", "--backfill-hours", "25", "KBOS"]).unwrap();
+        assert!(app.config(None, FileConfig::default()).validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_app_canonical_stations() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // The configured ID is answered by the API with a different, canonical, ID
+        let observations = Arc::new(AtomicUsize::new(0));
+        let count = observations.clone();
+        let api = Router::new()
+            .route(
+                "/stations/KOLD",
+                get(|| async {
+                    axum::Json(serde_json::json!({
+                        "id": "https://api.weather.gov/stations/KBOS",
+                        "type": "Feature",
+                        "geometry": null,
+                        "properties": {
+                            "@id": "https://api.weather.gov/stations/KBOS",
+                            "@type": "wx:ObservationStation",
+                            "elevation": {"unitCode": "wmoUnit:m", "value": 9},
+                            "stationIdentifier": "KBOS",
+                            "name": "Boston, Logan International Airport",
+                            "timeZone": "America/New_York",
+                        },
+                    }))
+                }),
+            )
+            .route(
+                "/stations/KBOS/observations/latest",
+                get(move || async move {
+                    count.fetch_add(1, Ordering::AcqRel);
+                    (
+                        [(axum::http::header::CONTENT_TYPE, "application/geo+json")],
+                        include_str!("../../ext/fixtures/KBOS.json"),
+                    )
+                }),
+            );
+
+        let config = UpdateConfig::default();
+        let mut registry = Registry::default();
+        let exporter_metrics = ExporterMetrics::new(&mut registry);
+        let warnings = WarnOnce::new(&mut registry, WARN_SUMMARY_INTERVAL);
+        let http_metrics = HttpMetrics::new(&mut registry);
+        let mut update = UpdateTask::new(
+            &config,
+            vec![StationConfig::new("KOLD", StationSource::Cli)],
+            Vec::new(),
+            exporter_metrics,
+            warnings,
+            Box::new(point_client(api)),
+        );
+        update.initialize().await.unwrap();
+
+        let (refresh, refresh_rx) = RefreshTrigger::new(&config);
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        let state = request_state(&mut update, Arc::new(registry), refresh, http_metrics, clock);
+        tokio::spawn(update.run(refresh_rx));
+
+        let (_shutdown_tx, shutdown_rx) = watch::channel(());
+        let servers = start_servers(&["127.0.0.1:0".parse().unwrap()], app(state), shutdown_rx).unwrap();
+        let base = format!("http://{}", servers[0].0);
+        let http = Client::new();
+        let wait_for = |n: usize| {
+            let observations = observations.clone();
+            async move {
+                for _ in 0..100 {
+                    if observations.load(Ordering::Acquire) >= n {
+                        return;
                     }
+                    tokio::time::sleep(Duration::from_millis(20)).await;
                 }
+                panic!("expected {} observation requests", n);
             }
+        };
+
+        let status: serde_json::Value = http
+            .get(format!("{}/status", base))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(serde_json::json!([{"id": "KBOS", "source": "cli"}]), status["stations"]);
+
+        // Every station has been fetched once the first refresh completes
+        wait_for(1).await;
+        let mut oldest = None;
+        for _ in 0..100 {
+            let res = http.get(format!("{}/metrics", base)).send().await.unwrap();
+            oldest = res.headers().get("x-nws-oldest-station-update").cloned();
+            if oldest.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
         }
+        assert!(oldest.is_some());
+
+        // Refreshes are requested with the canonical ID, the configured one isn't known
+        let res = http
+            .post(format!("{}/api/v1/refresh", base))
+            .json(&serde_json::json!({"stations": ["KOLD"]}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(reqwest::StatusCode::BAD_REQUEST, res.status());
+
+        let res = http
+            .post(format!("{}/api/v1/refresh", base))
+            .json(&serde_json::json!({"stations": ["kbos"]}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(reqwest::StatusCode::ACCEPTED, res.status());
+        wait_for(2).await;
     }
 }
@@ -16,29 +16,137 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+use crate::config::{DEFAULT_MAX_BODY_BYTES, DEFAULT_TIMEOUT_MILLIS};
+use crate::geo::Geometry;
+use crate::interval::{self, Interval};
+use crate::metrics::ClientMetrics;
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
-use reqwest::header::{ACCEPT, USER_AGENT};
+use reqwest::header::{HeaderMap, HeaderName, ACCEPT, DATE, LOCATION, USER_AGENT};
 use reqwest::{Client, Response, StatusCode, Url};
-use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::hash_map::RandomState;
 use std::error;
 use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
 
 /// Error resulting from setup of or calls to an `NwsClient` instance.
 #[derive(Debug)]
 pub enum ClientError {
     Internal(reqwest::Error),
+    Timeout(reqwest::Error),
+    Connect(reqwest::Error),
     Initialization(String),
     InvalidStation(String),
-    Unexpected(StatusCode, Url),
+    InvalidPoint(String),
+    InvalidZone(String),
+    InvalidLink(String),
+    NotFound(Url),
+    NoObservations(String),
+    Unsupported(&'static str),
+    Redirect(StatusCode, Url, Option<String>),
+    Unexpected(StatusCode, Url, Box<ResponseIds>),
+    BodyTooLarge(Url, u64),
+    Decode(Url, serde_json::Error),
 }
 
 impl fmt::Display for ClientError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Internal(e) => write!(f, "{}", e),
+            Self::Timeout(e) => write!(f, "timeout: {}", e),
+            Self::Connect(e) => write!(f, "connection error: {}", e),
             Self::Initialization(msg) => write!(f, "initialization error: {}", msg),
             Self::InvalidStation(s) => write!(f, "invalid station {}", s),
-            Self::Unexpected(status, url) => write!(f, "unexpected status {} for {}", status, url),
+            Self::InvalidPoint(p) => write!(f, "invalid point {}", p),
+            Self::InvalidZone(z) => write!(f, "invalid zone {}", z),
+            Self::InvalidLink(l) => write!(f, "invalid link {}", l),
+            Self::NotFound(url) => write!(f, "not found: {}", url),
+            Self::NoObservations(s) => write!(f, "no observations for station {}", s),
+            Self::Unsupported(what) => write!(f, "{} not supported by this client", what),
+            Self::Redirect(status, url, location) => write!(
+                f,
+                "unexpected redirect {} for {} to {}",
+                status,
+                url,
+                location.as_deref().unwrap_or("unknown location")
+            ),
+            Self::Unexpected(status, url, ids) if ids.is_empty() => {
+                write!(f, "unexpected status {} for {}", status, url)
+            }
+            Self::Unexpected(status, url, ids) => write!(f, "unexpected status {} for {} ({})", status, url, ids),
+            Self::BodyTooLarge(url, max) => write!(f, "response body for {} larger than {} bytes", url, max),
+            Self::Decode(url, e) => write!(f, "cannot decode response for {}: {}", url, e),
+        }
+    }
+}
+
+impl ClientError {
+    /// Return true if this error means the API itself is unavailable (the request couldn't be
+    /// made or the server returned a 5xx status) rather than a problem with a particular request.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            Self::Internal(e) => !e.is_decode() && !e.is_builder(),
+            Self::Timeout(_) | Self::Connect(_) => true,
+            Self::Unexpected(status, ..) => status.is_server_error(),
+            _ => false,
+        }
+    }
+
+    /// Return true if the request might succeed if it were made again: the request timed out,
+    /// the connection failed or was reset, or the server was overloaded (429) or returned a 5xx
+    /// status. Other errors, including 404 and other 4xx statuses, are returned the same way
+    /// every time.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Internal(e) => e.is_request(),
+            Self::Timeout(_) | Self::Connect(_) => true,
+            Self::Unexpected(status, ..) => *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+            _ => false,
+        }
+    }
+
+    /// Short, stable description of the kind of error suitable for use as a metric label.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::Internal(_) => "internal",
+            Self::Timeout(_) => "timeout",
+            Self::Connect(_) => "connect",
+            Self::Initialization(_) => "initialization",
+            Self::InvalidStation(_) => "invalid_station",
+            Self::InvalidPoint(_) => "invalid_point",
+            Self::InvalidZone(_) => "invalid_zone",
+            Self::InvalidLink(_) => "invalid_link",
+            Self::NotFound(_) => "not_found",
+            Self::NoObservations(_) => "no_observations",
+            Self::Unsupported(_) => "unsupported",
+            Self::Redirect(..) => "redirect",
+            Self::Unexpected(..) => "unexpected_status",
+            Self::BodyTooLarge(..) => "body_too_large",
+            Self::Decode(..) => "decode",
+        }
+    }
+
+    /// HTTP status code returned by the API if the error was caused by the status.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Redirect(status, ..) | Self::Unexpected(status, ..) => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout(e)
+        } else if e.is_connect() {
+            Self::Connect(e)
+        } else {
+            Self::Internal(e)
         }
     }
 }
@@ -46,22 +154,261 @@ impl fmt::Display for ClientError {
 impl error::Error for ClientError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
-            Self::Internal(e) => Some(e),
+            Self::Internal(e) | Self::Timeout(e) | Self::Connect(e) => Some(e),
+            Self::Decode(_, e) => Some(e),
             _ => None,
         }
     }
 }
 
+/// Identifiers the API returns in response headers, useful for finding a particular request
+/// when reporting a problem to NWS. Each is `None` if the header wasn't present.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponseIds {
+    pub correlation_id: Option<String>,
+    pub request_id: Option<String>,
+    pub server_id: Option<String>,
+}
+
+impl ResponseIds {
+    const CORRELATION_ID: HeaderName = HeaderName::from_static("x-correlation-id");
+    const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+    const SERVER_ID: HeaderName = HeaderName::from_static("x-server-id");
+
+    /// Extract identifiers from the headers of a response, ignoring any that aren't valid strings.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name: &HeaderName| headers.get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_owned());
+        Self {
+            correlation_id: get(&Self::CORRELATION_ID),
+            request_id: get(&Self::REQUEST_ID),
+            server_id: get(&Self::SERVER_ID),
+        }
+    }
+
+    /// Return true if the response didn't include any identifiers.
+    pub fn is_empty(&self) -> bool {
+        self.correlation_id.is_none() && self.request_id.is_none() && self.server_id.is_none()
+    }
+}
+
+impl fmt::Display for ResponseIds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids = [
+            ("correlation_id", &self.correlation_id),
+            ("request_id", &self.request_id),
+            ("server_id", &self.server_id),
+        ];
+
+        let mut first = true;
+        for (name, id) in ids {
+            if let Some(id) = id {
+                if !first {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}={}", name, id)?;
+                first = false;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How requests that fail with a retryable error (see `ClientError::is_retryable()`) are
+/// made again.
+///
+/// Each attempt is limited to `timeout` and all attempts for a request, including the time
+/// spent waiting between them, are limited to `timeout` times `max_attempts`. The wait before
+/// each retry doubles, starting from 250ms up to a maximum of 8s, and is randomly shortened
+/// by up to half so that many stations failing at once don't all retry at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub timeout: Duration,
+}
+
+impl RetryPolicy {
+    const BASE_DELAY: Duration = Duration::from_millis(250);
+    const MAX_DELAY: Duration = Duration::from_secs(8);
+
+    pub fn new(max_attempts: u32, timeout: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            timeout,
+        }
+    }
+
+    /// Total time allowed for all attempts of a single request.
+    pub fn budget(&self) -> Duration {
+        self.timeout.saturating_mul(self.max_attempts)
+    }
+
+    /// Time to wait before making the given attempt again, starting from 1 for the first
+    /// attempt.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = Self::BASE_DELAY
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(Self::MAX_DELAY);
+        let jitter = RandomState::new().build_hasher().finish() % (delay.as_millis() as u64 / 2 + 1);
+        delay - Duration::from_millis(jitter)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(1, Duration::from_millis(DEFAULT_TIMEOUT_MILLIS))
+    }
+}
+
+/// What caused a request to be made, used to tell scheduled traffic apart from
+/// user-triggered traffic in metrics and logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    Scheduled,
+    Scrape,
+    Probe,
+    Manual,
+    Backfill,
+}
+
+impl Trigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Scheduled => "scheduled",
+            Self::Scrape => "scrape",
+            Self::Probe => "probe",
+            Self::Manual => "manual",
+            Self::Backfill => "backfill",
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Source of station metadata and observations.
+///
+/// This is implemented by `NwsClient` for fetching from the API and by other sources of
+/// the same data such as fixture files.
+#[async_trait]
+pub trait WeatherClient: Send + Sync {
+    /// Fetch station metadata for the given station ID.
+    async fn station(&self, station: &str, trigger: Trigger) -> Result<Station, ClientError>;
+
+    /// Fetch the most recent observation for the given station ID.
+    async fn observation(&self, station: &str, trigger: Trigger) -> Result<Observation, ClientError>;
+
+    /// Fetch all observations made since `start` for the given station ID. Clients that don't
+    /// support this return the `ClientError::Unsupported` error variant.
+    async fn observations_since(
+        &self,
+        _station: &str,
+        _start: DateTime<Utc>,
+        _trigger: Trigger,
+    ) -> Result<ObservationCollection, ClientError> {
+        Err(ClientError::Unsupported("observation history"))
+    }
+
+    /// Fetch metadata for a latitude and longitude. Clients that don't support this return
+    /// the `ClientError::Unsupported` error variant.
+    async fn point(&self, _latitude: f64, _longitude: f64, _trigger: Trigger) -> Result<Point, ClientError> {
+        Err(ClientError::Unsupported("points"))
+    }
+
+    /// Fetch the forecast for a square of the grid of a forecast office. Clients that don't
+    /// support this return the `ClientError::Unsupported` error variant.
+    async fn forecast(
+        &self,
+        _office: &str,
+        _grid_x: i64,
+        _grid_y: i64,
+        _trigger: Trigger,
+    ) -> Result<Forecast, ClientError> {
+        Err(ClientError::Unsupported("forecasts"))
+    }
+
+    /// Fetch the hourly forecast for a square of the grid of a forecast office. Clients that
+    /// don't support this return the `ClientError::Unsupported` error variant.
+    async fn hourly_forecast(
+        &self,
+        _office: &str,
+        _grid_x: i64,
+        _grid_y: i64,
+        _trigger: Trigger,
+    ) -> Result<Forecast, ClientError> {
+        Err(ClientError::Unsupported("hourly forecasts"))
+    }
+
+    /// Fetch raw forecast data for a square of the grid of a forecast office. Clients that
+    /// don't support this return the `ClientError::Unsupported` error variant.
+    async fn gridpoint(
+        &self,
+        _office: &str,
+        _grid_x: i64,
+        _grid_y: i64,
+        _trigger: Trigger,
+    ) -> Result<Gridpoint, ClientError> {
+        Err(ClientError::Unsupported("gridpoints"))
+    }
+
+    /// Fetch metadata for a forecast or county zone. Clients that don't support this return
+    /// the `ClientError::Unsupported` error variant.
+    async fn zone(&self, _zone: &str, _trigger: Trigger) -> Result<Zone, ClientError> {
+        Err(ClientError::Unsupported("zones"))
+    }
+
+    /// Fetch active alerts for a forecast or county zone. Clients that don't support this
+    /// return the `ClientError::Unsupported` error variant.
+    async fn alerts_for_zone(&self, _zone: &str, _trigger: Trigger) -> Result<AlertCollection, ClientError> {
+        Err(ClientError::Unsupported("alerts"))
+    }
+
+    /// Fetch active alerts for a latitude and longitude. Clients that don't support this
+    /// return the `ClientError::Unsupported` error variant.
+    async fn alerts_for_point(
+        &self,
+        _latitude: f64,
+        _longitude: f64,
+        _trigger: Trigger,
+    ) -> Result<AlertCollection, ClientError> {
+        Err(ClientError::Unsupported("alerts"))
+    }
+}
+
 /// Client for fetching station metadata and forecasts using an underlying reqwest client
 #[derive(Debug)]
 pub struct NwsClient {
     client: Client,
     base_url: Url,
+    max_body_bytes: u64,
+    retry: RetryPolicy,
+    metrics: Option<ClientMetrics>,
 }
 
 impl NwsClient {
     const USER_AGENT: &'static str = "nws_exporter/0.4.0 (https://github.com/56quarters/nws_exporter)";
     const JSON_RESPONSE: &'static str = "application/geo+json";
+    const STATION_ENDPOINT: &'static str = "station";
+    const OBSERVATION_ENDPOINT: &'static str = "observation";
+    const OBSERVATIONS_ENDPOINT: &'static str = "observations";
+    const STATIONS_ENDPOINT: &'static str = "stations";
+    const POINT_ENDPOINT: &'static str = "point";
+    const FORECAST_ENDPOINT: &'static str = "forecast";
+    const HOURLY_FORECAST_ENDPOINT: &'static str = "hourly_forecast";
+    const GRIDPOINT_ENDPOINT: &'static str = "gridpoint";
+    const ALERTS_ENDPOINT: &'static str = "alerts";
+    const ZONE_ENDPOINT: &'static str = "zone";
+    /// Maximum number of decimal places the API accepts for coordinates.
+    const COORDINATE_PRECISION: usize = 4;
+    /// Number of observations to request when falling back to the list of observations. More
+    /// than one is requested since the list isn't guaranteed to be sorted.
+    const OBSERVATIONS_LIMIT: u32 = 5;
+    /// Retries aren't made with less than this much of the time for a request left since
+    /// they'd almost certainly time out.
+    const MIN_ATTEMPT_TIME: Duration = Duration::from_millis(100);
 
     /// Create a new `NwsClient` from the provided reqwest client and based URL for the
     /// API (this will almost always be "https://api.weather.gov/" in typical use).
@@ -76,61 +423,576 @@ impl NwsClient {
             base_url: base_url
                 .parse()
                 .map_err(|e| ClientError::Initialization(format!("cannot parse {}: {}", base_url, e)))?,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            retry: RetryPolicy::default(),
+            metrics: None,
         })
     }
 
+    /// Fail requests with response bodies larger than `max_body_bytes` instead of reading
+    /// the entire body into memory.
+    pub fn with_max_body_bytes(mut self, max_body_bytes: u64) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    /// Retry requests that fail with a retryable error according to `retry` instead of only
+    /// making each request once. The timeout of the policy replaces the timeout of the underlying
+    /// HTTP client when requests may be made more than once so they should be the same.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Record metrics about requests made to the API using the provided `ClientMetrics`.
+    pub fn with_metrics(mut self, metrics: ClientMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Fetch station metadata for the given station ID, returning an error if the request
     /// failed or the response couldn't be deserialized.
     ///
     /// # Errors
     ///
     /// If the provided station ID is not valid, the `ClientError::InvalidStation` error
-    /// variant will be returned. Unexpected HTTP status codes (non-200) will result in the
-    /// `ClientError::Unexpected` error variant. Any other errors from the underlying HTTP
-    /// client will result in the `ClientError::Internal` error variant.
-    pub async fn station(&self, station: &str) -> Result<Station, ClientError> {
+    /// variant will be returned. Redirects not followed by the underlying HTTP client will
+    /// result in the `ClientError::Redirect` error variant. Unexpected HTTP status codes
+    /// (non-200) will result in the `ClientError::Unexpected` error variant. Any other errors
+    /// from the underlying HTTP client will result in the `ClientError::Timeout`,
+    /// `ClientError::Connect`, or `ClientError::Internal` error variants.
+    /// Responses that are too large or can't be decoded result in the `ClientError::BodyTooLarge`
+    /// or `ClientError::Decode` error variants.
+    pub async fn station(&self, station: &str, trigger: Trigger) -> Result<Station, ClientError> {
         let station_url = self.station_url(station);
-        tracing::debug!(message = "making station information request", url = %station_url);
+        tracing::debug!(message = "making station information request", url = %station_url, trigger = %trigger);
+
+        let res = self
+            .make_request(station, Self::STATION_ENDPOINT, trigger, station_url)
+            .await?;
+        self.read_json::<Station>(res).await
+    }
+
+    /// Fetch the first page of stations matching the provided query, returning an error if the
+    /// request failed or the response couldn't be deserialized.
+    ///
+    /// Use `NwsClient::stations_page()` to fetch later pages.
+    ///
+    /// # Errors
+    ///
+    /// Errors are the same as `NwsClient::station()`.
+    pub async fn stations(&self, query: &StationsQuery, trigger: Trigger) -> Result<Vec<Station>, ClientError> {
+        Ok(self.stations_page(query, trigger).await?.features)
+    }
+
+    /// Fetch a page of stations matching the provided query along with the cursor for the next
+    /// page, if any. Set `StationsQuery::cursor` to the cursor of the previous page to fetch the
+    /// next one.
+    ///
+    /// Requests for lists of stations aren't for any particular station so they're recorded
+    /// with an empty station in metrics.
+    ///
+    /// # Errors
+    ///
+    /// If the API can't find the list (because of an invalid cursor, for example) the
+    /// `ClientError::NotFound` error variant will be returned. Other errors are the same as
+    /// `NwsClient::station()`.
+    pub async fn stations_page(
+        &self,
+        query: &StationsQuery,
+        trigger: Trigger,
+    ) -> Result<StationCollection, ClientError> {
+        let request_url = self.stations_url(query);
+        tracing::debug!(message = "making station list request", url = %request_url, trigger = %trigger);
+
+        let res = self
+            .make_request("", Self::STATIONS_ENDPOINT, trigger, request_url.clone())
+            .await
+            .map_err(|e| match e {
+                ClientError::InvalidStation(_) => ClientError::NotFound(request_url),
+                e => e,
+            })?;
+        self.read_json::<StationCollection>(res).await
+    }
+
+    /// Fetch metadata for a latitude and longitude, in degrees, including links to forecasts and
+    /// stations for the location, returning an error if the request failed or the response
+    /// couldn't be deserialized.
+    ///
+    /// Coordinates are rounded to the four decimal places the API accepts.
+    ///
+    /// # Errors
+    ///
+    /// If the coordinates are out of range or the API doesn't have data for them (they're
+    /// outside of the United States, for example) the `ClientError::InvalidPoint` error variant
+    /// will be returned. Other errors are the same as `NwsClient::station()`.
+    pub async fn point(&self, latitude: f64, longitude: f64, trigger: Trigger) -> Result<Point, ClientError> {
+        let point = format!(
+            "{},{}",
+            format_coordinate(latitude, Self::COORDINATE_PRECISION),
+            format_coordinate(longitude, Self::COORDINATE_PRECISION)
+        );
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return Err(ClientError::InvalidPoint(point));
+        }
+
+        let request_url = self.point_url(&point);
+        tracing::debug!(message = "making point request", url = %request_url, trigger = %trigger);
+
+        let res = self
+            .make_request("", Self::POINT_ENDPOINT, trigger, request_url)
+            .await
+            .map_err(|e| match e {
+                ClientError::InvalidStation(_) => ClientError::InvalidPoint(point),
+                e => e,
+            })?;
+        self.read_json::<Point>(res).await
+    }
+
+    /// Fetch stations that observations can be fetched from for a latitude and longitude, in
+    /// degrees, closest first.
+    ///
+    /// Stations are found by fetching metadata for the point and then the list of stations it
+    /// links to.
+    ///
+    /// # Errors
+    ///
+    /// Errors are the same as `NwsClient::point()`, including when the list of stations for the
+    /// point doesn't exist. If the link to the list of stations isn't a valid URL, the
+    /// `ClientError::InvalidLink` error variant will be returned.
+    pub async fn stations_for_point(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        trigger: Trigger,
+    ) -> Result<Vec<Station>, ClientError> {
+        let point = self.point(latitude, longitude, trigger).await?;
+        let request_url = link(&point.properties.observation_stations)?;
+        tracing::debug!(message = "making station list request", url = %request_url, trigger = %trigger);
+
+        let res = self
+            .make_request("", Self::STATIONS_ENDPOINT, trigger, request_url)
+            .await
+            .map_err(|e| match e {
+                ClientError::InvalidStation(_) => ClientError::InvalidPoint(format!(
+                    "{},{}",
+                    format_coordinate(latitude, Self::COORDINATE_PRECISION),
+                    format_coordinate(longitude, Self::COORDINATE_PRECISION)
+                )),
+                e => e,
+            })?;
+        Ok(self.read_json::<StationCollection>(res).await?.features)
+    }
+
+    /// Fetch the forecast for a square of the grid of a forecast office, returning an error if
+    /// the request failed or the response couldn't be deserialized. The office and grid square
+    /// for a location are part of the metadata from `NwsClient::point()`.
+    ///
+    /// # Errors
+    ///
+    /// If the office or grid square doesn't exist, the `ClientError::InvalidPoint` error variant
+    /// will be returned. Other errors are the same as `NwsClient::station()`.
+    pub async fn forecast(
+        &self,
+        office: &str,
+        grid_x: i64,
+        grid_y: i64,
+        trigger: Trigger,
+    ) -> Result<Forecast, ClientError> {
+        let request_url = self.gridpoint_url(office, grid_x, grid_y, &["forecast"]);
+        tracing::debug!(message = "making forecast request", url = %request_url, trigger = %trigger);
+        self.gridpoint_forecast(office, grid_x, grid_y, Self::FORECAST_ENDPOINT, trigger, request_url)
+            .await
+    }
+
+    /// Fetch the hourly forecast for a square of the grid of a forecast office, returning an
+    /// error if the request failed or the response couldn't be deserialized. Hourly forecasts
+    /// have a period for each hour of the next week, starting with the current hour.
+    ///
+    /// # Errors
+    ///
+    /// Errors are the same as `NwsClient::forecast()`.
+    pub async fn hourly_forecast(
+        &self,
+        office: &str,
+        grid_x: i64,
+        grid_y: i64,
+        trigger: Trigger,
+    ) -> Result<Forecast, ClientError> {
+        let request_url = self.gridpoint_url(office, grid_x, grid_y, &["forecast", "hourly"]);
+        tracing::debug!(message = "making hourly forecast request", url = %request_url, trigger = %trigger);
+        self.gridpoint_forecast(
+            office,
+            grid_x,
+            grid_y,
+            Self::HOURLY_FORECAST_ENDPOINT,
+            trigger,
+            request_url,
+        )
+        .await
+    }
+
+    /// Fetch raw forecast data for a square of the grid of a forecast office, returning an
+    /// error if the request failed or the response couldn't be deserialized. This includes
+    /// values that aren't part of forecasts such as the amount of precipitation expected,
+    /// each as a series of values for intervals of time.
+    ///
+    /// # Errors
+    ///
+    /// Errors are the same as `NwsClient::forecast()`.
+    pub async fn gridpoint(
+        &self,
+        office: &str,
+        grid_x: i64,
+        grid_y: i64,
+        trigger: Trigger,
+    ) -> Result<Gridpoint, ClientError> {
+        let request_url = self.gridpoint_url(office, grid_x, grid_y, &[]);
+        tracing::debug!(message = "making gridpoint request", url = %request_url, trigger = %trigger);
+        let res = self
+            .gridpoint_request(office, grid_x, grid_y, Self::GRIDPOINT_ENDPOINT, trigger, request_url)
+            .await?;
+        self.read_json::<Gridpoint>(res).await
+    }
+
+    /// Fetch metadata for a forecast or county zone (e.g. `MAZ015` or `MAC025`), including its
+    /// name and state, returning an error if the request failed or the response couldn't be
+    /// deserialized. The type of zone is determined from the ID: county zones have a `C` after
+    /// the state and all others are forecast zones.
+    ///
+    /// # Errors
+    ///
+    /// If the zone doesn't exist, the `ClientError::InvalidZone` error variant will be returned.
+    /// Other errors are the same as `NwsClient::station()`.
+    pub async fn zone(&self, zone: &str, trigger: Trigger) -> Result<Zone, ClientError> {
+        let zone_type = match zone.as_bytes().get(2) {
+            Some(b'C') => "county",
+            _ => "forecast",
+        };
+
+        let request_url = self.zone_url(zone_type, zone);
+        tracing::debug!(message = "making zone request", url = %request_url, trigger = %trigger);
+
+        let res = self
+            .make_request("", Self::ZONE_ENDPOINT, trigger, request_url)
+            .await
+            .map_err(|e| match e {
+                ClientError::InvalidStation(_) => ClientError::InvalidZone(zone.to_owned()),
+                e => e,
+            })?;
+        self.read_json::<Zone>(res).await
+    }
+
+    /// Fetch alerts that are currently in effect for a forecast or county zone (e.g. `MAZ015`
+    /// or `MAC025`), returning an error if the request failed or the response couldn't be
+    /// deserialized. Zones without any alerts return an empty collection.
+    ///
+    /// # Errors
+    ///
+    /// If the zone doesn't exist, the `ClientError::InvalidZone` error variant will be returned.
+    /// Other errors are the same as `NwsClient::station()`.
+    pub async fn alerts_for_zone(&self, zone: &str, trigger: Trigger) -> Result<AlertCollection, ClientError> {
+        let request_url = self.alerts_url(&["active", "zone", zone]);
+        tracing::debug!(message = "making alerts request", url = %request_url, trigger = %trigger);
+
+        let res = self
+            .make_request("", Self::ALERTS_ENDPOINT, trigger, request_url)
+            .await
+            .map_err(|e| match e {
+                ClientError::InvalidStation(_) => ClientError::InvalidZone(zone.to_owned()),
+                e => e,
+            })?;
+        self.read_json::<AlertCollection>(res).await
+    }
+
+    /// Fetch alerts that are currently in effect for a latitude and longitude, in degrees,
+    /// returning an error if the request failed or the response couldn't be deserialized.
+    /// Locations without any alerts return an empty collection.
+    ///
+    /// Coordinates are rounded to the four decimal places the API accepts.
+    ///
+    /// # Errors
+    ///
+    /// If the coordinates are out of range the `ClientError::InvalidPoint` error variant will
+    /// be returned. Other errors are the same as `NwsClient::station()`.
+    pub async fn alerts_for_point(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        trigger: Trigger,
+    ) -> Result<AlertCollection, ClientError> {
+        let point = format!(
+            "{},{}",
+            format_coordinate(latitude, Self::COORDINATE_PRECISION),
+            format_coordinate(longitude, Self::COORDINATE_PRECISION)
+        );
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return Err(ClientError::InvalidPoint(point));
+        }
+
+        let mut request_url = self.alerts_url(&["active"]);
+        request_url.query_pairs_mut().append_pair("point", &point);
+        tracing::debug!(message = "making alerts request", url = %request_url, trigger = %trigger);
 
-        let res = self.make_request(station, station_url).await?;
-        res.json::<Station>().await.map_err(ClientError::Internal)
+        let res = self
+            .make_request("", Self::ALERTS_ENDPOINT, trigger, request_url)
+            .await
+            .map_err(|e| match e {
+                ClientError::InvalidStation(_) => ClientError::InvalidPoint(point),
+                e => e,
+            })?;
+        self.read_json::<AlertCollection>(res).await
+    }
+
+    async fn gridpoint_forecast(
+        &self,
+        office: &str,
+        grid_x: i64,
+        grid_y: i64,
+        endpoint: &'static str,
+        trigger: Trigger,
+        request_url: Url,
+    ) -> Result<Forecast, ClientError> {
+        let res = self
+            .gridpoint_request(office, grid_x, grid_y, endpoint, trigger, request_url)
+            .await?;
+        self.read_json::<Forecast>(res).await
+    }
+
+    async fn gridpoint_request(
+        &self,
+        office: &str,
+        grid_x: i64,
+        grid_y: i64,
+        endpoint: &'static str,
+        trigger: Trigger,
+        request_url: Url,
+    ) -> Result<Response, ClientError> {
+        self.make_request("", endpoint, trigger, request_url)
+            .await
+            .map_err(|e| match e {
+                ClientError::InvalidStation(_) => {
+                    ClientError::InvalidPoint(format!("{}/{},{}", office, grid_x, grid_y))
+                }
+                e => e,
+            })
     }
 
     /// Fetch the most recent forecast information for the given station ID, returning an
     /// error if the request failed or the response couldn't be deserialized.
     ///
+    /// Some stations (usually new stations and buoys) intermittently return a 404 for their
+    /// latest observation even though they have observations. When that happens, the most
+    /// recent observation from the list of observations for the station is used instead.
+    ///
     /// # Errors
     ///
     /// If the provided station ID is not valid, the `ClientError::InvalidStation` error
-    /// variant will be returned. Unexpected HTTP status codes (non-200) will result in the
-    /// `ClientError::Unexpected` error variant. Any other errors from the underlying HTTP
-    /// client will result in the `ClientError::Internal` error variant.
-    pub async fn observation(&self, station: &str) -> Result<Observation, ClientError> {
+    /// variant will be returned. Redirects not followed by the underlying HTTP client will
+    /// result in the `ClientError::Redirect` error variant. Unexpected HTTP status codes
+    /// (non-200) will result in the `ClientError::Unexpected` error variant. Any other errors
+    /// from the underlying HTTP client will result in the `ClientError::Timeout`,
+    /// `ClientError::Connect`, or `ClientError::Internal` error variants.
+    /// Responses that are too large or can't be decoded result in the `ClientError::BodyTooLarge`
+    /// or `ClientError::Decode` error variants. If the list of observations is used and is
+    /// empty, the `ClientError::NoObservations` error variant will be returned.
+    pub async fn observation(&self, station: &str, trigger: Trigger) -> Result<Observation, ClientError> {
         let request_url = self.observation_url(station);
-        tracing::debug!(message = "making latest observation request", url = %request_url);
+        tracing::debug!(message = "making latest observation request", url = %request_url, trigger = %trigger);
+
+        match self
+            .make_request(station, Self::OBSERVATION_ENDPOINT, trigger, request_url)
+            .await
+        {
+            Ok(res) => {
+                let server_time = server_time(&res);
+                let mut obs = self.read_json::<Observation>(res).await?;
+                obs.server_time = server_time;
+                Ok(obs)
+            }
+            Err(ClientError::InvalidStation(_)) => self.latest_from_observations(station, trigger).await,
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Fetch all observations for the given station ID made since `start`, returning an error
+    /// if the request failed or the response couldn't be deserialized. Observations aren't
+    /// guaranteed to be in any particular order, use `ObservationCollection::chronological()`
+    /// to sort them.
+    ///
+    /// # Errors
+    ///
+    /// Errors are the same as `NwsClient::station()`.
+    pub async fn observations_since(
+        &self,
+        station: &str,
+        start: DateTime<Utc>,
+        trigger: Trigger,
+    ) -> Result<ObservationCollection, ClientError> {
+        let start = start.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let request_url = self.observations_url(station, &[("start", &start)]);
+        tracing::debug!(message = "making observations request", url = %request_url, trigger = %trigger);
 
-        let res = self.make_request(station, request_url).await?;
-        res.json::<Observation>().await.map_err(ClientError::Internal)
+        let res = self
+            .make_request(station, Self::OBSERVATIONS_ENDPOINT, trigger, request_url)
+            .await?;
+        self.read_json::<ObservationCollection>(res).await
     }
 
-    async fn make_request<S: Into<String>>(&self, station: S, url: Url) -> Result<Response, ClientError> {
+    /// Fetch the most recent observation from the list of observations for the station.
+    async fn latest_from_observations(&self, station: &str, trigger: Trigger) -> Result<Observation, ClientError> {
+        let limit = Self::OBSERVATIONS_LIMIT.to_string();
+        let request_url = self.observations_url(station, &[("limit", &limit)]);
+        tracing::info!(message = "latest observation not found, using list of observations", station_id = %station, url = %request_url);
+        if let Some(m) = &self.metrics {
+            m.observation_fallback(station);
+        }
+
         let res = self
+            .make_request(station, Self::OBSERVATIONS_ENDPOINT, trigger, request_url)
+            .await?;
+        let server_time = server_time(&res);
+        let mut obs = self
+            .read_json::<ObservationCollection>(res)
+            .await?
+            .latest()
+            .ok_or_else(|| ClientError::NoObservations(station.to_owned()))?;
+        obs.server_time = server_time;
+        Ok(obs)
+    }
+
+    /// Make a request, retrying it according to the retry policy of this client until it
+    /// succeeds, fails with an error that isn't retryable, or runs out of attempts or time.
+    async fn make_request(
+        &self,
+        station: &str,
+        endpoint: &'static str,
+        trigger: Trigger,
+        url: Url,
+    ) -> Result<Response, ClientError> {
+        let deadline = Instant::now() + self.retry.budget();
+        let mut attempt = 1;
+        loop {
+            // Requests that are only made once use the timeout of the HTTP client as-is.
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let timeout = (self.retry.max_attempts > 1).then(|| remaining.min(self.retry.timeout));
+            let err = match self
+                .make_attempt(station, endpoint, trigger, url.clone(), timeout)
+                .await
+            {
+                Ok(res) => return Ok(res),
+                Err(e) => e,
+            };
+
+            // The delay is only known once the attempt has failed so a retry that would end
+            // after the deadline isn't made at all rather than being cut short.
+            let delay = self.retry.delay(attempt);
+            if !err.is_retryable()
+                || attempt >= self.retry.max_attempts
+                || Instant::now() + delay + Self::MIN_ATTEMPT_TIME >= deadline
+            {
+                return Err(err);
+            }
+
+            tracing::debug!(
+                message = "retrying failed request",
+                url = %url,
+                attempt = attempt,
+                delay_millis = delay.as_millis() as u64,
+                error = %err,
+            );
+            if let Some(m) = &self.metrics {
+                m.retry(station);
+            }
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn make_attempt(
+        &self,
+        station: &str,
+        endpoint: &'static str,
+        trigger: Trigger,
+        url: Url,
+        timeout: Option<Duration>,
+    ) -> Result<Response, ClientError> {
+        if let Some(m) = &self.metrics {
+            m.request(station, endpoint, trigger);
+        }
+
+        let mut req = self
             .client
             .get(url.clone())
             .header(USER_AGENT, Self::USER_AGENT)
-            .header(ACCEPT, Self::JSON_RESPONSE)
-            .send()
-            .await
-            .map_err(ClientError::Internal)?;
+            .header(ACCEPT, Self::JSON_RESPONSE);
+        if let Some(timeout) = timeout {
+            req = req.timeout(timeout);
+        }
+
+        let start = Instant::now();
+        let res = req.send().await;
+
+        if let Some(m) = &self.metrics {
+            m.duration(station, endpoint, trigger, start.elapsed());
+        }
+
+        let res = res.map_err(ClientError::from)?;
+
+        if res.url() != &url {
+            tracing::warn!(message = "request was redirected", url = %url, final_url = %res.url());
+            if let Some(m) = &self.metrics {
+                m.redirect(station);
+            }
+        }
 
         let status = res.status();
+        if let Some(m) = &self.metrics {
+            m.response(endpoint, status);
+        }
+
+        let ids = ResponseIds::from_headers(res.headers());
+        tracing::debug!(message = "received response", url = %url, status = %status, ids = %ids);
+
         if status == StatusCode::OK {
             Ok(res)
+        } else if status.is_redirection() {
+            let location = res
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_owned());
+            Err(ClientError::Redirect(status, url, location))
         } else if status == StatusCode::NOT_FOUND {
-            Err(ClientError::InvalidStation(station.into()))
+            Err(ClientError::InvalidStation(station.to_owned()))
         } else {
-            Err(ClientError::Unexpected(status, url))
+            Err(ClientError::Unexpected(status, url, Box::new(ids)))
+        }
+    }
+
+    /// Read the body of a response a chunk at a time and decode it as JSON, failing as soon as
+    /// the body is known to be larger than the configured limit.
+    async fn read_json<T: DeserializeOwned>(&self, mut res: Response) -> Result<T, ClientError> {
+        let url = res.url().clone();
+        if res
+            .content_length()
+            .map(|len| len > self.max_body_bytes)
+            .unwrap_or(false)
+        {
+            return Err(ClientError::BodyTooLarge(url, self.max_body_bytes));
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = res.chunk().await.map_err(ClientError::from)? {
+            if (body.len() + chunk.len()) as u64 > self.max_body_bytes {
+                return Err(ClientError::BodyTooLarge(url, self.max_body_bytes));
+            }
+
+            body.extend_from_slice(&chunk);
         }
+
+        serde_json::from_slice(&body).map_err(|e| ClientError::Decode(url, e))
     }
 
     fn station_url(&self, station: &str) -> Url {
@@ -147,103 +1009,834 @@ impl NwsClient {
         url
     }
 
-    fn observation_url(&self, station: &str) -> Url {
-        let mut url = self.station_url(station);
+    fn point_url(&self, point: &str) -> Url {
+        let mut url = self.base_url.clone();
         {
+            // Coordinates are only digits, signs, and separators so they're used as-is rather
+            // than encoded like station IDs.
             url.path_segments_mut()
                 .map(|mut p| {
-                    p.push("observations").push("latest");
+                    p.clear().push("points").push(point);
                 })
-                .expect("unable to modify observation URL path segments");
+                .expect("unable to modify point URL path segments");
         }
 
         url
     }
-}
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Station {
-    #[serde(alias = "id")]
-    pub id: String,
-    #[serde(alias = "type")]
-    pub type_: String,
-    #[serde(alias = "properties")]
-    pub properties: StationProperties,
-}
+    fn gridpoint_url(&self, office: &str, grid_x: i64, grid_y: i64, resource: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        {
+            url.path_segments_mut()
+                .map(|mut p| {
+                    p.clear()
+                        .push("gridpoints")
+                        .push(office)
+                        .push(&format!("{},{}", grid_x, grid_y))
+                        .extend(resource);
+                })
+                .expect("unable to modify gridpoint URL path segments");
+        }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct StationProperties {
-    #[serde(alias = "@id")]
-    pub id: String,
-    #[serde(alias = "@type")]
-    pub type_: String,
-    #[serde(alias = "elevation")]
-    pub elevation: Measurement,
-    #[serde(alias = "stationIdentifier")]
-    pub station_identifier: String,
-    #[serde(alias = "name")]
-    pub name: String,
-    #[serde(alias = "timezone")]
-    pub timezone: Option<String>,
-}
+        url
+    }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Observation {
-    #[serde(alias = "id")]
-    pub id: String,
+    fn zone_url(&self, zone_type: &str, zone: &str) -> Url {
+        let mut url = self.base_url.clone();
+        {
+            url.path_segments_mut()
+                .map(|mut p| {
+                    p.clear().push("zones").push(zone_type).push(zone);
+                })
+                .expect("unable to modify zone URL path segments");
+        }
+
+        url
+    }
+
+    fn alerts_url(&self, resource: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        {
+            url.path_segments_mut()
+                .map(|mut p| {
+                    p.clear().push("alerts").extend(resource);
+                })
+                .expect("unable to modify alerts URL path segments");
+        }
+
+        url
+    }
+
+    fn stations_url(&self, query: &StationsQuery) -> Url {
+        let mut url = self.base_url.clone();
+        {
+            url.path_segments_mut()
+                .map(|mut p| {
+                    p.clear().push("stations");
+                })
+                .expect("unable to modify stations URL path segments");
+        }
+
+        {
+            let mut pairs = url.query_pairs_mut();
+            if !query.id.is_empty() {
+                pairs.append_pair("id", &query.id.join(","));
+            }
+            if !query.state.is_empty() {
+                pairs.append_pair("state", &query.state.join(","));
+            }
+            if let Some(limit) = query.limit {
+                pairs.append_pair("limit", &limit.to_string());
+            }
+            if let Some(cursor) = &query.cursor {
+                pairs.append_pair("cursor", cursor);
+            }
+        }
+
+        // Remove the empty "?" left when there aren't any parameters.
+        if url.query() == Some("") {
+            url.set_query(None);
+        }
+
+        url
+    }
+
+    fn observations_url(&self, station: &str, query: &[(&str, &str)]) -> Url {
+        let mut url = self.station_url(station);
+        {
+            url.path_segments_mut()
+                .map(|mut p| {
+                    p.push("observations");
+                })
+                .expect("unable to modify observation URL path segments");
+        }
+
+        url.query_pairs_mut().extend_pairs(query);
+        url
+    }
+
+    fn observation_url(&self, station: &str) -> Url {
+        let mut url = self.station_url(station);
+        {
+            url.path_segments_mut()
+                .map(|mut p| {
+                    p.push("observations").push("latest");
+                })
+                .expect("unable to modify observation URL path segments");
+        }
+
+        url
+    }
+}
+
+#[async_trait]
+impl WeatherClient for NwsClient {
+    async fn station(&self, station: &str, trigger: Trigger) -> Result<Station, ClientError> {
+        NwsClient::station(self, station, trigger).await
+    }
+
+    async fn observation(&self, station: &str, trigger: Trigger) -> Result<Observation, ClientError> {
+        NwsClient::observation(self, station, trigger).await
+    }
+
+    async fn observations_since(
+        &self,
+        station: &str,
+        start: DateTime<Utc>,
+        trigger: Trigger,
+    ) -> Result<ObservationCollection, ClientError> {
+        NwsClient::observations_since(self, station, start, trigger).await
+    }
+
+    async fn point(&self, latitude: f64, longitude: f64, trigger: Trigger) -> Result<Point, ClientError> {
+        NwsClient::point(self, latitude, longitude, trigger).await
+    }
+
+    async fn forecast(
+        &self,
+        office: &str,
+        grid_x: i64,
+        grid_y: i64,
+        trigger: Trigger,
+    ) -> Result<Forecast, ClientError> {
+        NwsClient::forecast(self, office, grid_x, grid_y, trigger).await
+    }
+
+    async fn hourly_forecast(
+        &self,
+        office: &str,
+        grid_x: i64,
+        grid_y: i64,
+        trigger: Trigger,
+    ) -> Result<Forecast, ClientError> {
+        NwsClient::hourly_forecast(self, office, grid_x, grid_y, trigger).await
+    }
+
+    async fn gridpoint(
+        &self,
+        office: &str,
+        grid_x: i64,
+        grid_y: i64,
+        trigger: Trigger,
+    ) -> Result<Gridpoint, ClientError> {
+        NwsClient::gridpoint(self, office, grid_x, grid_y, trigger).await
+    }
+
+    async fn zone(&self, zone: &str, trigger: Trigger) -> Result<Zone, ClientError> {
+        NwsClient::zone(self, zone, trigger).await
+    }
+
+    async fn alerts_for_zone(&self, zone: &str, trigger: Trigger) -> Result<AlertCollection, ClientError> {
+        NwsClient::alerts_for_zone(self, zone, trigger).await
+    }
+
+    async fn alerts_for_point(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        trigger: Trigger,
+    ) -> Result<AlertCollection, ClientError> {
+        NwsClient::alerts_for_point(self, latitude, longitude, trigger).await
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Station {
+    #[serde(alias = "id")]
+    pub id: String,
     #[serde(alias = "type")]
     pub type_: String,
+    #[serde(alias = "geometry")]
+    pub geometry: Option<Geometry>,
     #[serde(alias = "properties")]
-    pub properties: ObservationProperties,
+    pub properties: StationProperties,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct ObservationProperties {
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StationProperties {
     #[serde(alias = "@id")]
     pub id: String,
     #[serde(alias = "@type")]
     pub type_: String,
     #[serde(alias = "elevation")]
     pub elevation: Measurement,
-    #[serde(alias = "station")]
+    #[serde(alias = "stationIdentifier")]
+    pub station_identifier: String,
+    #[serde(alias = "name")]
+    pub name: String,
+    #[serde(alias = "timeZone")]
+    pub timezone: Option<String>,
+    #[serde(alias = "forecast", default)]
+    pub forecast_zone: Option<String>,
+    #[serde(alias = "county", default)]
+    pub county: Option<String>,
+}
+
+impl StationProperties {
+    /// IDs of the forecast and county zones the station is in (e.g. `MAZ015`), from the links
+    /// to each zone. Some stations, such as buoys, aren't in any zones.
+    pub fn zones(&self) -> Vec<String> {
+        [&self.forecast_zone, &self.county]
+            .into_iter()
+            .flatten()
+            .map(|url| zone_id(url).to_owned())
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Observation {
+    #[serde(alias = "id")]
+    pub id: String,
+    #[serde(alias = "type")]
+    pub type_: String,
+    #[serde(alias = "geometry")]
+    pub geometry: Option<Geometry>,
+    #[serde(alias = "properties")]
+    pub properties: ObservationProperties,
+    /// Time the observation was returned according to the `Date` header of the API response,
+    /// if there was one. This isn't part of the observation itself.
+    #[serde(skip)]
+    pub server_time: Option<DateTime<Utc>>,
+}
+
+/// Metadata for a latitude and longitude from `NwsClient::point()`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Point {
+    #[serde(alias = "id")]
+    pub id: String,
+    #[serde(alias = "type")]
+    pub type_: String,
+    #[serde(alias = "geometry")]
+    pub geometry: Option<Geometry>,
+    #[serde(alias = "properties")]
+    pub properties: PointProperties,
+}
+
+/// Forecast office, grid, and zones for a point along with links to related resources.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PointProperties {
+    #[serde(alias = "@id", default)]
+    pub id: String,
+    #[serde(alias = "gridId", default)]
+    pub grid_id: String,
+    #[serde(alias = "gridX", default)]
+    pub grid_x: i64,
+    #[serde(alias = "gridY", default)]
+    pub grid_y: i64,
+    #[serde(alias = "forecast", default)]
+    pub forecast: Option<String>,
+    #[serde(alias = "forecastHourly", default)]
+    pub forecast_hourly: Option<String>,
+    #[serde(alias = "forecastGridData", default)]
+    pub forecast_grid_data: Option<String>,
+    #[serde(alias = "observationStations")]
+    pub observation_stations: String,
+    #[serde(alias = "forecastZone", default)]
+    pub forecast_zone: Option<String>,
+    #[serde(alias = "county", default)]
+    pub county: Option<String>,
+    #[serde(alias = "fireWeatherZone", default)]
+    pub fire_weather_zone: Option<String>,
+    #[serde(alias = "timeZone", default)]
+    pub timezone: Option<String>,
+}
+
+/// Forecast for a square of the grid of a forecast office, split into periods.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Forecast {
+    #[serde(alias = "properties")]
+    pub properties: ForecastProperties,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ForecastProperties {
+    #[serde(alias = "updateTime", default)]
+    pub update_time: Option<Timestamp>,
+    #[serde(alias = "periods", default, deserialize_with = "null_as_empty")]
+    pub periods: Vec<ForecastPeriod>,
+}
+
+impl ForecastProperties {
+    /// Get the period that includes the given time, if any.
+    pub fn period_at(&self, time: DateTime<Utc>) -> Option<&ForecastPeriod> {
+        self.periods
+            .iter()
+            .find(|p| p.start_time.utc() <= time && time < p.end_time.utc())
+    }
+}
+
+/// Forecast for a period of time, usually a day or night for daily forecasts.
+///
+/// Temperature and wind speed are returned as plain values with a separate unit or as text
+/// unless the API is asked for measurements with units, use `ForecastPeriod::temperature()`
+/// and `ForecastPeriod::wind_speed()` to get them as measurements either way.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ForecastPeriod {
+    #[serde(alias = "number", default)]
+    pub number: u32,
+    #[serde(alias = "name", default)]
+    pub name: String,
+    #[serde(alias = "startTime")]
+    pub start_time: Timestamp,
+    #[serde(alias = "endTime")]
+    pub end_time: Timestamp,
+    #[serde(alias = "isDaytime", default)]
+    pub is_daytime: bool,
+    #[serde(alias = "temperature", default)]
+    pub temperature: Option<ForecastValue>,
+    #[serde(alias = "temperatureUnit", default)]
+    pub temperature_unit: Option<String>,
+    #[serde(alias = "windSpeed", default)]
+    pub wind_speed: Option<ForecastValue>,
+    #[serde(alias = "probabilityOfPrecipitation", default = "Measurement::absent")]
+    pub probability_of_precipitation: Measurement,
+    #[serde(alias = "shortForecast", default)]
+    pub short_forecast: String,
+}
+
+impl ForecastPeriod {
+    /// Get the temperature as a measurement. It has no value if the temperature is missing
+    /// or can't be parsed.
+    pub fn temperature(&self) -> Measurement {
+        let unit = match self.temperature_unit.as_deref() {
+            Some("F") => "degF",
+            Some("C") => "degC",
+            Some(u) => u,
+            None => "",
+        };
+
+        match &self.temperature {
+            Some(ForecastValue::Number(v)) => Measurement::new(unit, *v),
+            Some(ForecastValue::Text(t)) => t.trim().parse().map(|v| Measurement::new(unit, v)).unwrap_or_default(),
+            Some(ForecastValue::Measurement(m)) => m.clone(),
+            None => Measurement::absent(),
+        }
+    }
+
+    /// Get the wind speed as a measurement. Ranges of speeds (e.g. `5 to 10 mph`) use the
+    /// highest speed of the range. It has no value if the wind speed is missing or can't be
+    /// parsed.
+    pub fn wind_speed(&self) -> Measurement {
+        match &self.wind_speed {
+            Some(ForecastValue::Text(t)) => parse_speed(t).unwrap_or_default(),
+            Some(ForecastValue::Measurement(m)) => m.clone(),
+            // Speeds are always text or measurements so there's no way to know the unit.
+            Some(ForecastValue::Number(_)) => Measurement::default(),
+            None => Measurement::absent(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Gridpoint {
+    #[serde(alias = "properties")]
+    pub properties: GridpointProperties,
+}
+
+/// Raw forecast data for a square of the grid of a forecast office.
+///
+/// Only some of the layers the API returns are included. Amounts (precipitation and snowfall)
+/// are the total for each interval of a layer while other values apply to the whole interval.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GridpointProperties {
+    #[serde(alias = "updateTime", default)]
+    pub update_time: Option<Timestamp>,
+    #[serde(alias = "quantitativePrecipitation", default)]
+    pub quantitative_precipitation: GridpointLayer,
+    #[serde(alias = "snowfallAmount", default)]
+    pub snowfall_amount: GridpointLayer,
+    #[serde(alias = "skyCover", default)]
+    pub sky_cover: GridpointLayer,
+}
+
+/// Series of values of a gridpoint layer for consecutive intervals of time, in the same unit.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GridpointLayer {
+    #[serde(alias = "uom", default)]
+    pub uom: String,
+    #[serde(alias = "values", default, deserialize_with = "null_as_empty")]
+    pub values: Vec<GridpointValue>,
+}
+
+impl GridpointLayer {
+    /// Get the total of the values of this layer between `start` (inclusive) and `end`
+    /// (exclusive) as a measurement, prorating values for intervals that are only partially
+    /// between them. It has no value if no intervals with values overlap the time.
+    pub fn total(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Measurement {
+        let values = self
+            .values
+            .iter()
+            .filter_map(|v| v.value.map(|value| (&v.valid_time, value)));
+
+        match interval::prorated_sum(values, start, end) {
+            Some(v) => Measurement::new(self.uom.as_str(), v),
+            None => Measurement::default(),
+        }
+    }
+
+    /// Get the value of this layer for the interval that includes the given time as a
+    /// measurement. It has no value if no interval includes the time.
+    pub fn at(&self, time: DateTime<Utc>) -> Measurement {
+        match self
+            .values
+            .iter()
+            .find(|v| v.valid_time.contains(time))
+            .and_then(|v| v.value)
+        {
+            Some(v) => Measurement::new(self.uom.as_str(), v),
+            None => Measurement::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GridpointValue {
+    #[serde(alias = "validTime")]
+    pub valid_time: Interval,
+    #[serde(alias = "value", default)]
+    pub value: Option<f64>,
+}
+
+/// Value in a forecast that may be a plain number, text, or measurement with a unit depending
+/// on the field and how the forecast was requested.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ForecastValue {
+    Number(f64),
+    Text(String),
+    Measurement(Measurement),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Zone {
+    #[serde(alias = "id")]
+    pub id: String,
+    #[serde(alias = "properties")]
+    pub properties: ZoneProperties,
+}
+
+/// Metadata for a forecast or county zone. `type_` is the kind of zone such as `public`
+/// (land forecast zones), `coastal`, `offshore`, or `county`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ZoneProperties {
+    #[serde(alias = "id")]
+    pub id: String,
+    #[serde(alias = "name", default)]
+    pub name: String,
+    #[serde(alias = "state", default)]
+    pub state: Option<String>,
+    #[serde(alias = "type", default)]
+    pub type_: String,
+}
+
+/// Alerts returned by `NwsClient::alerts_for_zone()` or `NwsClient::alerts_for_point()`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AlertCollection {
+    #[serde(alias = "features", default, deserialize_with = "null_as_empty")]
+    pub features: Vec<Alert>,
+}
+
+/// Alert along with the area it covers, if it's described by a polygon rather than only zones.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Alert {
+    #[serde(alias = "id")]
+    pub id: String,
+    #[serde(alias = "geometry", default)]
+    pub geometry: Option<Geometry>,
+    #[serde(alias = "properties")]
+    pub properties: AlertProperties,
+}
+
+/// Details of a watch, warning, advisory, or statement issued by the NWS.
+///
+/// `severity` is one of `Extreme`, `Severe`, `Moderate`, `Minor`, or `Unknown` and `urgency`
+/// is one of `Immediate`, `Expected`, `Future`, `Past`, or `Unknown`. `status` is `Actual`
+/// for real alerts and `message_type` is `Cancel` for alerts that have been cancelled.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AlertProperties {
+    #[serde(alias = "id", default)]
+    pub id: String,
+    #[serde(alias = "event", default)]
+    pub event: String,
+    #[serde(alias = "headline", default)]
+    pub headline: Option<String>,
+    #[serde(alias = "severity", default)]
+    pub severity: String,
+    #[serde(alias = "urgency", default)]
+    pub urgency: String,
+    #[serde(alias = "certainty", default)]
+    pub certainty: String,
+    #[serde(alias = "status", default)]
+    pub status: String,
+    #[serde(alias = "messageType", default)]
+    pub message_type: String,
+    #[serde(alias = "onset", default)]
+    pub onset: Option<Timestamp>,
+    #[serde(alias = "expires", default)]
+    pub expires: Option<Timestamp>,
+    #[serde(alias = "ends", default)]
+    pub ends: Option<Timestamp>,
+    #[serde(alias = "areaDesc", default)]
+    pub area_desc: String,
+    #[serde(alias = "affectedZones", default, deserialize_with = "null_as_empty")]
+    pub affected_zones: Vec<String>,
+}
+
+impl AlertProperties {
+    /// IDs of the zones affected by the alert (e.g. `MAZ015`), from the links to each zone.
+    pub fn zones(&self) -> Vec<String> {
+        self.affected_zones.iter().map(|url| zone_id(url).to_owned()).collect()
+    }
+
+    /// Return true if the alert is a real alert that is still in effect at the given time:
+    /// it hasn't been cancelled and hasn't expired. Alerts without an expiration are in
+    /// effect until they're removed.
+    pub fn in_effect(&self, time: DateTime<Utc>) -> bool {
+        self.status == "Actual"
+            && self.message_type != "Cancel"
+            && self.expires.as_ref().map(|e| e.utc() > time).unwrap_or(true)
+    }
+}
+
+/// Filters for listing stations with `NwsClient::stations()`. Empty filters aren't sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StationsQuery {
+    /// Only stations with these IDs.
+    pub id: Vec<String>,
+    /// Only stations in these states or marine areas, using two letter codes (e.g. `MA`).
+    pub state: Vec<String>,
+    /// Maximum number of stations in each page.
+    pub limit: Option<u32>,
+    /// Cursor of the page to fetch, from `StationCollection::cursor()` of the previous page.
+    pub cursor: Option<String>,
+}
+
+/// Page of stations matching a `StationsQuery`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StationCollection {
+    #[serde(alias = "features", default, deserialize_with = "null_as_empty")]
+    pub features: Vec<Station>,
+    #[serde(alias = "pagination", default)]
+    pub pagination: Option<Pagination>,
+}
+
+impl StationCollection {
+    /// Get the cursor for the next page of stations or `None` if there isn't one.
+    ///
+    /// The API includes a link to the next page even for the last page so an empty page
+    /// should be treated as the end of the list as well.
+    pub fn cursor(&self) -> Option<String> {
+        let next = self.pagination.as_ref()?.next.parse::<Url>().ok()?;
+        next.query_pairs()
+            .find(|(k, _)| k == "cursor")
+            .map(|(_, v)| v.into_owned())
+    }
+}
+
+/// Link to the next page of a paginated response.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pagination {
+    #[serde(alias = "next")]
+    pub next: String,
+}
+
+/// List of observations for a station.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ObservationCollection {
+    #[serde(alias = "features", default, deserialize_with = "null_as_empty")]
+    pub features: Vec<Observation>,
+}
+
+impl ObservationCollection {
+    /// Get the observation with the most recent timestamp or `None` if there are none.
+    pub fn latest(self) -> Option<Observation> {
+        self.features.into_iter().max_by_key(|o| o.properties.observed_at())
+    }
+
+    /// Get all observations sorted from oldest to newest. The API usually returns the newest
+    /// observations first but doesn't guarantee any order.
+    pub fn chronological(self) -> Vec<Observation> {
+        let mut features = self.features;
+        features.sort_by_key(|o| o.properties.observed_at());
+        features
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ObservationProperties {
+    #[serde(alias = "@id", default)]
+    pub id: String,
+    #[serde(alias = "@type", default)]
+    pub type_: String,
+    #[serde(alias = "elevation", default = "Measurement::absent")]
+    pub elevation: Measurement,
+    #[serde(alias = "station", default)]
     pub station: String,
     #[serde(alias = "timestamp")]
-    pub timestamp: String,
+    pub timestamp: Timestamp,
     #[serde(alias = "rawMessage")]
     pub raw_message: Option<String>,
     #[serde(alias = "textDescription")]
     pub description: Option<String>,
     #[serde(alias = "icon")]
     pub icon: Option<String>,
-    #[serde(alias = "presentWeather")]
+    #[serde(alias = "presentWeather", default, deserialize_with = "null_as_empty")]
     pub present_weather: Vec<Weather>,
-    #[serde(alias = "temperature")]
+    #[serde(alias = "temperature", default = "Measurement::absent")]
     pub temperature: Measurement,
-    #[serde(alias = "dewpoint")]
+    #[serde(alias = "dewpoint", default = "Measurement::absent")]
     pub dewpoint: Measurement,
-    #[serde(alias = "windDirection")]
+    #[serde(alias = "windDirection", default = "Measurement::absent")]
     pub wind_direction: Measurement,
-    #[serde(alias = "windSpeed")]
+    #[serde(alias = "windSpeed", default = "Measurement::absent")]
     pub wind_speed: Measurement,
-    #[serde(alias = "windGust")]
+    #[serde(alias = "windGust", default = "Measurement::absent")]
     pub wind_gust: Measurement,
-    #[serde(alias = "barometricPressure")]
+    #[serde(alias = "barometricPressure", default = "Measurement::absent")]
     pub barometric_pressure: Measurement,
-    #[serde(alias = "seaLevelPressure")]
+    #[serde(alias = "seaLevelPressure", default = "Measurement::absent")]
     pub sea_level_pressure: Measurement,
-    #[serde(alias = "visibility")]
+    #[serde(alias = "visibility", default = "Measurement::absent")]
     pub visibility: Measurement,
-    #[serde(alias = "relativeHumidity")]
+    #[serde(alias = "precipitationLastHour", default = "Measurement::absent")]
+    pub precipitation_last_hour: Measurement,
+    #[serde(alias = "relativeHumidity", default = "Measurement::absent")]
     pub relative_humidity: Measurement,
-    #[serde(alias = "windChill")]
+    #[serde(alias = "windChill", default = "Measurement::absent")]
     pub wind_chill: Measurement,
-    #[serde(alias = "heatIndex")]
+    #[serde(alias = "heatIndex", default = "Measurement::absent")]
     pub heat_index: Measurement,
-    #[serde(alias = "cloudLayers")]
+    #[serde(alias = "cloudLayers", default, deserialize_with = "null_as_empty")]
     pub cloud_layers: Vec<CloudLayer>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl ObservationProperties {
+    const MEASUREMENTS: usize = 13;
+
+    /// Time of the observation in UTC.
+    pub fn observed_at(&self) -> DateTime<Utc> {
+        self.timestamp.utc()
+    }
+
+    /// Each measurement in the observation along with the name of its field.
+    pub fn measurements(&self) -> [(&'static str, &Measurement); Self::MEASUREMENTS] {
+        [
+            ("elevation", &self.elevation),
+            ("temperature", &self.temperature),
+            ("dewpoint", &self.dewpoint),
+            ("wind_direction", &self.wind_direction),
+            ("wind_speed", &self.wind_speed),
+            ("wind_gust", &self.wind_gust),
+            ("barometric_pressure", &self.barometric_pressure),
+            ("sea_level_pressure", &self.sea_level_pressure),
+            ("visibility", &self.visibility),
+            ("precipitation_last_hour", &self.precipitation_last_hour),
+            ("relative_humidity", &self.relative_humidity),
+            ("wind_chill", &self.wind_chill),
+            ("heat_index", &self.heat_index),
+        ]
+    }
+
+    /// Each measurement in the observation along with the name of its field, mutably.
+    pub fn measurements_mut(&mut self) -> [(&'static str, &mut Measurement); Self::MEASUREMENTS] {
+        [
+            ("elevation", &mut self.elevation),
+            ("temperature", &mut self.temperature),
+            ("dewpoint", &mut self.dewpoint),
+            ("wind_direction", &mut self.wind_direction),
+            ("wind_speed", &mut self.wind_speed),
+            ("wind_gust", &mut self.wind_gust),
+            ("barometric_pressure", &mut self.barometric_pressure),
+            ("sea_level_pressure", &mut self.sea_level_pressure),
+            ("visibility", &mut self.visibility),
+            ("precipitation_last_hour", &mut self.precipitation_last_hour),
+            ("relative_humidity", &mut self.relative_humidity),
+            ("wind_chill", &mut self.wind_chill),
+            ("heat_index", &mut self.heat_index),
+        ]
+    }
+}
+
+/// Time parsed from a timestamp in a response along with the timestamp exactly as it was
+/// returned, for logging.
+///
+/// Timestamps are expected to be RFC 3339 (with either `Z` or an offset like `+00:00`) but some
+/// stations use offsets without a colon (`+0000`) so those are accepted too. Any number of
+/// fractional second digits is allowed. Responses with timestamps in any other format fail to
+/// decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timestamp {
+    raw: String,
+    time: DateTime<FixedOffset>,
+}
+
+impl Timestamp {
+    /// Parse a timestamp in any of the accepted formats.
+    pub fn parse(raw: &str) -> Result<Self, chrono::ParseError> {
+        let time =
+            DateTime::parse_from_rfc3339(raw).or_else(|_| DateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%.f%z"))?;
+        Ok(Self {
+            raw: raw.to_owned(),
+            time,
+        })
+    }
+
+    /// The timestamp exactly as it was returned.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// The time with the offset it was returned with.
+    pub fn time(&self) -> DateTime<FixedOffset> {
+        self.time
+    }
+
+    /// The time in UTC.
+    pub fn utc(&self) -> DateTime<Utc> {
+        self.time.with_timezone(&Utc)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Self::parse(&raw).map_err(|e| de::Error::custom(format!("invalid timestamp {}: {}", raw, e)))
+    }
+}
+
+/// Time a response was sent according to its `Date` header, ignoring the header if it's
+/// missing or can't be parsed.
+fn server_time(res: &Response) -> Option<DateTime<Utc>> {
+    let date = res.headers().get(DATE)?.to_str().ok()?;
+    match DateTime::parse_from_rfc2822(date) {
+        Ok(t) => Some(t.with_timezone(&Utc)),
+        Err(e) => {
+            tracing::debug!(message = "ignoring invalid date header", date = %date, error = %e);
+            None
+        }
+    }
+}
+
+/// Format a coordinate with at most the given number of decimal places, without trailing
+/// zeros since the API redirects coordinates with them to the same coordinates without.
+fn format_coordinate(value: f64, precision: usize) -> String {
+    let formatted = format!("{:.*}", precision, value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => "0".to_owned(),
+        t => t.to_owned(),
+    }
+}
+
+/// Get the ID of a zone from the last path segment of a link to it.
+fn zone_id(url: &str) -> &str {
+    url.trim_end_matches('/').rsplit('/').next().unwrap_or(url)
+}
+
+/// Parse a link to another resource included in a response.
+fn link(url: &str) -> Result<Url, ClientError> {
+    url.parse()
+        .map_err(|e| ClientError::InvalidLink(format!("{}: {}", url, e)))
+}
+
+/// Parse a speed like `10 mph` or `5 to 10 mph` into a measurement of the highest speed.
+fn parse_speed(text: &str) -> Option<Measurement> {
+    let unit = match text.split_whitespace().last()? {
+        "mph" => "mi_h-1",
+        "km/h" => "km_h-1",
+        "kt" | "knots" => "kn",
+        _ => return None,
+    };
+
+    text.split_whitespace()
+        .filter_map(|w| w.parse::<f64>().ok())
+        .reduce(f64::max)
+        .map(|v| Measurement::new(unit, v))
+}
+
+/// Deserialize a list that some responses set to `null` instead of an empty list.
+fn null_as_empty<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Weather {
     #[serde(alias = "weather")]
     pub weather: String,
@@ -255,7 +1848,7 @@ pub struct Weather {
     pub modifier: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CloudLayer {
     #[serde(alias = "base")]
     pub base: Measurement,
@@ -263,12 +1856,1469 @@ pub struct CloudLayer {
     pub amount: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Measurement {
-    #[serde(alias = "unitCode")]
+    #[serde(alias = "unitCode", default)]
     pub unit_code: String,
     #[serde(alias = "value")]
     pub value: Option<f64>,
     #[serde(alias = "qualityControl")]
     pub quality_control: Option<String>,
+    /// Set when the measurement was missing from the response entirely rather than null.
+    #[serde(skip)]
+    pub absent: bool,
+}
+
+impl Measurement {
+    /// Measurement with the given unit code and value, for values that aren't returned as
+    /// measurements by the API.
+    pub fn new<S: Into<String>>(unit_code: S, value: f64) -> Self {
+        Self {
+            unit_code: unit_code.into(),
+            value: Some(value),
+            ..Self::default()
+        }
+    }
+
+    /// Measurement for a field that was missing from the response. It has no value, the
+    /// same as a field that was null.
+    pub fn absent() -> Self {
+        Self {
+            absent: true,
+            ..Self::default()
+        }
+    }
+
+    /// Namespace prefixes that unit codes may use. Current responses use `wmoUnit:` but older
+    /// cached responses and other deployments of the API use `unit:` or no prefix at all.
+    const UNIT_PREFIXES: [&'static str; 2] = ["wmoUnit:", "unit:"];
+
+    /// Get the unit code of the measurement without any namespace prefix, e.g. `degC`
+    /// for `wmoUnit:degC`, `unit:degC`, or `degC`.
+    pub fn unit(&self) -> &str {
+        Self::UNIT_PREFIXES
+            .iter()
+            .find_map(|p| self.unit_code.strip_prefix(p))
+            .unwrap_or(&self.unit_code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, Bytes};
+    use axum::extract::State;
+    use axum::http::header;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use prometheus_client::encoding::text;
+    use prometheus_client::registry::Registry;
+    use reqwest::redirect::Policy;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Serve `app` on an ephemeral port, returning the base URL to use for the API.
+    fn serve(app: Router) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service());
+        tokio::spawn(server);
+        format!("http://{}/", addr)
+    }
+
+    fn client(base_url: &str, redirects: Policy) -> (NwsClient, Registry) {
+        let mut reg = Registry::default();
+        let http = Client::builder().redirect(redirects).build().unwrap();
+        let client = NwsClient::new(http, base_url)
+            .unwrap()
+            .with_metrics(ClientMetrics::new(&mut reg, Duration::from_secs(1)));
+        (client, reg)
+    }
+
+    fn encode(reg: &Registry) -> String {
+        let mut out = String::new();
+        text::encode(&mut out, reg).unwrap();
+        out
+    }
+
+    fn station_json(id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": format!("https://api.weather.gov/stations/{}", id),
+            "type": "Feature",
+            "geometry": {"type": "Point", "coordinates": [-71.01, 42.36]},
+            "properties": {
+                "@id": format!("https://api.weather.gov/stations/{}", id),
+                "@type": "wx:ObservationStation",
+                "elevation": {"unitCode": "wmoUnit:m", "value": 9},
+                "stationIdentifier": id,
+                "name": "Boston, Logan International Airport",
+                "timeZone": "America/New_York",
+            },
+        })
+    }
+
+    /// API where the old ID of a station is permanently redirected to its new ID.
+    fn renamed_station() -> Router {
+        Router::new()
+            .route(
+                "/stations/KOLD",
+                get(|| async {
+                    (StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, "/stations/KBOS")]).into_response()
+                }),
+            )
+            .route("/stations/KBOS", get(|| async { Json(station_json("KBOS")) }))
+    }
+
+    #[tokio::test]
+    async fn test_redirect_followed() {
+        let (client, reg) = client(&serve(renamed_station()), Policy::limited(5));
+        let station = client.station("KOLD", Trigger::Scheduled).await.unwrap();
+        assert_eq!("KBOS", station.properties.station_identifier);
+
+        let out = encode(&reg);
+        assert!(out.contains("nws_exporter_api_redirects_total{station=\"KOLD\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_not_followed() {
+        let (client, reg) = client(&serve(renamed_station()), Policy::none());
+        let err = client.station("KOLD", Trigger::Scheduled).await.unwrap_err();
+        assert!(!err.is_retryable());
+        match err {
+            ClientError::Redirect(status, url, location) => {
+                assert_eq!(StatusCode::MOVED_PERMANENTLY, status);
+                assert!(url.path().ends_with("/stations/KOLD"));
+                assert_eq!(Some("/stations/KBOS"), location.as_deref());
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+
+        let out = encode(&reg);
+        assert!(!out.contains("nws_exporter_api_redirects_total{"));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_limit() {
+        let app = Router::new().route(
+            "/stations/KOLD",
+            get(|| async { (StatusCode::PERMANENT_REDIRECT, [(header::LOCATION, "/stations/KOLD")]).into_response() }),
+        );
+        let (client, _reg) = client(&serve(app), Policy::limited(2));
+        let err = client.station("KOLD", Trigger::Scheduled).await.unwrap_err();
+        assert!(
+            matches!(&err, ClientError::Internal(e) if e.is_redirect()),
+            "unexpected error {:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_alert_zones() {
+        let alerts: AlertCollection = serde_json::from_str(include_str!("../../ext/fixtures/alerts_one.json")).unwrap();
+        let alert = &alerts.features[0];
+        assert_eq!(vec!["MAZ015", "MAZ016"], alert.properties.zones());
+        assert!(alert.geometry.is_none());
+    }
+
+    #[test]
+    fn test_alert_geometry() {
+        let alert: Alert = serde_json::from_value(serde_json::json!({
+            "id": "gale",
+            "geometry": {"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 0]]]},
+            "properties": {"affectedZones": null},
+        }))
+        .unwrap();
+        assert!(alert.properties.zones().is_empty());
+        assert!(matches!(alert.geometry, Some(Geometry::Polygon(_))));
+    }
+
+    #[test]
+    fn test_station_zones() {
+        let station: StationProperties = serde_json::from_value(serde_json::json!({
+            "@id": "https://api.weather.gov/stations/KBOS",
+            "@type": "wx:ObservationStation",
+            "elevation": {"unitCode": "wmoUnit:m", "value": 9},
+            "stationIdentifier": "KBOS",
+            "name": "Boston, Logan International Airport",
+            "timeZone": "America/New_York",
+            "forecast": "https://api.weather.gov/zones/forecast/MAZ015",
+            "county": "https://api.weather.gov/zones/county/MAC025",
+        }))
+        .unwrap();
+        assert_eq!(vec!["MAZ015", "MAC025"], station.zones());
+
+        let buoy = StationProperties {
+            forecast_zone: None,
+            county: None,
+            ..station
+        };
+        assert!(buoy.zones().is_empty());
+    }
+
+    /// Stream a list of stations that never ends, counting the bytes sent.
+    async fn endless_stations(State(sent): State<Arc<AtomicUsize>>) -> axum::http::Response<Body> {
+        let (mut tx, body) = Body::channel();
+        tokio::spawn(async move {
+            let mut station = serde_json::to_vec(&station_json("KBOS")).unwrap();
+            station.push(b',');
+            let mut chunk = Bytes::from_static(b"{\"features\":[");
+            while tx.send_data(chunk).await.is_ok() {
+                chunk = Bytes::from(station.clone());
+                sent.fetch_add(chunk.len(), Ordering::Relaxed);
+            }
+        });
+        axum::http::Response::new(body)
+    }
+
+    #[tokio::test]
+    async fn test_body_too_large_streaming() {
+        const MAX_BODY_BYTES: u64 = 64 * 1024;
+        let sent = Arc::new(AtomicUsize::new(0));
+        let app = Router::new()
+            .route("/stations", get(endless_stations))
+            .with_state(sent.clone());
+        let (client, _reg) = client(&serve(app), Policy::none());
+        let client = client.with_max_body_bytes(MAX_BODY_BYTES);
+
+        // The body never ends so this only returns if reading stops at the limit
+        let res = tokio::time::timeout(
+            Duration::from_secs(10),
+            client.stations(&StationsQuery::default(), Trigger::Scheduled),
+        )
+        .await
+        .expect("body was read past the limit");
+
+        match res {
+            Err(ClientError::BodyTooLarge(url, max)) => {
+                assert_eq!("/stations", url.path());
+                assert_eq!(MAX_BODY_BYTES, max);
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        // Only as much as fits in socket buffers is sent after the client stops reading
+        assert!(sent.load(Ordering::Relaxed) < 64 * 1024 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_body_too_large_content_length() {
+        let app = Router::new().route("/stations/KBOS", get(|| async { Json(station_json("KBOS")) }));
+        let (client, _reg) = client(&serve(app), Policy::none());
+
+        let small = client.with_max_body_bytes(64);
+        assert!(matches!(
+            small.station("KBOS", Trigger::Scheduled).await,
+            Err(ClientError::BodyTooLarge(_, 64))
+        ));
+
+        let large = small.with_max_body_bytes(64 * 1024);
+        let station = large.station("KBOS", Trigger::Scheduled).await.unwrap();
+        assert_eq!("KBOS", station.properties.station_identifier);
+    }
+
+    #[tokio::test]
+    async fn test_request_triggers() {
+        let app = Router::new().route("/stations/KBOS", get(|| async { Json(station_json("KBOS")) }));
+        let (client, reg) = client(&serve(app), Policy::none());
+
+        let triggers = [
+            (Trigger::Scheduled, "scheduled"),
+            (Trigger::Scrape, "scrape"),
+            (Trigger::Probe, "probe"),
+            (Trigger::Manual, "manual"),
+            (Trigger::Backfill, "backfill"),
+        ];
+        for (trigger, name) in triggers {
+            assert_eq!(name, trigger.as_str());
+            assert_eq!(name, trigger.to_string());
+            client.station("KBOS", trigger).await.unwrap();
+        }
+        client.station("KBOS", Trigger::Manual).await.unwrap();
+
+        let out = encode(&reg);
+        for (trigger, name) in triggers {
+            let count = if trigger == Trigger::Manual { 2 } else { 1 };
+            let labels = format!("{{station=\"KBOS\",endpoint=\"station\",trigger=\"{}\"}}", name);
+            assert!(
+                out.contains(&format!("nws_exporter_api_requests_total{} {}", labels, count)),
+                "{}",
+                out
+            );
+            assert!(
+                out.contains(&format!(
+                    "nws_exporter_api_request_duration_seconds_count{} {}",
+                    labels, count
+                )),
+                "{}",
+                out
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_reason_connect() {
+        // Nothing is listening on the port once the listener is dropped
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let err = Client::new().get(format!("http://{}/", addr)).send().await.unwrap_err();
+        let err = ClientError::from(err);
+        assert_eq!("connect", err.reason());
+        assert_eq!(None, err.status());
+    }
+
+    #[tokio::test]
+    async fn test_error_reason_timeout() {
+        let app = Router::new().route(
+            "/",
+            get(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                "late"
+            }),
+        );
+        let http = Client::builder().timeout(Duration::from_millis(50)).build().unwrap();
+        let err = http.get(serve(app)).send().await.unwrap_err();
+        let err = ClientError::from(err);
+        assert_eq!("timeout", err.reason());
+        assert_eq!(None, err.status());
+    }
+
+    #[test]
+    fn test_error_reason() {
+        let url: Url = "https://api.weather.gov/stations/KBOS/observations/latest"
+            .parse()
+            .unwrap();
+        let decode = serde_json::from_str::<Station>("{").unwrap_err();
+        for (err, reason, status) in [
+            (ClientError::Initialization("bad".to_owned()), "initialization", None),
+            (ClientError::InvalidStation("KXXX".to_owned()), "invalid_station", None),
+            (ClientError::InvalidPoint("0,0".to_owned()), "invalid_point", None),
+            (ClientError::InvalidZone("XXZ000".to_owned()), "invalid_zone", None),
+            (ClientError::InvalidLink("nope".to_owned()), "invalid_link", None),
+            (ClientError::NotFound(url.clone()), "not_found", None),
+            (ClientError::NoObservations("KBOS".to_owned()), "no_observations", None),
+            (ClientError::Unsupported("stations"), "unsupported", None),
+            (
+                ClientError::Redirect(StatusCode::MOVED_PERMANENTLY, url.clone(), None),
+                "redirect",
+                Some(301),
+            ),
+            (
+                ClientError::Unexpected(StatusCode::SERVICE_UNAVAILABLE, url.clone(), Box::default()),
+                "unexpected_status",
+                Some(503),
+            ),
+            (ClientError::BodyTooLarge(url.clone(), 1024), "body_too_large", None),
+            (ClientError::Decode(url, decode), "decode", None),
+        ] {
+            assert_eq!(reason, err.reason(), "{}", err);
+            assert_eq!(status, err.status().map(|s| s.as_u16()), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_responses_by_status() {
+        let app = renamed_station()
+            .route("/stations/KXXX", get(|| async { StatusCode::NOT_FOUND }))
+            .route("/stations/KERR", get(|| async { StatusCode::SERVICE_UNAVAILABLE }));
+        let (client, reg) = client(&serve(app), Policy::none());
+
+        client.station("KBOS", Trigger::Scheduled).await.unwrap();
+        client.station("KBOS", Trigger::Scheduled).await.unwrap();
+        assert!(matches!(
+            client.station("KOLD", Trigger::Scheduled).await,
+            Err(ClientError::Redirect(..))
+        ));
+        assert!(matches!(
+            client.station("KXXX", Trigger::Scheduled).await,
+            Err(ClientError::InvalidStation(_))
+        ));
+        assert!(matches!(
+            client.station("KERR", Trigger::Scheduled).await,
+            Err(ClientError::Unexpected(..))
+        ));
+
+        let out = encode(&reg);
+        for (code, count) in [("200", 2), ("301", 1), ("404", 1), ("503", 1)] {
+            let expected = format!(
+                "nws_exporter_api_responses_total{{endpoint=\"station\",code=\"{}\"}} {}\n",
+                code, count
+            );
+            assert!(out.contains(&expected), "{}", out);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_without_metrics() {
+        let http = Client::builder().redirect(Policy::none()).build().unwrap();
+        let client = NwsClient::new(http, &serve(renamed_station())).unwrap();
+        let station = client.station("KBOS", Trigger::Scheduled).await.unwrap();
+        assert_eq!("KBOS", station.properties.station_identifier);
+    }
+
+    #[tokio::test]
+    async fn test_observation_invalid_timestamp() {
+        let observation = |timestamp: &'static str| {
+            move || async move {
+                let mut json: serde_json::Value =
+                    serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap();
+                json["properties"]["timestamp"] = timestamp.into();
+                Json(json)
+            }
+        };
+        let app = Router::new()
+            .route(
+                "/stations/KBOS/observations/latest",
+                get(observation("2023-10-21T14:54:00+00:00")),
+            )
+            .route(
+                "/stations/KJFK/observations/latest",
+                get(observation("2023-10-21T14:54:00.25+0000")),
+            )
+            .route("/stations/KXXX/observations/latest", get(observation("yesterday")));
+        let (client, _reg) = client(&serve(app), Policy::none());
+
+        let obs = client.observation("KBOS", Trigger::Scheduled).await.unwrap();
+        assert_eq!(1697900040, obs.properties.timestamp.utc().timestamp());
+        let obs = client.observation("KJFK", Trigger::Scheduled).await.unwrap();
+        assert_eq!("2023-10-21T14:54:00.25+0000", obs.properties.timestamp.raw());
+
+        // The whole observation fails to decode, not just its age
+        let err = client.observation("KXXX", Trigger::Scheduled).await.unwrap_err();
+        assert!(matches!(err, ClientError::Decode(..)), "{}", err);
+        assert_eq!("decode", err.reason());
+        assert!(err.to_string().contains("invalid timestamp yesterday"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_response_ids_in_errors() {
+        let app = Router::new()
+            .route(
+                "/stations/KERR",
+                get(|| async {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        [
+                            ("x-correlation-id", "abc123"),
+                            ("x-request-id", "req-456"),
+                            ("x-server-id", "vm-lnx-nids-apiapp13.ncep.noaa.gov"),
+                        ],
+                    )
+                        .into_response()
+                }),
+            )
+            .route(
+                "/stations/KPART",
+                get(|| async { (StatusCode::BAD_GATEWAY, [("x-request-id", "req-789")]).into_response() }),
+            )
+            .route("/stations/KNONE", get(|| async { StatusCode::SERVICE_UNAVAILABLE }));
+        let (client, _reg) = client(&serve(app), Policy::none());
+
+        let err = client.station("KERR", Trigger::Scheduled).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("(correlation_id=abc123 request_id=req-456 server_id=vm-lnx-nids-apiapp13.ncep.noaa.gov)"),
+            "{}",
+            err
+        );
+
+        let err = client.station("KPART", Trigger::Scheduled).await.unwrap_err();
+        assert!(err.to_string().ends_with("(request_id=req-789)"), "{}", err);
+
+        let err = client.station("KNONE", Trigger::Scheduled).await.unwrap_err();
+        assert!(err.to_string().ends_with("/stations/KNONE"), "{}", err);
+    }
+
+    #[test]
+    fn test_measurement_unit() {
+        for code in ["wmoUnit:degC", "unit:degC", "degC"] {
+            assert_eq!("degC", Measurement::new(code, 16.7).unit(), "{}", code);
+        }
+
+        // Only a single known prefix is removed
+        assert_eq!("unit:degC", Measurement::new("wmoUnit:unit:degC", 16.7).unit());
+        assert_eq!("other:degC", Measurement::new("other:degC", 16.7).unit());
+    }
+
+    #[test]
+    fn test_null_arrays() {
+        let with = |value: serde_json::Value| -> Observation {
+            let mut json: serde_json::Value =
+                serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap();
+            json["properties"]["cloudLayers"] = value.clone();
+            json["properties"]["presentWeather"] = value;
+            serde_json::from_value(json).unwrap()
+        };
+
+        let obs = with(serde_json::Value::Null);
+        assert!(obs.properties.cloud_layers.is_empty());
+        assert!(obs.properties.present_weather.is_empty());
+        assert_eq!(Some(16.7), obs.properties.temperature.value);
+
+        let obs = with(serde_json::json!([]));
+        assert!(obs.properties.cloud_layers.is_empty());
+        assert!(obs.properties.present_weather.is_empty());
+
+        // The literal payload shape returned by the API
+        let raw = r#"{
+            "id": "https://api.weather.gov/stations/KBOS/observations/2023-10-21T14:54:00+00:00",
+            "type": "Feature",
+            "properties": {
+                "station": "https://api.weather.gov/stations/KBOS",
+                "timestamp": "2023-10-21T14:54:00+00:00",
+                "presentWeather": null,
+                "cloudLayers": null
+            }
+        }"#;
+        let obs: Observation = serde_json::from_str(raw).unwrap();
+        assert!(obs.properties.cloud_layers.is_empty());
+        assert!(obs.properties.present_weather.is_empty());
+    }
+
+    #[test]
+    fn test_timestamp_parse() {
+        for (raw, expected) in [
+            ("2023-10-21T14:54:00+00:00", "2023-10-21T14:54:00Z"),
+            ("2023-10-21T14:54:00Z", "2023-10-21T14:54:00Z"),
+            ("2023-10-21T14:54:00+0000", "2023-10-21T14:54:00Z"),
+            ("2023-10-21T10:54:00-04:00", "2023-10-21T14:54:00Z"),
+            ("2023-10-21T10:54:00-0400", "2023-10-21T14:54:00Z"),
+            ("2023-10-21T14:54:00.5+00:00", "2023-10-21T14:54:00.500Z"),
+            ("2023-10-21T14:54:00.123456Z", "2023-10-21T14:54:00.123456Z"),
+            ("2023-10-21T14:54:00.25+0000", "2023-10-21T14:54:00.250Z"),
+        ] {
+            let ts = Timestamp::parse(raw).unwrap_or_else(|e| panic!("{}: {}", raw, e));
+            assert_eq!(
+                expected,
+                ts.utc().to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                "{}",
+                raw
+            );
+            assert_eq!(raw, ts.raw());
+            assert_eq!(raw, ts.to_string());
+        }
+
+        // The offset the time was returned with is kept
+        let ts = Timestamp::parse("2023-10-21T10:54:00-04:00").unwrap();
+        assert_eq!(-4 * 3600, ts.time().offset().local_minus_utc());
+
+        for raw in [
+            "",
+            "yesterday",
+            "2023-10-21",
+            "2023-10-21T14:54:00",
+            "2023-10-21 14:54:00",
+            "21 Oct 2023 14:54:00 +0000",
+        ] {
+            assert!(Timestamp::parse(raw).is_err(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_timestamp_serde() {
+        let raw = "\"2023-10-21T14:54:00.25+0000\"";
+        let ts: Timestamp = serde_json::from_str(raw).unwrap();
+        assert_eq!(raw, serde_json::to_string(&ts).unwrap());
+
+        let err = serde_json::from_str::<Timestamp>("\"2023-10-21\"").unwrap_err();
+        assert!(err.to_string().contains("invalid timestamp 2023-10-21"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_observation_fallback() {
+        let observation = |time: &str, temperature: f64| {
+            let mut json: serde_json::Value =
+                serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap();
+            json["id"] = format!("https://api.weather.gov/stations/KBOS/observations/{}", time).into();
+            json["properties"]["timestamp"] = time.into();
+            json["properties"]["temperature"]["value"] = temperature.into();
+            json
+        };
+        let collection = serde_json::json!({
+            "type": "FeatureCollection",
+            "features": [
+                observation("2023-10-21T13:54:00+00:00", 15.0),
+                observation("2023-10-21T14:54:00+00:00", 16.7),
+                observation("2023-10-21T12:54:00+00:00", 14.4),
+            ],
+        });
+
+        let app = Router::new()
+            .route(
+                "/stations/KBOS/observations/latest",
+                get(|| async { StatusCode::NOT_FOUND }),
+            )
+            .route(
+                "/stations/KBOS/observations",
+                get(move || async move { Json(collection) }),
+            )
+            .route(
+                "/stations/KNEW/observations/latest",
+                get(|| async { StatusCode::NOT_FOUND }),
+            )
+            .route(
+                "/stations/KNEW/observations",
+                get(|| async { Json(serde_json::json!({"type": "FeatureCollection", "features": []})) }),
+            );
+        let (client, reg) = client(&serve(app), Policy::none());
+
+        // The most recent observation in the list is used, regardless of its position
+        let obs = client.observation("KBOS", Trigger::Scheduled).await.unwrap();
+        assert_eq!("2023-10-21T14:54:00+00:00", obs.properties.timestamp.raw());
+        assert_eq!(Some(16.7), obs.properties.temperature.value);
+
+        let err = client.observation("KNEW", Trigger::Scheduled).await.unwrap_err();
+        assert!(
+            matches!(err, ClientError::NoObservations(ref s) if s == "KNEW"),
+            "{}",
+            err
+        );
+
+        // Stations that don't exist at all are still invalid
+        let err = client.observation("KXXX", Trigger::Scheduled).await.unwrap_err();
+        assert!(matches!(err, ClientError::InvalidStation(_)), "{}", err);
+
+        let out = encode(&reg);
+        for (station, count) in [("KBOS", 1), ("KNEW", 1), ("KXXX", 1)] {
+            let expected = format!(
+                "nws_exporter_api_observation_fallbacks_total{{station=\"{}\"}} {}\n",
+                station, count
+            );
+            assert!(out.contains(&expected), "{}", out);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observation_server_time() {
+        const FIXTURE: &str = include_str!("../../ext/fixtures/KBOS.json");
+        let observation = |date: &'static str| {
+            move || async move {
+                (
+                    [(header::DATE, date), (header::CONTENT_TYPE, "application/geo+json")],
+                    FIXTURE,
+                )
+            }
+        };
+        let collection = move || async move {
+            let obs: serde_json::Value = serde_json::from_str(FIXTURE).unwrap();
+            (
+                [(header::DATE, "Sat, 21 Oct 2023 15:00:00 GMT")],
+                Json(serde_json::json!({"type": "FeatureCollection", "features": [obs]})),
+            )
+        };
+
+        let app = Router::new()
+            .route(
+                "/stations/KBOS/observations/latest",
+                get(observation("Sat, 21 Oct 2023 14:59:00 GMT")),
+            )
+            .route("/stations/KJFK/observations/latest", get(observation("not a date")))
+            .route(
+                "/stations/KNEW/observations/latest",
+                get(|| async { StatusCode::NOT_FOUND }),
+            )
+            .route("/stations/KNEW/observations", get(collection));
+        let (client, _reg) = client(&serve(app), Policy::none());
+
+        let obs = client.observation("KBOS", Trigger::Scheduled).await.unwrap();
+        assert_eq!(
+            Some("2023-10-21T14:59:00Z".parse::<DateTime<Utc>>().unwrap()),
+            obs.server_time
+        );
+
+        // Dates that can't be parsed are ignored rather than failing the request
+        let obs = client.observation("KJFK", Trigger::Scheduled).await.unwrap();
+        assert_eq!(None, obs.server_time);
+
+        // The time of the response is kept when falling back to the list of observations
+        let obs = client.observation("KNEW", Trigger::Scheduled).await.unwrap();
+        assert_eq!(
+            Some("2023-10-21T15:00:00Z".parse::<DateTime<Utc>>().unwrap()),
+            obs.server_time
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stations() {
+        let queries = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route(
+                "/stations",
+                get(
+                    |State(queries): State<Arc<std::sync::Mutex<Vec<String>>>>,
+                     axum::extract::RawQuery(query): axum::extract::RawQuery| async move {
+                        let query = query.unwrap_or_default();
+                        queries.lock().unwrap().push(query.clone());
+                        if query.contains("cursor=expired") {
+                            StatusCode::NOT_FOUND.into_response()
+                        } else {
+                            (
+                                [(header::CONTENT_TYPE, "application/geo+json")],
+                                include_str!("../../ext/fixtures/stations_two.json"),
+                            )
+                                .into_response()
+                        }
+                    },
+                ),
+            )
+            .with_state(queries.clone());
+        let (client, _reg) = client(&serve(app), Policy::none());
+
+        let query = StationsQuery {
+            id: vec![],
+            state: vec!["MA".to_owned(), "RI".to_owned()],
+            limit: Some(2),
+            cursor: None,
+        };
+        let page = client.stations_page(&query, Trigger::Scheduled).await.unwrap();
+        let ids: Vec<&str> = page
+            .features
+            .iter()
+            .map(|s| s.properties.station_identifier.as_str())
+            .collect();
+        assert_eq!(vec!["KBOS", "KBED"], ids);
+        let bed = &page.features[1].properties;
+        assert_eq!("Bedford, Laurence G. Hanscom Field", bed.name);
+        assert_eq!(Some(40.843), bed.elevation.value);
+        assert_eq!(Some("America/New_York"), bed.timezone.as_deref());
+        assert_eq!(
+            Some("https://api.weather.gov/zones/forecast/MAZ005"),
+            bed.forecast_zone.as_deref()
+        );
+        assert_eq!(Some("eyJzIjoyfQ==".to_owned()), page.cursor());
+
+        let query = StationsQuery {
+            id: vec!["KBOS".to_owned(), "KBED".to_owned()],
+            cursor: page.cursor(),
+            ..StationsQuery::default()
+        };
+        assert_eq!(2, client.stations(&query, Trigger::Scheduled).await.unwrap().len());
+        client
+            .stations(&StationsQuery::default(), Trigger::Scheduled)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![
+                "state=MA%2CRI&limit=2".to_owned(),
+                "id=KBOS%2CKBED&cursor=eyJzIjoyfQ%3D%3D".to_owned(),
+                String::new(),
+            ],
+            *queries.lock().unwrap()
+        );
+
+        // A list that doesn't exist isn't blamed on a station
+        let query = StationsQuery {
+            cursor: Some("expired".to_owned()),
+            ..StationsQuery::default()
+        };
+        let err = client.stations(&query, Trigger::Scheduled).await.unwrap_err();
+        assert!(
+            matches!(err, ClientError::NotFound(ref url) if url.query() == Some("cursor=expired")),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stations_for_point_not_found() {
+        let app = Router::new()
+            .route(
+                "/points/:point",
+                get(|axum::extract::Host(host): axum::extract::Host| async move {
+                    Json(serde_json::json!({
+                        "id": "https://api.weather.gov/points/42.36,-71.01",
+                        "type": "Feature",
+                        "geometry": null,
+                        "properties": {
+                            "observationStations": format!("http://{}/gridpoints/BOX/71,90/stations", host),
+                        },
+                    }))
+                }),
+            )
+            .route(
+                "/gridpoints/BOX/71,90/stations",
+                get(|| async { StatusCode::NOT_FOUND }),
+            );
+        let (client, _reg) = client(&serve(app), Policy::none());
+
+        let err = client
+            .stations_for_point(42.36, -71.01, Trigger::Scheduled)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ClientError::InvalidPoint(ref p) if p == "42.36,-71.01"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_format_coordinate() {
+        for (value, expected) in [
+            (42.360081234, "42.3601"),
+            (-71.010234567, "-71.0102"),
+            (42.36, "42.36"),
+            (-71.0, "-71"),
+            (10.0, "10"),
+            (-0.00001, "0"),
+            (0.0, "0"),
+        ] {
+            assert_eq!(expected, format_coordinate(value, NwsClient::COORDINATE_PRECISION));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stations_for_point() {
+        let paths = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route(
+                "/points/:point",
+                get(
+                    |State(paths): State<Arc<std::sync::Mutex<Vec<String>>>>,
+                     axum::extract::Host(host): axum::extract::Host,
+                     uri: axum::http::Uri| async move {
+                        paths.lock().unwrap().push(uri.path().to_owned());
+                        Json(serde_json::json!({
+                            "id": "https://api.weather.gov/points/42.3601,-71.0102",
+                            "type": "Feature",
+                            "geometry": null,
+                            "properties": {
+                                "gridId": "BOX",
+                                "gridX": 71,
+                                "gridY": 90,
+                                "observationStations": format!("http://{}/gridpoints/BOX/71,90/stations", host),
+                            },
+                        }))
+                    },
+                ),
+            )
+            .route(
+                "/gridpoints/BOX/71,90/stations",
+                get(|| async {
+                    (
+                        [(header::CONTENT_TYPE, "application/geo+json")],
+                        include_str!("../../ext/fixtures/stations_two.json"),
+                    )
+                }),
+            )
+            .with_state(paths.clone());
+        let (client, reg) = client(&serve(app), Policy::none());
+
+        let point = client
+            .point(42.360081234, -71.010234567, Trigger::Scheduled)
+            .await
+            .unwrap();
+        assert_eq!("BOX", point.properties.grid_id);
+        assert_eq!((71, 90), (point.properties.grid_x, point.properties.grid_y));
+
+        // The stations linked from the point are returned closest first, as listed
+        let stations = client
+            .stations_for_point(42.360081234, -71.010234567, Trigger::Scheduled)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = stations
+            .iter()
+            .map(|s| s.properties.station_identifier.as_str())
+            .collect();
+        assert_eq!(vec!["KBOS", "KBED"], ids);
+
+        // Coordinates are rounded and the sign of negative ones isn't encoded
+        assert_eq!(
+            vec![
+                "/points/42.3601,-71.0102".to_owned(),
+                "/points/42.3601,-71.0102".to_owned()
+            ],
+            *paths.lock().unwrap()
+        );
+
+        // Coordinates that are out of range aren't requested at all
+        for (latitude, longitude) in [(91.0, 0.0), (0.0, -181.0), (f64::NAN, 0.0)] {
+            let err = client
+                .stations_for_point(latitude, longitude, Trigger::Scheduled)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, ClientError::InvalidPoint(_)), "{}", err);
+        }
+        assert_eq!(2, paths.lock().unwrap().len());
+
+        let out = encode(&reg);
+        for (endpoint, count) in [("point", 2), ("stations", 1)] {
+            let expected = format!(
+                "nws_exporter_api_responses_total{{endpoint=\"{}\",code=\"200\"}} {}\n",
+                endpoint, count
+            );
+            assert!(out.contains(&expected), "{}", out);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forecast() {
+        let app = Router::new().route(
+            "/gridpoints/BOX/71,90/forecast",
+            get(|| async {
+                (
+                    [(header::CONTENT_TYPE, "application/geo+json")],
+                    include_str!("../../ext/fixtures/forecast_BOX.json"),
+                )
+            }),
+        );
+        let (client, reg) = client(&serve(app), Policy::none());
+
+        let forecast = client.forecast("BOX", 71, 90, Trigger::Scheduled).await.unwrap();
+        let periods = &forecast.properties.periods;
+        assert_eq!(8, periods.len());
+        assert_eq!("This Afternoon", periods[0].name);
+        assert!(periods[0].is_daytime);
+        assert!(!periods[1].is_daytime);
+        assert_eq!(
+            "2023-10-21T15:00:00Z".parse::<DateTime<Utc>>().unwrap(),
+            periods[0].start_time.utc()
+        );
+        assert_eq!(periods[0].end_time.utc(), periods[1].start_time.utc());
+
+        // Temperatures are plain numbers with a separate unit and speeds are text
+        let unit_value = |m: Measurement| (m.unit_code, m.value);
+        assert_eq!(("degF".to_owned(), Some(62.0)), unit_value(periods[0].temperature()));
+        assert_eq!(("mi_h-1".to_owned(), Some(15.0)), unit_value(periods[0].wind_speed()));
+        assert_eq!(("mi_h-1".to_owned(), Some(10.0)), unit_value(periods[3].wind_speed()));
+        assert_eq!(None, periods[0].probability_of_precipitation.value);
+        assert_eq!(Some(20.0), periods[1].probability_of_precipitation.value);
+
+        // Periods include their start but not their end
+        let time = "2023-10-21T21:59:59Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!("This Afternoon", forecast.properties.period_at(time).unwrap().name);
+        let time = "2023-10-21T22:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!("Tonight", forecast.properties.period_at(time).unwrap().name);
+        let time = "2023-10-25T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert!(forecast.properties.period_at(time).is_none());
+
+        // Grid squares that don't exist are reported as invalid points
+        let err = client.forecast("BOX", 1, 2, Trigger::Scheduled).await.unwrap_err();
+        assert!(
+            matches!(err, ClientError::InvalidPoint(ref p) if p == "BOX/1,2"),
+            "{}",
+            err
+        );
+
+        let out = encode(&reg);
+        for (code, count) in [("200", 1), ("404", 1)] {
+            let expected = format!(
+                "nws_exporter_api_responses_total{{endpoint=\"forecast\",code=\"{}\"}} {}\n",
+                code, count
+            );
+            assert!(out.contains(&expected), "{}", out);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hourly_forecast() {
+        let app = Router::new().route(
+            "/gridpoints/BOX/71,90/forecast/hourly",
+            get(|| async {
+                (
+                    [(header::CONTENT_TYPE, "application/geo+json")],
+                    include_str!("../../ext/fixtures/forecast_hourly_BOX.json"),
+                )
+            }),
+        );
+        let (client, reg) = client(&serve(app), Policy::none());
+
+        let hourly = client.hourly_forecast("BOX", 71, 90, Trigger::Scheduled).await.unwrap();
+        let periods = &hourly.properties.periods;
+        assert_eq!(24, periods.len());
+        for pair in periods.windows(2) {
+            assert_eq!(pair[0].end_time.utc(), pair[1].start_time.utc());
+            assert_eq!(3600, (pair[0].end_time.utc() - pair[0].start_time.utc()).num_seconds());
+        }
+
+        // Probability of precipitation is a measurement rather than a plain number
+        let p = &periods[12];
+        assert_eq!("wmoUnit:percent", p.probability_of_precipitation.unit_code);
+        assert_eq!(Some(40.0), p.probability_of_precipitation.value);
+        let t = p.temperature();
+        assert_eq!(("degF", Some(50.0)), (t.unit_code.as_str(), t.value));
+
+        let time = "2023-10-22T03:30:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(13, hourly.properties.period_at(time).unwrap().number);
+
+        let out = encode(&reg);
+        assert!(out.contains("nws_exporter_api_responses_total{endpoint=\"hourly_forecast\",code=\"200\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn test_gridpoint() {
+        let app = Router::new().route(
+            "/gridpoints/BOX/71,90",
+            get(|| async {
+                (
+                    [(header::CONTENT_TYPE, "application/geo+json")],
+                    include_str!("../../ext/fixtures/gridpoint_BOX.json"),
+                )
+            }),
+        );
+        let (client, _reg) = client(&serve(app), Policy::none());
+
+        let gridpoint = client.gridpoint("BOX", 71, 90, Trigger::Scheduled).await.unwrap();
+        let props = &gridpoint.properties;
+        assert_eq!("wmoUnit:mm", props.quantitative_precipitation.uom);
+        assert_eq!(6, props.quantitative_precipitation.values.len());
+        let first = &props.quantitative_precipitation.values[1];
+        assert_eq!("2023-10-21T18:00:00+00:00/PT6H", first.valid_time.raw());
+        assert_eq!(Some(2.54), first.value);
+
+        let time = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let (start, end) = (time("2023-10-21T15:00:00Z"), time("2023-10-22T15:00:00Z"));
+        let total = props.quantitative_precipitation.total(start, end);
+        assert_eq!("wmoUnit:mm", total.unit_code);
+        assert!((2.54 + 5.08 + 1.27 + 0.127 - total.value.unwrap()).abs() < 1e-9);
+        assert_eq!(Some(0.0), props.snowfall_amount.total(start, end).value);
+
+        // Values are for the interval that includes the time, if any
+        assert_eq!(Some(40.0), props.sky_cover.at(start).value);
+        assert_eq!(Some(55.0), props.sky_cover.at(time("2023-10-21T16:00:00Z")).value);
+        assert_eq!(None, props.sky_cover.at(time("2023-10-22T18:00:00Z")).value);
+        assert_eq!(
+            None,
+            props
+                .quantitative_precipitation
+                .total(time("2023-10-25T00:00:00Z"), time("2023-10-26T00:00:00Z"))
+                .value
+        );
+    }
+
+    #[tokio::test]
+    async fn test_alerts_for_zone() {
+        let app = Router::new().route(
+            "/alerts/active/zone/MAZ015",
+            get(|| async {
+                (
+                    [(header::CONTENT_TYPE, "application/geo+json")],
+                    include_str!("../../ext/fixtures/alerts_one.json"),
+                )
+            }),
+        );
+        let (client, _reg) = client(&serve(app), Policy::none());
+
+        let alerts = client.alerts_for_zone("MAZ015", Trigger::Scheduled).await.unwrap();
+        assert_eq!(1, alerts.features.len());
+        let props = &alerts.features[0].properties;
+        assert_eq!("Winter Storm Warning", props.event);
+        assert_eq!("Severe", props.severity);
+        assert_eq!("Expected", props.urgency);
+        let time = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(
+            Some(time("2024-01-07T04:00:00Z")),
+            props.onset.as_ref().map(|t| t.utc())
+        );
+        assert_eq!(
+            Some(time("2024-01-07T09:00:00Z")),
+            props.expires.as_ref().map(|t| t.utc())
+        );
+        assert_eq!(Some(time("2024-01-08T00:00:00Z")), props.ends.as_ref().map(|t| t.utc()));
+
+        let err = client.alerts_for_zone("MAZ999", Trigger::Scheduled).await.unwrap_err();
+        assert!(
+            matches!(err, ClientError::InvalidZone(ref z) if z == "MAZ999"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_alert_in_effect() {
+        let alerts: AlertCollection = serde_json::from_str(include_str!("../../ext/fixtures/alerts_one.json")).unwrap();
+        let alert = &alerts.features[0].properties;
+        let time = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+
+        // Alerts are in effect until they expire, even before their onset
+        assert!(alert.in_effect(time("2024-01-07T00:00:00Z")));
+        assert!(alert.in_effect(time("2024-01-07T08:59:59Z")));
+        assert!(!alert.in_effect(time("2024-01-07T09:00:00Z")));
+
+        let mut cancelled = alert.clone();
+        cancelled.message_type = "Cancel".to_owned();
+        assert!(!cancelled.in_effect(time("2024-01-07T00:00:00Z")));
+
+        let mut update = alert.clone();
+        update.message_type = "Update".to_owned();
+        assert!(update.in_effect(time("2024-01-07T00:00:00Z")));
+
+        let mut test = alert.clone();
+        test.status = "Test".to_owned();
+        assert!(!test.in_effect(time("2024-01-07T00:00:00Z")));
+
+        let mut no_expiry = alert.clone();
+        no_expiry.expires = None;
+        assert!(no_expiry.in_effect(time("2030-01-01T00:00:00Z")));
+    }
+
+    #[tokio::test]
+    async fn test_alerts_for_point() {
+        let app = Router::new().route(
+            "/alerts/active",
+            get(
+                |axum::extract::Query(query): axum::extract::Query<std::collections::HashMap<String, String>>| async move {
+                    let body = match query.get("point").map(|p| p.as_str()) {
+                        Some("42.3601,-71.0102") => include_str!("../../ext/fixtures/alerts_overlapping.json"),
+                        Some("42.37,-71.03") => include_str!("../../ext/fixtures/alerts_one.json"),
+                        _ => include_str!("../../ext/fixtures/alerts_none.json"),
+                    };
+                    ([(header::CONTENT_TYPE, "application/geo+json")], body)
+                },
+            ),
+        );
+        let (client, _reg) = client(&serve(app), Policy::none());
+
+        let alerts = client
+            .alerts_for_point(42.37, -71.03, Trigger::Scheduled)
+            .await
+            .unwrap();
+        assert_eq!(1, alerts.features.len());
+
+        // Coordinates are rounded the same way as for points
+        let alerts = client
+            .alerts_for_point(42.360081234, -71.010234567, Trigger::Scheduled)
+            .await
+            .unwrap();
+        let events: Vec<&str> = alerts.features.iter().map(|a| a.properties.event.as_str()).collect();
+        assert_eq!(
+            vec!["Winter Storm Warning", "Wind Advisory", "Coastal Flood Watch"],
+            events
+        );
+
+        // No alerts is the usual case rather than an error
+        let alerts = client.alerts_for_point(40.0, -100.0, Trigger::Scheduled).await.unwrap();
+        assert!(alerts.features.is_empty());
+
+        let err = client
+            .alerts_for_point(100.0, 0.0, Trigger::Scheduled)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::InvalidPoint(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_zone() {
+        let zone = |kind: &'static str, id: &'static str, name: &'static str| {
+            move || async move {
+                Json(serde_json::json!({
+                    "id": format!("https://api.weather.gov/zones/{}/{}", kind, id),
+                    "type": "Feature",
+                    "geometry": null,
+                    "properties": {
+                        "@id": format!("https://api.weather.gov/zones/{}/{}", kind, id),
+                        "@type": "wx:Zone",
+                        "id": id,
+                        "type": if kind == "county" { "county" } else { "public" },
+                        "name": name,
+                        "state": "MA",
+                    },
+                }))
+            }
+        };
+        let app = Router::new()
+            .route("/zones/forecast/MAZ015", get(zone("forecast", "MAZ015", "Suffolk")))
+            .route("/zones/county/MAC025", get(zone("county", "MAC025", "Suffolk")))
+            .route(
+                "/zones/forecast/ANZ230",
+                get(zone("forecast", "ANZ230", "Boston Harbor")),
+            );
+        let (client, reg) = client(&serve(app), Policy::none());
+
+        // The type of zone is determined from its ID
+        for (id, kind, name) in [
+            ("MAZ015", "public", "Suffolk"),
+            ("MAC025", "county", "Suffolk"),
+            ("ANZ230", "public", "Boston Harbor"),
+        ] {
+            let zone = client.zone(id, Trigger::Scheduled).await.unwrap();
+            assert_eq!(id, zone.properties.id);
+            assert_eq!(kind, zone.properties.type_);
+            assert_eq!(name, zone.properties.name);
+            assert_eq!(Some("MA"), zone.properties.state.as_deref());
+        }
+
+        for id in ["MAZ999", "MAC999"] {
+            let err = client.zone(id, Trigger::Scheduled).await.unwrap_err();
+            assert!(matches!(err, ClientError::InvalidZone(ref z) if z == id), "{}", err);
+            assert_eq!("invalid_zone", err.reason());
+        }
+
+        let out = encode(&reg);
+        for (code, count) in [("200", 3), ("404", 2)] {
+            let expected = format!(
+                "nws_exporter_api_responses_total{{endpoint=\"zone\",code=\"{}\"}} {}\n",
+                code, count
+            );
+            assert!(out.contains(&expected), "{}", out);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observations_since() {
+        let observation = |time: &str| {
+            let mut json: serde_json::Value =
+                serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap();
+            json["id"] = format!("https://api.weather.gov/stations/KBOS/observations/{}", time).into();
+            json["properties"]["timestamp"] = time.into();
+            json
+        };
+        let collection = serde_json::json!({
+            "type": "FeatureCollection",
+            "features": [
+                observation("2023-10-21T14:54:00+00:00"),
+                observation("2023-10-21T12:54:00+00:00"),
+                observation("2023-10-21T13:54:00-00:00"),
+            ],
+        });
+
+        let queries = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route(
+                "/stations/KBOS/observations",
+                get(
+                    move |State(queries): State<Arc<std::sync::Mutex<Vec<String>>>>,
+                          axum::extract::RawQuery(query): axum::extract::RawQuery| async move {
+                        queries.lock().unwrap().push(query.unwrap_or_default());
+                        Json(collection)
+                    },
+                ),
+            )
+            .with_state(queries.clone());
+        let (client, reg) = client(&serve(app), Policy::none());
+
+        // The start time is sent in UTC with whole seconds and observations are sorted oldest
+        // first, regardless of the order they're returned in
+        let start = DateTime::parse_from_rfc3339("2023-10-20T11:00:00.250-04:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let observations = client
+            .observations_since("KBOS", start, Trigger::Backfill)
+            .await
+            .unwrap()
+            .chronological();
+        assert_eq!(vec!["start=2023-10-20T15%3A00%3A00Z"], *queries.lock().unwrap());
+        let times: Vec<&str> = observations.iter().map(|o| o.properties.timestamp.raw()).collect();
+        assert_eq!(
+            vec![
+                "2023-10-21T12:54:00+00:00",
+                "2023-10-21T13:54:00-00:00",
+                "2023-10-21T14:54:00+00:00"
+            ],
+            times
+        );
+
+        let err = client
+            .observations_since("KXXX", start, Trigger::Backfill)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ClientError::InvalidStation(ref s) if s == "KXXX"),
+            "{}",
+            err
+        );
+
+        let out = encode(&reg);
+        assert!(
+            out.contains(
+                "nws_exporter_api_requests_total{station=\"KBOS\",endpoint=\"observations\",trigger=\"backfill\"} 1\n"
+            ),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        // Requests are always made at least once
+        assert_eq!(1, RetryPolicy::new(0, Duration::from_secs(1)).max_attempts);
+        assert_eq!(1, RetryPolicy::default().max_attempts);
+
+        let policy = RetryPolicy::new(3, Duration::from_millis(1500));
+        assert_eq!(Duration::from_millis(4500), policy.budget());
+
+        // Delays double up to a maximum and are shortened by up to half
+        for (attempt, max) in [(1, 250), (2, 500), (3, 1000), (6, 8000), (20, 8000)] {
+            for _ in 0..20 {
+                let delay = policy.delay(attempt).as_millis();
+                assert!(delay >= max / 2 && delay <= max, "attempt {}: {}ms", attempt, delay);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_is_retryable() {
+        let url: Url = "https://api.weather.gov/stations/KBOS".parse().unwrap();
+        for (status, retryable) in [
+            (StatusCode::TOO_MANY_REQUESTS, true),
+            (StatusCode::INTERNAL_SERVER_ERROR, true),
+            (StatusCode::BAD_GATEWAY, true),
+            (StatusCode::SERVICE_UNAVAILABLE, true),
+            (StatusCode::GATEWAY_TIMEOUT, true),
+            (StatusCode::BAD_REQUEST, false),
+            (StatusCode::FORBIDDEN, false),
+            (StatusCode::GONE, false),
+        ] {
+            let err = ClientError::Unexpected(status, url.clone(), Box::default());
+            assert_eq!(retryable, err.is_retryable(), "{}", status);
+        }
+
+        for err in [
+            ClientError::InvalidStation("KXXX".to_owned()),
+            ClientError::NotFound(url.clone()),
+            ClientError::Redirect(StatusCode::MOVED_PERMANENTLY, url.clone(), None),
+            ClientError::BodyTooLarge(url.clone(), 1024),
+            ClientError::Decode(url, serde_json::from_str::<Station>("{").unwrap_err()),
+        ] {
+            assert!(!err.is_retryable(), "{}", err);
+        }
+
+        // Nothing is listening on the port once the listener is dropped
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let err = Client::new().get(format!("http://{}/", addr)).send().await.unwrap_err();
+        assert!(ClientError::from(err).is_retryable());
+    }
+
+    /// API that responds to requests for KBOS with each of `statuses` in turn, returning the
+    /// station for a 200 status or once they run out. A status of 504 is a response that never
+    /// arrives instead. Also returns the number of requests made.
+    fn scripted(statuses: &[StatusCode]) -> (String, Arc<AtomicUsize>) {
+        let statuses = Arc::new(std::sync::Mutex::new(statuses.to_vec()));
+        let requests = Arc::new(AtomicUsize::new(0));
+        let count = requests.clone();
+        let app = Router::new().route(
+            "/stations/KBOS",
+            get(move || async move {
+                count.fetch_add(1, Ordering::AcqRel);
+                let status = {
+                    let mut statuses = statuses.lock().unwrap();
+                    (!statuses.is_empty()).then(|| statuses.remove(0))
+                };
+                match status.unwrap_or(StatusCode::OK) {
+                    StatusCode::OK => Json(station_json("KBOS")).into_response(),
+                    StatusCode::GATEWAY_TIMEOUT => {
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                        StatusCode::GATEWAY_TIMEOUT.into_response()
+                    }
+                    s => s.into_response(),
+                }
+            }),
+        );
+        (serve(app), requests)
+    }
+
+    /// Client for `base_url` that retries requests according to `policy`.
+    fn retrying(base_url: &str, policy: RetryPolicy) -> (NwsClient, Registry) {
+        let (client, reg) = client(base_url, Policy::none());
+        (client.with_retry(policy), reg)
+    }
+
+    fn retries(reg: &Registry) -> Option<u64> {
+        let prefix = "nws_exporter_api_request_retries_total{station=\"KBOS\"} ";
+        encode(reg)
+            .lines()
+            .find_map(|l| l.strip_prefix(prefix))
+            .map(|v| v.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_retry_then_succeed() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(500));
+        for statuses in [
+            vec![StatusCode::BAD_GATEWAY],
+            vec![StatusCode::SERVICE_UNAVAILABLE, StatusCode::INTERNAL_SERVER_ERROR],
+            vec![StatusCode::TOO_MANY_REQUESTS],
+            vec![StatusCode::GATEWAY_TIMEOUT, StatusCode::BAD_GATEWAY],
+        ] {
+            let (url, requests) = scripted(&statuses);
+            let (client, reg) = retrying(&url, policy);
+
+            let station = client.station("KBOS", Trigger::Scheduled).await.unwrap();
+            assert_eq!("KBOS", station.properties.station_identifier, "{:?}", statuses);
+            assert_eq!(statuses.len() + 1, requests.load(Ordering::Acquire), "{:?}", statuses);
+            assert_eq!(Some(statuses.len() as u64), retries(&reg), "{:?}", statuses);
+
+            // Each attempt is still counted as a request
+            let expected = format!(
+                "nws_exporter_api_requests_total{{station=\"KBOS\",endpoint=\"station\",trigger=\"scheduled\"}} {}\n",
+                statuses.len() + 1
+            );
+            assert!(encode(&reg).contains(&expected), "{:?}", statuses);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_not_made() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(500));
+        for (status, reason) in [
+            (StatusCode::NOT_FOUND, "invalid_station"),
+            (StatusCode::BAD_REQUEST, "unexpected_status"),
+            (StatusCode::FORBIDDEN, "unexpected_status"),
+            (StatusCode::MOVED_PERMANENTLY, "redirect"),
+        ] {
+            let (url, requests) = scripted(&[status]);
+            let (client, reg) = retrying(&url, policy);
+
+            let err = client.station("KBOS", Trigger::Scheduled).await.unwrap_err();
+            assert_eq!(reason, err.reason(), "{}", status);
+            assert_eq!(1, requests.load(Ordering::Acquire), "{}", status);
+            assert_eq!(None, retries(&reg), "{}", status);
+        }
+
+        // Requests are only made once without a retry policy
+        let (url, requests) = scripted(&[StatusCode::SERVICE_UNAVAILABLE]);
+        let (client, reg) = client(&url, Policy::none());
+        let err = client.station("KBOS", Trigger::Scheduled).await.unwrap_err();
+        assert_eq!(Some(StatusCode::SERVICE_UNAVAILABLE), err.status());
+        assert_eq!(1, requests.load(Ordering::Acquire));
+        assert_eq!(None, retries(&reg));
+    }
+
+    #[tokio::test]
+    async fn test_retry_attempts_exhausted() {
+        let (url, requests) = scripted(&[StatusCode::BAD_GATEWAY; 5]);
+        let (client, reg) = retrying(&url, RetryPolicy::new(3, Duration::from_millis(500)));
+
+        let err = client.station("KBOS", Trigger::Scheduled).await.unwrap_err();
+        assert_eq!(Some(StatusCode::BAD_GATEWAY), err.status());
+        assert_eq!(3, requests.load(Ordering::Acquire));
+        assert_eq!(Some(2), retries(&reg));
+
+        // Connection errors are retried the same way. Nothing is listening on the port once
+        // the listener is dropped.
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (client, reg) = retrying(
+            &format!("http://{}/", addr),
+            RetryPolicy::new(2, Duration::from_millis(500)),
+        );
+        let err = client.station("KBOS", Trigger::Scheduled).await.unwrap_err();
+        assert_eq!("connect", err.reason());
+        assert_eq!(Some(1), retries(&reg));
+    }
+
+    #[tokio::test]
+    async fn test_retry_time_budget() {
+        // Every attempt hangs. Attempts are cut off at the timeout and a retry isn't made once
+        // it couldn't finish within the time for all attempts.
+        let (url, requests) = scripted(&[StatusCode::GATEWAY_TIMEOUT; 5]);
+        let policy = RetryPolicy::new(3, Duration::from_millis(300));
+        let (client, reg) = retrying(&url, policy);
+
+        let start = Instant::now();
+        let err = client.station("KBOS", Trigger::Scheduled).await.unwrap_err();
+        let elapsed = start.elapsed();
+        assert_eq!("timeout", err.reason());
+        assert!(elapsed <= policy.budget() + Duration::from_millis(200), "{:?}", elapsed);
+
+        let made = requests.load(Ordering::Acquire);
+        assert!((2..=3).contains(&made), "{}", made);
+        assert_eq!(Some(made as u64 - 1), retries(&reg));
+    }
 }
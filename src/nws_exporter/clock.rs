@@ -0,0 +1,127 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use chrono::{DateTime, Utc};
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Source of the current time.
+///
+/// Anything that needs the current time should get it from a `Clock` so that it can be
+/// controlled in tests. Wall clock time is used for timestamps that are reported to users
+/// and monotonic time is used for measuring elapsed time.
+pub trait Clock: Debug + Send + Sync {
+    /// Current wall clock time.
+    fn now_wall(&self) -> DateTime<Utc>;
+
+    /// Current monotonic time.
+    fn now_monotonic(&self) -> Instant;
+}
+
+/// Clock that uses the system time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_wall(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn now_monotonic(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when told to, for testing.
+///
+/// Wall clock and monotonic time both advance together with `advance`. The wall clock may
+/// also be set independently to simulate the system clock being changed.
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    state: Mutex<(DateTime<Utc>, Duration)>,
+}
+
+impl ManualClock {
+    /// Create a new `ManualClock` starting at the given wall clock time.
+    pub fn new(wall: DateTime<Utc>) -> Self {
+        Self {
+            start: Instant::now(),
+            state: Mutex::new((wall, Duration::ZERO)),
+        }
+    }
+
+    /// Move both the wall clock and monotonic time forward.
+    pub fn advance(&self, amount: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.0 += chrono::Duration::from_std(amount).expect("duration out of range");
+        state.1 += amount;
+    }
+
+    /// Set the wall clock time without changing monotonic time.
+    pub fn set_wall(&self, wall: DateTime<Utc>) {
+        self.state.lock().unwrap().0 = wall;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_wall(&self) -> DateTime<Utc> {
+        self.state.lock().unwrap().0
+    }
+
+    fn now_monotonic(&self) -> Instant {
+        self.start + self.state.lock().unwrap().1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_manual_clock_advance() {
+        let start = Utc.with_ymd_and_hms(2023, 10, 21, 15, 0, 0).unwrap();
+        let clock = ManualClock::new(start);
+        let monotonic = clock.now_monotonic();
+        assert_eq!(start, clock.now_wall());
+        assert_eq!(monotonic, clock.now_monotonic());
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(start + chrono::Duration::seconds(90), clock.now_wall());
+        assert_eq!(Duration::from_secs(90), clock.now_monotonic() - monotonic);
+    }
+
+    #[test]
+    fn test_manual_clock_set_wall() {
+        let start = Utc.with_ymd_and_hms(2023, 10, 21, 15, 0, 0).unwrap();
+        let clock = ManualClock::new(start);
+        let monotonic = clock.now_monotonic();
+
+        // Jumps in either direction only change the wall clock
+        let earlier = start - chrono::Duration::days(1);
+        clock.set_wall(earlier);
+        assert_eq!(earlier, clock.now_wall());
+        assert_eq!(monotonic, clock.now_monotonic());
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(earlier + chrono::Duration::seconds(60), clock.now_wall());
+        assert_eq!(Duration::from_secs(60), clock.now_monotonic() - monotonic);
+    }
+}
@@ -0,0 +1,1043 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::geo::Coordinates;
+use crate::limits::{self, Range};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const DEFAULT_API_URL: &str = "https://api.weather.gov/";
+pub const DEFAULT_BIND_ADDR: ([u8; 4], u16) = ([0, 0, 0, 0], 9782);
+pub const DEFAULT_REFRESH_SECS: u64 = 300;
+pub const DEFAULT_REFRESH_COOLDOWN_SECS: u64 = 60;
+pub const DEFAULT_STALE_AFTER_FAILURES: u64 = 3;
+pub const DEFAULT_METADATA_REFRESH_SECS: u64 = 24 * 3600;
+pub const DEFAULT_FUTURE_TOLERANCE_SECS: u64 = 300;
+pub const DEFAULT_TIMEOUT_MILLIS: u64 = 5000;
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+pub const DEFAULT_MAX_BODY_BYTES: u64 = 16 * 1024 * 1024;
+pub const DEFAULT_FLATLINE_SECS: u64 = 6 * 3600;
+pub const MAX_VALUE_PRECISION: u32 = 15;
+pub const MAX_FORECAST_PERIODS: u32 = 6;
+pub const DEFAULT_FORECAST_REFRESH_SECS: u64 = 3600;
+pub const DEFAULT_ALERT_REFRESH_SECS: u64 = 300;
+pub const MAX_BACKFILL_HOURS: u32 = 24;
+pub const DEFAULT_QC_ACCEPT: [&str; 3] = ["V", "C", "S"];
+
+/// Error resulting from validating a `Config` or one of its sections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    NoStations,
+    FixturesWithStations,
+    EmptyStation,
+    InvalidApiUrl(String, String),
+    ZeroDuration(&'static str),
+    ZeroBodySize,
+    ZeroAttempts,
+    NoBindAddress,
+    DuplicateBindAddress(SocketAddr),
+    File(PathBuf, String),
+    UnknownThresholdSet(String, String),
+    InvalidThresholds(String),
+    ValuePrecision(u32),
+    ForecastPeriods(u32),
+    BackfillHours(u32),
+    InvalidZone(String),
+    StationLabelFormat(String),
+    InvalidLocation(String),
+    UnknownLimit(String),
+    InvalidLimit(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoStations => write!(f, "at least one station, location, or fixture file must be configured"),
+            Self::FixturesWithStations => write!(f, "fixture files cannot be combined with stations or a location"),
+            Self::EmptyStation => write!(f, "station IDs cannot be empty"),
+            Self::InvalidApiUrl(url, e) => write!(f, "invalid API URL {}: {}", url, e),
+            Self::ZeroDuration(name) => write!(f, "{} must be greater than zero", name),
+            Self::NoBindAddress => write!(f, "at least one bind address must be configured"),
+            Self::DuplicateBindAddress(addr) => write!(f, "bind address {} used more than once", addr),
+            Self::File(path, e) => write!(f, "cannot load config file {}: {}", path.display(), e),
+            Self::UnknownThresholdSet(station, set) => {
+                write!(f, "station {} uses unknown threshold set {}", station, set)
+            }
+            Self::InvalidThresholds(set) => write!(
+                f,
+                "warning threshold of {} must be colder than its advisory threshold",
+                set
+            ),
+            Self::ZeroBodySize => write!(f, "maximum response body size must be greater than zero"),
+            Self::ZeroAttempts => write!(f, "maximum attempts must be greater than zero"),
+            Self::ValuePrecision(p) => write!(
+                f,
+                "value precision {} is greater than the maximum of {}",
+                p, MAX_VALUE_PRECISION
+            ),
+            Self::InvalidLocation(location) => write!(
+                f,
+                "invalid location {}, latitude must be between -90 and 90 and longitude between -180 and 180",
+                location
+            ),
+            Self::ForecastPeriods(p) => write!(
+                f,
+                "forecast periods {} is greater than the maximum of {}",
+                p, MAX_FORECAST_PERIODS
+            ),
+            Self::BackfillHours(h) => write!(
+                f,
+                "backfill hours {} is greater than the maximum of {}",
+                h, MAX_BACKFILL_HOURS
+            ),
+            Self::InvalidZone(zone) => write!(
+                f,
+                "invalid alert zone {}, must be a forecast or county zone ID like MAZ015 or MAC025",
+                zone
+            ),
+            Self::UnknownLimit(field) => write!(f, "unknown field {} in limits", field),
+            Self::InvalidLimit(field) => {
+                write!(f, "minimum of limit for {} must not be greater than its maximum", field)
+            }
+            Self::StationLabelFormat(format) => {
+                write!(f, "unknown station label format {}, must be one of url or id", format)
+            }
+        }
+    }
+}
+
+impl error::Error for ConfigError {}
+
+/// Where the ID of a configured station came from. Stations are assumed to come from a
+/// config file unless otherwise specified.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StationSource {
+    Cli,
+    Env,
+    #[default]
+    Config,
+    Discovered,
+    Api,
+    Fixture,
+}
+
+impl StationSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cli => "cli",
+            Self::Env => "env",
+            Self::Config => "config",
+            Self::Discovered => "discovered",
+            Self::Api => "api",
+            Self::Fixture => "fixture",
+        }
+    }
+}
+
+impl fmt::Display for StationSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How stations are identified by the `station` label of metrics. Every metric for a station
+/// uses the same format so that they can be joined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StationLabelFormat {
+    /// The full URL of the station, e.g. `https://api.weather.gov/stations/KBOS`.
+    #[default]
+    Url,
+    /// The short identifier of the station, e.g. `KBOS`.
+    Id,
+}
+
+impl StationLabelFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Url => "url",
+            Self::Id => "id",
+        }
+    }
+
+    /// Get the label for a station from its full URL. The identifier of a station is the
+    /// last segment of its URL.
+    pub fn label(&self, url: &str) -> String {
+        match self {
+            Self::Url => url.to_owned(),
+            Self::Id => url.trim_end_matches('/').rsplit('/').next().unwrap_or(url).to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for StationLabelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for StationLabelFormat {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "url" => Ok(Self::Url),
+            "id" => Ok(Self::Id),
+            _ => Err(ConfigError::StationLabelFormat(s.to_owned())),
+        }
+    }
+}
+
+/// A station to fetch observations for along with where it was configured.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StationConfig {
+    pub id: String,
+    #[serde(default)]
+    pub source: StationSource,
+}
+
+impl StationConfig {
+    pub fn new<S: Into<String>>(id: S, source: StationSource) -> Self {
+        Self { id: id.into(), source }
+    }
+
+    /// Normalize the ID of each station and remove stations that were configured more than
+    /// once, keeping the first one (and its source). Removed stations are logged.
+    pub fn normalize(stations: Vec<Self>) -> Vec<Self> {
+        let mut seen = HashSet::new();
+        stations
+            .into_iter()
+            .map(|s| Self::new(normalize_station_id(&s.id), s.source))
+            .filter(|s| {
+                let first = seen.insert(s.id.clone());
+                if !first {
+                    tracing::info!(message = "ignoring duplicate station", station_id = %s.id, source = %s.source);
+                }
+                first
+            })
+            .collect()
+    }
+}
+
+/// Normalize a station ID by removing surrounding whitespace and converting it to uppercase.
+/// Station IDs in API URLs are case-sensitive but the NWS uses uppercase IDs for every station.
+pub fn normalize_station_id(id: &str) -> String {
+    id.trim().to_uppercase()
+}
+
+/// Return true if the ID is a forecast (`Z`) or county (`C`) zone ID: a two letter state or
+/// marine area code, the type of zone, and three digits, e.g. `MAZ015`.
+fn is_zone(id: &str) -> bool {
+    let b = id.as_bytes();
+    b.len() == 6
+        && b[..2].iter().all(u8::is_ascii_uppercase)
+        && (b[2] == b'Z' || b[2] == b'C')
+        && b[3..].iter().all(u8::is_ascii_digit)
+}
+
+/// All options for running the exporter, independent of where they were set.
+///
+/// Each section is used to construct the corresponding subsystem. Sections that are
+/// missing when deserializing use the same defaults as the command line.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub stations: Vec<StationConfig>,
+    /// Also fetch observations for the station nearest to these coordinates, found at startup.
+    pub location: Option<Coordinates>,
+    pub api: ApiConfig,
+    pub update: UpdateConfig,
+    pub web: WebConfig,
+    pub output: OutputConfig,
+    pub reporting: ReportingConfig,
+    pub cold_risk: ColdRiskConfig,
+}
+
+impl Config {
+    /// Check that the config describes something the exporter can run.
+    ///
+    /// # Errors
+    ///
+    /// The first problem found with the stations or any section is returned.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.stations.iter().any(|s| s.id.trim().is_empty()) {
+            return Err(ConfigError::EmptyStation);
+        }
+
+        if let Some(c) = self.location {
+            if !(-90.0..=90.0).contains(&c.latitude) || !(-180.0..=180.0).contains(&c.longitude) {
+                return Err(ConfigError::InvalidLocation(format!("{},{}", c.latitude, c.longitude)));
+            }
+        }
+
+        let stations = self.stations.is_empty() && self.location.is_none();
+        match (stations, self.api.fixture_files.is_empty()) {
+            (true, true) => return Err(ConfigError::NoStations),
+            (false, false) => return Err(ConfigError::FixturesWithStations),
+            _ => {}
+        }
+
+        self.api.validate()?;
+        self.update.validate()?;
+        self.web.validate()?;
+        self.output.validate()?;
+        self.reporting.validate()?;
+        self.cold_risk.validate()
+    }
+}
+
+/// Options for fetching stations and observations from the Weather.gov API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub url: String,
+    pub timeout_millis: u64,
+    pub max_redirects: usize,
+    pub follow_redirects: bool,
+    pub max_body_bytes: u64,
+    /// Make requests that fail with a retryable error up to this many times in total.
+    pub max_attempts: u32,
+    pub fixture_files: Vec<PathBuf>,
+}
+
+impl ApiConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_millis)
+    }
+
+    /// # Errors
+    ///
+    /// An error is returned if the API URL can't be parsed or the timeout, maximum body
+    /// size, or maximum attempts is zero.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        reqwest::Url::parse(&self.url).map_err(|e| ConfigError::InvalidApiUrl(self.url.clone(), e.to_string()))?;
+        if self.timeout_millis == 0 {
+            return Err(ConfigError::ZeroDuration("API timeout"));
+        }
+        if self.max_body_bytes == 0 {
+            return Err(ConfigError::ZeroBodySize);
+        }
+        if self.max_attempts == 0 {
+            return Err(ConfigError::ZeroAttempts);
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            url: DEFAULT_API_URL.to_owned(),
+            timeout_millis: DEFAULT_TIMEOUT_MILLIS,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            follow_redirects: true,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            fixture_files: Vec::new(),
+        }
+    }
+}
+
+/// Options for how often observations are fetched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateConfig {
+    pub refresh_secs: u64,
+    pub refresh_cooldown_secs: u64,
+    /// Remove metrics for a station after this many consecutive failed fetches, or never if zero.
+    pub stale_after_failures: u64,
+    /// Skip observations older than this many seconds, or never if zero.
+    pub max_observation_age_secs: u64,
+    /// Observations timestamped up to this many seconds in the future are expected.
+    pub future_tolerance_secs: u64,
+    /// Skip observations timestamped further in the future than the tolerance instead of
+    /// exporting them with an age of zero.
+    pub reject_future_observations: bool,
+    /// How stations are identified in metrics.
+    pub station_label: StationLabelFormat,
+    /// Fetch station metadata again after this many seconds, or never if zero.
+    pub metadata_refresh_secs: u64,
+    /// Export the ID of the most recent observation for each station.
+    pub expose_observation_id: bool,
+    /// Export forecasts for this many periods for each station, or don't fetch forecasts if zero.
+    pub forecast_periods: u32,
+    pub forecast_refresh_secs: u64,
+    /// Fetch active alerts for these forecast or county zones.
+    pub alert_zones: Vec<String>,
+    /// Fetch active alerts for the location of each station.
+    pub station_alerts: bool,
+    pub alert_refresh_secs: u64,
+    /// Replay observations from this many hours before startup to restore daily extremes
+    /// and pressure tendency, or don't if zero.
+    pub backfill_hours: u32,
+}
+
+impl UpdateConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.refresh_secs)
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.refresh_cooldown_secs)
+    }
+
+    /// Maximum age of an observation before it's skipped, or `None` if any age is allowed.
+    pub fn max_observation_age(&self) -> Option<chrono::Duration> {
+        match self.max_observation_age_secs {
+            0 => None,
+            secs => Some(chrono::Duration::seconds(secs as i64)),
+        }
+    }
+
+    /// How far in the future an observation may be timestamped before it's considered wrong.
+    pub fn future_tolerance(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.future_tolerance_secs as i64)
+    }
+
+    /// Interval between fetches of forecasts, or `None` if forecasts aren't fetched.
+    pub fn forecast_interval(&self) -> Option<Duration> {
+        match self.forecast_periods {
+            0 => None,
+            _ => Some(Duration::from_secs(self.forecast_refresh_secs)),
+        }
+    }
+
+    /// Interval between fetches of alerts, or `None` if there are no zones or stations to
+    /// fetch alerts for.
+    pub fn alert_interval(&self) -> Option<Duration> {
+        if !self.alerts_enabled() {
+            None
+        } else {
+            Some(Duration::from_secs(self.alert_refresh_secs))
+        }
+    }
+
+    /// Return true if alerts are fetched for any zones or stations.
+    pub fn alerts_enabled(&self) -> bool {
+        self.station_alerts || !self.alert_zones.is_empty()
+    }
+
+    /// How far before startup observations are replayed, or `None` if they aren't.
+    pub fn backfill(&self) -> Option<chrono::Duration> {
+        match self.backfill_hours {
+            0 => None,
+            hours => Some(chrono::Duration::hours(i64::from(hours))),
+        }
+    }
+
+    /// Interval between fetches of station metadata, or `None` if it's only fetched at startup.
+    pub fn metadata_interval(&self) -> Option<Duration> {
+        match self.metadata_refresh_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// An error is returned if the refresh interval is zero, if forecasts are fetched and
+    /// their refresh interval is zero, or if there are more forecast periods than
+    /// `MAX_FORECAST_PERIODS`. The same goes for alerts and any alert zones that aren't valid
+    /// zone IDs, and for backfilling more than `MAX_BACKFILL_HOURS`.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.refresh_secs == 0 {
+            return Err(ConfigError::ZeroDuration("refresh interval"));
+        }
+        if self.forecast_periods > MAX_FORECAST_PERIODS {
+            return Err(ConfigError::ForecastPeriods(self.forecast_periods));
+        }
+        if self.forecast_periods > 0 && self.forecast_refresh_secs == 0 {
+            return Err(ConfigError::ZeroDuration("forecast refresh interval"));
+        }
+        if let Some(zone) = self.alert_zones.iter().find(|z| !is_zone(z)) {
+            return Err(ConfigError::InvalidZone(zone.clone()));
+        }
+        if self.alerts_enabled() && self.alert_refresh_secs == 0 {
+            return Err(ConfigError::ZeroDuration("alert refresh interval"));
+        }
+        if self.backfill_hours > MAX_BACKFILL_HOURS {
+            return Err(ConfigError::BackfillHours(self.backfill_hours));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            refresh_secs: DEFAULT_REFRESH_SECS,
+            refresh_cooldown_secs: DEFAULT_REFRESH_COOLDOWN_SECS,
+            stale_after_failures: DEFAULT_STALE_AFTER_FAILURES,
+            max_observation_age_secs: 0,
+            future_tolerance_secs: DEFAULT_FUTURE_TOLERANCE_SECS,
+            reject_future_observations: false,
+            station_label: StationLabelFormat::default(),
+            metadata_refresh_secs: DEFAULT_METADATA_REFRESH_SECS,
+            expose_observation_id: false,
+            forecast_periods: 0,
+            forecast_refresh_secs: DEFAULT_FORECAST_REFRESH_SECS,
+            alert_zones: Vec::new(),
+            station_alerts: false,
+            alert_refresh_secs: DEFAULT_ALERT_REFRESH_SECS,
+            backfill_hours: 0,
+        }
+    }
+}
+
+/// Options for the HTTP server that exposes metrics.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebConfig {
+    /// Addresses to listen on. A server is started for each one.
+    pub bind: Vec<SocketAddr>,
+    /// Export metrics about the tokio runtime.
+    pub runtime_metrics: bool,
+}
+
+impl WebConfig {
+    /// # Errors
+    ///
+    /// An error is returned if there are no addresses to bind to or the same address is
+    /// used more than once.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.bind.is_empty() {
+            return Err(ConfigError::NoBindAddress);
+        }
+
+        for (i, addr) in self.bind.iter().enumerate() {
+            if self.bind[..i].contains(addr) {
+                return Err(ConfigError::DuplicateBindAddress(*addr));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            bind: vec![DEFAULT_BIND_ADDR.into()],
+            runtime_metrics: false,
+        }
+    }
+}
+
+/// Options controlling how values are exported.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Round exported values to this many decimal places or export them as-is when `None`.
+    pub value_precision: Option<u32>,
+    /// Compute values that the API didn't report from other measurements in the observation.
+    pub compute_derived: bool,
+    /// Export visibility at the reported maximum (10 miles) as exactly 16093 meters.
+    pub clamp_visibility: bool,
+    /// Don't export measurements that are inconsistent with the rest of the observation.
+    pub reject_inconsistent: bool,
+    /// Quality control codes of measurements to export. Measurements with other known codes
+    /// are rejected.
+    pub qc_accept: Vec<String>,
+    /// Plausible ranges of values by field, used instead of the default range for the field.
+    pub limits: HashMap<String, Range>,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            value_precision: None,
+            compute_derived: false,
+            clamp_visibility: false,
+            reject_inconsistent: false,
+            qc_accept: DEFAULT_QC_ACCEPT.map(String::from).to_vec(),
+            limits: HashMap::new(),
+        }
+    }
+}
+
+impl OutputConfig {
+    /// # Errors
+    ///
+    /// An error is returned if the value precision is greater than `MAX_VALUE_PRECISION` or
+    /// if a range is for an unknown field or its minimum is greater than its maximum.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(p) = self.value_precision.filter(|p| *p > MAX_VALUE_PRECISION) {
+            return Err(ConfigError::ValuePrecision(p));
+        }
+
+        for (field, range) in self.limits.iter() {
+            if !limits::is_known(field) {
+                return Err(ConfigError::UnknownLimit(field.clone()));
+            }
+            if range.min > range.max {
+                return Err(ConfigError::InvalidLimit(field.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Options for detecting stations that have stopped reporting new observations.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportingConfig {
+    /// Stations are considered to have stopped reporting when their observation hasn't
+    /// changed for this many seconds.
+    pub flatline_secs: u64,
+    /// File used to persist the most recent observation for each station across restarts.
+    pub state_file: Option<PathBuf>,
+}
+
+impl ReportingConfig {
+    pub fn flatline(&self) -> Duration {
+        Duration::from_secs(self.flatline_secs)
+    }
+
+    /// # Errors
+    ///
+    /// An error is returned if the flatline threshold is zero.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.flatline_secs == 0 {
+            return Err(ConfigError::ZeroDuration("flatline threshold"));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for ReportingConfig {
+    fn default() -> Self {
+        Self {
+            flatline_secs: DEFAULT_FLATLINE_SECS,
+            state_file: None,
+        }
+    }
+}
+
+/// Wind chill thresholds for cold weather advisories and warnings, in degrees celsius.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColdThresholds {
+    pub advisory: f64,
+    pub warning: f64,
+}
+
+impl Default for ColdThresholds {
+    /// Thresholds of -20 and -35 fahrenheit used by many forecast offices.
+    fn default() -> Self {
+        Self {
+            advisory: -28.9,
+            warning: -37.2,
+        }
+    }
+}
+
+/// Thresholds used for each station when computing cold risk levels.
+///
+/// Advisory and warning criteria differ between forecast offices so stations may be
+/// mapped to a named set of thresholds. Stations that aren't mapped use the default set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColdRiskConfig {
+    pub default: ColdThresholds,
+    pub threshold_sets: HashMap<String, ColdThresholds>,
+    pub stations: HashMap<String, String>,
+}
+
+impl ColdRiskConfig {
+    /// Thresholds to use for the station with the given ID.
+    pub fn thresholds(&self, station: &str) -> &ColdThresholds {
+        self.stations
+            .get(station)
+            .and_then(|set| self.threshold_sets.get(set))
+            .unwrap_or(&self.default)
+    }
+
+    /// # Errors
+    ///
+    /// An error is returned if a station is mapped to a threshold set that doesn't exist or
+    /// if the warning threshold of any set isn't colder than its advisory threshold.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some((station, set)) = self
+            .stations
+            .iter()
+            .find(|(_, set)| !self.threshold_sets.contains_key(*set))
+        {
+            return Err(ConfigError::UnknownThresholdSet(station.clone(), set.clone()));
+        }
+
+        let sets = [("default", &self.default)]
+            .into_iter()
+            .chain(self.threshold_sets.iter().map(|(name, t)| (name.as_str(), t)));
+        for (name, t) in sets {
+            if t.warning >= t.advisory {
+                return Err(ConfigError::InvalidThresholds(name.to_owned()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Options that can be set in a TOML config file.
+///
+/// Only stations and settings without a command line equivalent are read from the file.
+/// Unknown keys are rejected so that options that would be ignored aren't silently accepted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub stations: Vec<String>,
+    pub cold_risk: ColdRiskConfig,
+    pub limits: HashMap<String, Range>,
+}
+
+impl FileConfig {
+    /// Read and parse a config file.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the file can't be read or isn't valid.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| ConfigError::File(path.to_owned(), e.to_string()))?;
+        toml::from_str(&contents).map_err(|e| ConfigError::File(path.to_owned(), e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_fixtures() {
+        let config = Config {
+            api: ApiConfig {
+                fixture_files: vec![PathBuf::from("ext/fixtures/KBOS.json")],
+                ..ApiConfig::default()
+            },
+            ..Config::default()
+        };
+        assert_eq!(Ok(()), config.validate());
+
+        let mixed = Config {
+            stations: vec![StationConfig::new("KJFK", StationSource::Cli)],
+            ..config.clone()
+        };
+        assert_eq!(Err(ConfigError::FixturesWithStations), mixed.validate());
+
+        let located = Config {
+            location: Some(Coordinates::new(-71.01, 42.36)),
+            ..config
+        };
+        assert_eq!(Err(ConfigError::FixturesWithStations), located.validate());
+    }
+
+    #[test]
+    fn test_station_source_serialize() {
+        for (source, name) in [
+            (StationSource::Cli, "cli"),
+            (StationSource::Env, "env"),
+            (StationSource::Config, "config"),
+            (StationSource::Discovered, "discovered"),
+            (StationSource::Api, "api"),
+            (StationSource::Fixture, "fixture"),
+        ] {
+            assert_eq!(name, source.as_str());
+            assert_eq!(name, source.to_string());
+
+            let station = StationConfig::new("KBOS", source);
+            let json = serde_json::to_string(&station).unwrap();
+            assert_eq!(format!(r#"{{"id":"KBOS","source":"{}"}}"#, name), json);
+            assert_eq!(station, serde_json::from_str(&json).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_station_source_default_config() {
+        let station: StationConfig = serde_json::from_str(r#"{"id":"KBOS"}"#).unwrap();
+        assert_eq!(StationSource::Config, station.source);
+    }
+
+    #[test]
+    fn test_normalize_keeps_first_source() {
+        let stations = StationConfig::normalize(vec![
+            StationConfig::new(" kbos ", StationSource::Cli),
+            StationConfig::new("KJFK", StationSource::Env),
+            StationConfig::new("KBOS", StationSource::Config),
+            StationConfig::new("kjfk", StationSource::Discovered),
+        ]);
+
+        assert_eq!(
+            vec![
+                StationConfig::new("KBOS", StationSource::Cli),
+                StationConfig::new("KJFK", StationSource::Env),
+            ],
+            stations
+        );
+    }
+
+    #[test]
+    fn test_web_validate_bind() {
+        let localhost: SocketAddr = "127.0.0.1:9782".parse().unwrap();
+        let vlan: SocketAddr = "10.0.20.5:9782".parse().unwrap();
+
+        let config = WebConfig {
+            bind: vec![vlan, localhost],
+            ..WebConfig::default()
+        };
+        assert_eq!(Ok(()), config.validate());
+
+        let none = WebConfig {
+            bind: Vec::new(),
+            ..WebConfig::default()
+        };
+        assert_eq!(Err(ConfigError::NoBindAddress), none.validate());
+
+        let duplicate = WebConfig {
+            bind: vec![localhost, vlan, localhost],
+            ..WebConfig::default()
+        };
+        assert_eq!(Err(ConfigError::DuplicateBindAddress(localhost)), duplicate.validate());
+    }
+
+    #[test]
+    fn test_cold_risk_file() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            stations = ["KINL", "KBOS"]
+
+            [cold_risk.default]
+            advisory = -28.9
+            warning = -37.2
+
+            [cold_risk.threshold_sets.northern_plains]
+            advisory = -34.4
+            warning = -40.0
+
+            [cold_risk.stations]
+            KINL = "northern_plains"
+            "#,
+        )
+        .unwrap();
+
+        let cold_risk = file.cold_risk;
+        assert_eq!(Ok(()), cold_risk.validate());
+        assert_eq!(
+            &ColdThresholds {
+                advisory: -34.4,
+                warning: -40.0
+            },
+            cold_risk.thresholds("KINL")
+        );
+        assert_eq!(&ColdThresholds::default(), cold_risk.thresholds("KBOS"));
+    }
+
+    #[test]
+    fn test_cold_risk_validate() {
+        let unknown = ColdRiskConfig {
+            stations: HashMap::from([("KINL".to_owned(), "missing".to_owned())]),
+            ..ColdRiskConfig::default()
+        };
+        assert_eq!(
+            Err(ConfigError::UnknownThresholdSet(
+                "KINL".to_owned(),
+                "missing".to_owned()
+            )),
+            unknown.validate()
+        );
+
+        let inverted = ColdRiskConfig {
+            threshold_sets: HashMap::from([(
+                "backwards".to_owned(),
+                ColdThresholds {
+                    advisory: -40.0,
+                    warning: -30.0,
+                },
+            )]),
+            ..ColdRiskConfig::default()
+        };
+        assert_eq!(
+            Err(ConfigError::InvalidThresholds("backwards".to_owned())),
+            inverted.validate()
+        );
+    }
+
+    #[test]
+    fn test_station_label_format() {
+        let url = "https://api.weather.gov/stations/KBOS";
+        assert_eq!(url, StationLabelFormat::Url.label(url));
+        assert_eq!("KBOS", StationLabelFormat::Id.label(url));
+        assert_eq!(
+            "KBOS",
+            StationLabelFormat::Id.label("https://api.weather.gov/stations/KBOS/")
+        );
+
+        assert_eq!(Ok(StationLabelFormat::Url), "url".parse());
+        assert_eq!(Ok(StationLabelFormat::Id), "id".parse());
+        assert!(matches!(
+            "identifier".parse::<StationLabelFormat>(),
+            Err(ConfigError::StationLabelFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalize_preserves_order() {
+        let stations = StationConfig::normalize(vec![
+            StationConfig::new("kjfk", StationSource::Cli),
+            StationConfig::new("\tKbos\n", StationSource::Cli),
+            StationConfig::new("KJFK ", StationSource::Cli),
+            StationConfig::new("klga", StationSource::Cli),
+        ]);
+
+        let ids: Vec<&str> = stations.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(vec!["KJFK", "KBOS", "KLGA"], ids);
+    }
+
+    #[test]
+    fn test_validate_empty_station() {
+        for id in ["", "   "] {
+            let config = Config {
+                stations: StationConfig::normalize(vec![
+                    StationConfig::new("KBOS", StationSource::Cli),
+                    StationConfig::new(id, StationSource::Cli),
+                ]),
+                ..Config::default()
+            };
+            assert!(matches!(config.validate(), Err(ConfigError::EmptyStation)), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn test_limits_file() {
+        let file: FileConfig = toml::from_str(
+            r#"
+            [limits.temperature]
+            min = -95.0
+            max = 60.0
+            "#,
+        )
+        .unwrap();
+
+        let output = OutputConfig {
+            limits: file.limits,
+            ..OutputConfig::default()
+        };
+        assert_eq!(Ok(()), output.validate());
+        assert_eq!(Some(&Range::new(-95.0, 60.0)), output.limits.get("temperature"));
+
+        let unknown = OutputConfig {
+            limits: HashMap::from([("temprature".to_owned(), Range::new(-95.0, 60.0))]),
+            ..OutputConfig::default()
+        };
+        assert_eq!(
+            Err(ConfigError::UnknownLimit("temprature".to_owned())),
+            unknown.validate()
+        );
+
+        let inverted = OutputConfig {
+            limits: HashMap::from([("temperature".to_owned(), Range::new(60.0, -95.0))]),
+            ..OutputConfig::default()
+        };
+        assert_eq!(
+            Err(ConfigError::InvalidLimit("temperature".to_owned())),
+            inverted.validate()
+        );
+    }
+
+    #[test]
+    fn test_forecast_interval() {
+        // Forecasts are off unless there are periods to export
+        let config = UpdateConfig::default();
+        assert_eq!(None, config.forecast_interval());
+        config.validate().unwrap();
+
+        let config = UpdateConfig {
+            forecast_periods: MAX_FORECAST_PERIODS,
+            forecast_refresh_secs: 7200,
+            ..UpdateConfig::default()
+        };
+        assert_eq!(Some(Duration::from_secs(7200)), config.forecast_interval());
+        config.validate().unwrap();
+
+        let config = UpdateConfig {
+            forecast_periods: MAX_FORECAST_PERIODS + 1,
+            ..UpdateConfig::default()
+        };
+        assert_eq!(
+            Err(ConfigError::ForecastPeriods(MAX_FORECAST_PERIODS + 1)),
+            config.validate()
+        );
+
+        let config = UpdateConfig {
+            forecast_periods: 4,
+            forecast_refresh_secs: 0,
+            ..UpdateConfig::default()
+        };
+        assert_eq!(
+            Err(ConfigError::ZeroDuration("forecast refresh interval")),
+            config.validate()
+        );
+    }
+
+    #[test]
+    fn test_backfill() {
+        let config = UpdateConfig::default();
+        assert_eq!(None, config.backfill());
+        config.validate().unwrap();
+
+        let config = UpdateConfig {
+            backfill_hours: MAX_BACKFILL_HOURS,
+            ..UpdateConfig::default()
+        };
+        assert_eq!(Some(chrono::Duration::hours(24)), config.backfill());
+        config.validate().unwrap();
+
+        let config = UpdateConfig {
+            backfill_hours: MAX_BACKFILL_HOURS + 1,
+            ..UpdateConfig::default()
+        };
+        assert_eq!(
+            Err(ConfigError::BackfillHours(MAX_BACKFILL_HOURS + 1)),
+            config.validate()
+        );
+    }
+
+    #[test]
+    fn test_api_max_attempts() {
+        let config = ApiConfig::default();
+        assert_eq!(DEFAULT_MAX_ATTEMPTS, config.max_attempts);
+        config.validate().unwrap();
+
+        let config = ApiConfig {
+            max_attempts: 0,
+            ..ApiConfig::default()
+        };
+        assert_eq!(Err(ConfigError::ZeroAttempts), config.validate());
+    }
+}
@@ -0,0 +1,213 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::client::ObservationProperties;
+use crate::derive::{celsius, kilometers_per_hour};
+use crate::units::{convert, Unit};
+use std::fmt;
+
+/// How much two temperatures may differ in the wrong direction before they're considered
+/// inconsistent, in degrees celsius. This allows for rounding by stations.
+const TEMPERATURE_TOLERANCE: f64 = 0.5;
+
+/// A combination of measurements in an observation that isn't physically possible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Inconsistency {
+    DewpointAboveTemperature,
+    HumidityOutOfRange,
+    WindChillAboveTemperature,
+    GustBelowSpeed,
+}
+
+impl Inconsistency {
+    /// Find all inconsistencies between measurements in an observation. Measurements that
+    /// are missing are never inconsistent.
+    pub fn find(props: &ObservationProperties) -> Vec<Self> {
+        let t = celsius(&props.temperature);
+        let checks = [
+            (
+                Self::DewpointAboveTemperature,
+                t.zip(celsius(&props.dewpoint))
+                    .map(|(t, td)| dewpoint_above_temperature(t, td)),
+            ),
+            (
+                Self::HumidityOutOfRange,
+                convert(&props.relative_humidity, Unit::Percent).map(humidity_out_of_range),
+            ),
+            (
+                Self::WindChillAboveTemperature,
+                t.zip(celsius(&props.wind_chill))
+                    .map(|(t, wc)| wind_chill_above_temperature(t, wc)),
+            ),
+            (
+                Self::GustBelowSpeed,
+                kilometers_per_hour(&props.wind_speed)
+                    .zip(kilometers_per_hour(&props.wind_gust))
+                    .map(|(speed, gust)| gust_below_speed(speed, gust)),
+            ),
+        ];
+
+        checks
+            .into_iter()
+            .filter(|(_, inconsistent)| inconsistent.unwrap_or(false))
+            .map(|(check, _)| check)
+            .collect()
+    }
+
+    /// Remove the value of the measurement that is most likely wrong for this inconsistency.
+    pub fn suppress(&self, props: &mut ObservationProperties) {
+        let m = match self {
+            Self::DewpointAboveTemperature => &mut props.dewpoint,
+            Self::HumidityOutOfRange => &mut props.relative_humidity,
+            Self::WindChillAboveTemperature => &mut props.wind_chill,
+            Self::GustBelowSpeed => &mut props.wind_gust,
+        };
+
+        m.value = None;
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::DewpointAboveTemperature => "dewpoint_above_temperature",
+            Self::HumidityOutOfRange => "humidity_out_of_range",
+            Self::WindChillAboveTemperature => "wind_chill_above_temperature",
+            Self::GustBelowSpeed => "gust_below_speed",
+        }
+    }
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Dewpoint can't be higher than the temperature since air can't hold more than 100% humidity.
+pub fn dewpoint_above_temperature(temperature: f64, dewpoint: f64) -> bool {
+    dewpoint > temperature + TEMPERATURE_TOLERANCE
+}
+
+/// Relative humidity must be between 0 and 100.
+pub fn humidity_out_of_range(relative_humidity: f64) -> bool {
+    !(0.0..=100.0).contains(&relative_humidity)
+}
+
+/// Wind can only make it feel colder.
+pub fn wind_chill_above_temperature(temperature: f64, wind_chill: f64) -> bool {
+    wind_chill > temperature + TEMPERATURE_TOLERANCE
+}
+
+/// Gusts are the peak wind speed so they can't be slower than the sustained speed.
+pub fn gust_below_speed(speed: f64, gust: f64) -> bool {
+    gust < speed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Observation;
+
+    fn properties<F: FnOnce(&mut serde_json::Value)>(f: F) -> ObservationProperties {
+        let mut json: serde_json::Value = serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap();
+        f(&mut json["properties"]);
+        serde_json::from_value::<Observation>(json).unwrap().properties
+    }
+
+    #[test]
+    fn test_dewpoint_above_temperature() {
+        assert!(!dewpoint_above_temperature(20.0, 10.0));
+        assert!(!dewpoint_above_temperature(20.0, 20.0));
+        assert!(!dewpoint_above_temperature(20.0, 20.5));
+        assert!(dewpoint_above_temperature(20.0, 20.6));
+    }
+
+    #[test]
+    fn test_humidity_out_of_range() {
+        assert!(!humidity_out_of_range(0.0));
+        assert!(!humidity_out_of_range(49.4));
+        assert!(!humidity_out_of_range(100.0));
+        assert!(humidity_out_of_range(-0.1));
+        assert!(humidity_out_of_range(100.1));
+    }
+
+    #[test]
+    fn test_wind_chill_above_temperature() {
+        assert!(!wind_chill_above_temperature(-5.0, -12.0));
+        assert!(!wind_chill_above_temperature(-5.0, -4.5));
+        assert!(wind_chill_above_temperature(-5.0, -4.4));
+    }
+
+    #[test]
+    fn test_gust_below_speed() {
+        assert!(!gust_below_speed(20.0, 35.0));
+        assert!(!gust_below_speed(20.0, 20.0));
+        assert!(gust_below_speed(20.0, 19.9));
+    }
+
+    #[test]
+    fn test_find() {
+        assert!(Inconsistency::find(&properties(|_| {})).is_empty());
+
+        // Units are normalized before comparing, 70F is 21.1C which is above the 16.7C temperature
+        let props = properties(|p| {
+            p["dewpoint"] = serde_json::json!({"unitCode": "wmoUnit:degF", "value": 70.0});
+            p["windChill"] = serde_json::json!({"unitCode": "wmoUnit:degF", "value": 70.0});
+        });
+        assert_eq!(
+            vec![
+                Inconsistency::DewpointAboveTemperature,
+                Inconsistency::WindChillAboveTemperature,
+            ],
+            Inconsistency::find(&props)
+        );
+
+        let props = properties(|p| {
+            p["relativeHumidity"]["value"] = 104.0.into();
+            p["windGust"]["value"] = 10.0.into();
+        });
+        assert_eq!(
+            vec![Inconsistency::HumidityOutOfRange, Inconsistency::GustBelowSpeed],
+            Inconsistency::find(&props)
+        );
+
+        // Missing measurements are never inconsistent
+        let props = properties(|p| {
+            p["temperature"]["value"] = serde_json::Value::Null;
+            p["dewpoint"]["value"] = 70.0.into();
+            p["windSpeed"]["value"] = serde_json::Value::Null;
+        });
+        assert!(Inconsistency::find(&props).is_empty());
+    }
+
+    #[test]
+    fn test_suppress() {
+        let mut props = properties(|p| {
+            p["dewpoint"]["value"] = 30.0.into();
+            p["windGust"]["value"] = 10.0.into();
+        });
+        for i in Inconsistency::find(&props) {
+            i.suppress(&mut props);
+        }
+
+        assert_eq!(None, props.dewpoint.value);
+        assert_eq!(None, props.wind_gust.value);
+        assert!(props.temperature.value.is_some());
+        assert!(props.wind_speed.value.is_some());
+        assert!(Inconsistency::find(&props).is_empty());
+    }
+}
@@ -0,0 +1,573 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::client::Measurement;
+use crate::units::{convert, Unit};
+use chrono::{DateTime, NaiveDate, Utc};
+
+const KILOMETERS_PER_MILE: f64 = 1.609344;
+const MILLIS_PER_HOUR: f64 = 3_600_000.0;
+
+/// Lowest temperature that heat index is reported for, in degrees celsius (80 fahrenheit).
+const HEAT_INDEX_MIN_CELSIUS: f64 = 26.7;
+
+/// Highest temperature that wind chill is defined for, in degrees celsius (50 fahrenheit).
+const WIND_CHILL_MAX_CELSIUS: f64 = 10.0;
+
+/// Lowest wind speed that wind chill is defined for, in kilometers per hour (3 mph).
+const WIND_CHILL_MIN_KMH: f64 = 4.8;
+
+/// Get a temperature in degrees celsius, converting from other units if needed, or `None`
+/// if the temperature is missing or in an unknown unit.
+pub fn celsius(m: &Measurement) -> Option<f64> {
+    convert(m, Unit::Celsius)
+}
+
+/// Get a speed in kilometers per hour, converting from other units if needed, or `None` if
+/// the speed is missing or in an unknown unit.
+pub fn kilometers_per_hour(m: &Measurement) -> Option<f64> {
+    convert(m, Unit::KilometersPerHour)
+}
+
+/// Get the difference between the temperature and dewpoint in degrees celsius or `None`
+/// if either is missing. The units of each are normalized before being compared.
+pub fn dewpoint_depression(temperature: &Measurement, dewpoint: &Measurement) -> Option<f64> {
+    Some(celsius(temperature)? - celsius(dewpoint)?)
+}
+
+/// Get the relative humidity (0-100) for a temperature and dewpoint or `None` if either is
+/// missing.
+///
+/// Computed using the Magnus formula with the coefficients from Alduchov and Eskridge (1996),
+/// `RH = 100 * exp(b * Td / (c + Td)) / exp(b * T / (c + T))` where `b = 17.625` and
+/// `c = 243.04`. The result is clamped to 0-100.
+pub fn relative_humidity(temperature: &Measurement, dewpoint: &Measurement) -> Option<f64> {
+    let t = celsius(temperature)?;
+    let td = celsius(dewpoint)?;
+    let rh = 100.0 * (magnus(td) - magnus(t)).exp();
+    if rh.is_finite() {
+        Some(rh.clamp(0.0, 100.0))
+    } else {
+        None
+    }
+}
+
+/// Exponent of the Magnus formula for saturation vapor pressure at the given temperature.
+fn magnus(celsius: f64) -> f64 {
+    const B: f64 = 17.625;
+    const C: f64 = 243.04;
+    B * celsius / (C + celsius)
+}
+
+/// Get the heat index in degrees celsius for a temperature and relative humidity (0-100)
+/// or `None` if the temperature is missing or below 26.7 (80 fahrenheit).
+///
+/// Computed the same way as the NWS, see https://www.wpc.ncep.noaa.gov/html/heatindex_equation.shtml.
+/// Steadman's simple formula is used when it gives a heat index below 80 fahrenheit, otherwise
+/// the Rothfusz regression is used with adjustments for low and high humidity. Heat index isn't
+/// computed below 80 fahrenheit since the NWS doesn't report it in that range.
+pub fn heat_index(temperature: &Measurement, relative_humidity: f64) -> Option<f64> {
+    let t = celsius(temperature)?;
+    if t < HEAT_INDEX_MIN_CELSIUS {
+        return None;
+    }
+
+    let t = fahrenheit(t);
+
+    let rh = relative_humidity;
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    if (simple + t) / 2.0 < 80.0 {
+        return Some(from_fahrenheit(simple));
+    }
+
+    let mut hi =
+        -42.379 + 2.04901523 * t + 10.14333127 * rh - 0.22475541 * t * rh - 0.00683783 * t * t - 0.05481717 * rh * rh
+            + 0.00122874 * t * t * rh
+            + 0.00085282 * t * rh * rh
+            - 0.00000199 * t * t * rh * rh;
+
+    if rh < 13.0 && (80.0..=112.0).contains(&t) {
+        hi -= ((13.0 - rh) / 4.0) * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+    } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+        hi += ((rh - 85.0) / 10.0) * ((87.0 - t) / 5.0);
+    }
+
+    Some(from_fahrenheit(hi))
+}
+
+/// Get the wind chill in degrees celsius for a temperature and wind speed or `None` if either
+/// is missing or outside the range where wind chill is defined.
+///
+/// Computed using the NWS wind chill formula, see https://www.weather.gov/media/epz/wxcalc/windChill.pdf.
+/// `WC = 35.74 + 0.6215 * T - 35.75 * V^0.16 + 0.4275 * T * V^0.16` with the temperature
+/// in fahrenheit and the wind speed in miles per hour. Wind chill is only defined for
+/// temperatures at or below 10 celsius (50 fahrenheit) and wind speeds of at least 4.8
+/// kilometers per hour (3 miles per hour).
+pub fn wind_chill(temperature: &Measurement, wind_speed: &Measurement) -> Option<f64> {
+    let t = celsius(temperature)?;
+    let kmh = kilometers_per_hour(wind_speed)?;
+    if t > WIND_CHILL_MAX_CELSIUS || kmh < WIND_CHILL_MIN_KMH {
+        return None;
+    }
+
+    let t = fahrenheit(t);
+    let v = (kmh / KILOMETERS_PER_MILE).powf(0.16);
+    Some(from_fahrenheit(35.74 + 0.6215 * t - 35.75 * v + 0.4275 * t * v))
+}
+
+/// Get the absolute humidity in grams per cubic meter for a temperature and relative humidity
+/// (0-100) or `None` if the temperature is missing.
+///
+/// Computed from the saturation vapor pressure given by the Bolton (1980) formula as
+/// `AH = 6.112 * exp(17.67 * T / (T + 243.5)) * RH * 2.1674 / (273.15 + T)` with the
+/// temperature in celsius.
+pub fn absolute_humidity(temperature: &Measurement, relative_humidity: f64) -> Option<f64> {
+    let t = celsius(temperature)?;
+    let ah = 6.112 * (17.67 * t / (t + 243.5)).exp() * relative_humidity * 2.1674 / (273.15 + t);
+    if ah.is_finite() {
+        Some(ah)
+    } else {
+        None
+    }
+}
+
+/// Get the wet bulb temperature in degrees celsius for a temperature and relative humidity
+/// (0-100) or `None` if the temperature is missing or either is outside the range where the
+/// approximation is valid.
+///
+/// Computed using the approximation from Stull (2011), which is valid for relative humidity
+/// between 5 and 99 and temperatures between -20 and 50 celsius.
+/// `Tw = T * atan(0.151977 * (RH + 8.313659)^0.5) + atan(T + RH) - atan(RH - 1.676331)
+/// + 0.00391838 * RH^1.5 * atan(0.023101 * RH) - 4.686035`
+pub fn wet_bulb_temperature(temperature: &Measurement, relative_humidity: f64) -> Option<f64> {
+    let t = celsius(temperature)?;
+    let rh = relative_humidity;
+    if !(5.0..=99.0).contains(&rh) || !(-20.0..=50.0).contains(&t) {
+        tracing::debug!(
+            message = "skipping wet bulb temperature outside valid range",
+            temperature = t,
+            relative_humidity = rh
+        );
+        return None;
+    }
+
+    Some(
+        t * (0.151977 * (rh + 8.313659).sqrt()).atan() + (t + rh).atan() - (rh - 1.676331).atan()
+            + 0.00391838 * rh.powf(1.5) * (0.023101 * rh).atan()
+            - 4.686035,
+    )
+}
+
+/// Get the rate that pressure changed between two `(time, pascals)` readings in pascals per
+/// hour or `None` if the current reading isn't newer than the previous one.
+pub fn pressure_tendency(previous: (DateTime<Utc>, f64), current: (DateTime<Utc>, f64)) -> Option<f64> {
+    let elapsed = current.0.signed_duration_since(previous.0).num_milliseconds();
+    if elapsed <= 0 {
+        return None;
+    }
+
+    Some((current.1 - previous.1) / (elapsed as f64 / MILLIS_PER_HOUR))
+}
+
+/// Get the sine and cosine of a wind direction in degrees.
+///
+/// Unlike the direction itself, these can be averaged over time (359 and 1 degrees average to
+/// 0 rather than 180). Components that only differ from zero due to floating point error are
+/// returned as zero so cardinal directions are exact.
+pub fn wind_direction_components(degrees: f64) -> (f64, f64) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let snap = |v: f64| if v.abs() < 1e-12 { 0.0 } else { v };
+    (snap(sin), snap(cos))
+}
+
+/// Get the temperature it feels like, in degrees celsius.
+///
+/// This is the heat index when it's at least 26.7 (80 fahrenheit), the wind chill when it's
+/// at most 10 (50 fahrenheit), and the temperature otherwise or when the heat index or wind
+/// chill isn't available.
+pub fn apparent_temperature(celsius: f64, heat_index: Option<f64>, wind_chill: Option<f64>) -> f64 {
+    if celsius >= HEAT_INDEX_MIN_CELSIUS {
+        heat_index.unwrap_or(celsius)
+    } else if celsius <= WIND_CHILL_MAX_CELSIUS {
+        wind_chill.unwrap_or(celsius)
+    } else {
+        celsius
+    }
+}
+
+fn fahrenheit(celsius: f64) -> f64 {
+    celsius * 9.0 / 5.0 + 32.0
+}
+
+fn from_fahrenheit(fahrenheit: f64) -> f64 {
+    (fahrenheit - 32.0) * 5.0 / 9.0
+}
+
+/// Get the NWS condition code (e.g. `rain_showers`) from the URL of an observation icon or
+/// `None` if the URL isn't in the expected format.
+///
+/// Icon URLs look like `https://api.weather.gov/icons/land/day/rain_showers,30/tsra?size=medium`
+/// where each path segment after the time of day is a condition with an optional probability.
+/// Only the first condition is used when there are two and the probability is removed.
+pub fn condition_from_icon(icon: &str) -> Option<&str> {
+    let path = icon.split(['?', '#']).next()?;
+    let mut segments = path.split('/').skip_while(|s| *s != "day" && *s != "night");
+    segments.next()?;
+    let condition = segments.next()?.split(',').next()?;
+    if !condition.is_empty() && condition.bytes().all(|b| b.is_ascii_lowercase() || b == b'_') {
+        Some(condition)
+    } else {
+        None
+    }
+}
+
+/// Minimum and maximum of values observed during a single day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DailyExtremes {
+    pub day: NaiveDate,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl DailyExtremes {
+    pub fn new(day: NaiveDate, value: f64) -> Self {
+        Self {
+            day,
+            min: value,
+            max: value,
+        }
+    }
+
+    /// Include a value observed on the given day. Values from a later day replace the
+    /// minimum and maximum, values from an earlier day are ignored.
+    pub fn update(&mut self, day: NaiveDate, value: f64) {
+        if day > self.day {
+            *self = Self::new(day, value);
+        } else if day == self.day {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn assert_close(expected: f64, actual: Option<f64>) {
+        let actual = actual.unwrap();
+        assert!((expected - actual).abs() < 0.01, "expected {} got {}", expected, actual);
+    }
+
+    fn null(unit: &str) -> Measurement {
+        Measurement {
+            unit_code: unit.to_owned(),
+            ..Measurement::default()
+        }
+    }
+
+    #[test]
+    fn test_dewpoint_depression() {
+        assert_close(
+            5.5,
+            dewpoint_depression(
+                &Measurement::new("wmoUnit:degC", 16.7),
+                &Measurement::new("wmoUnit:degC", 11.2),
+            ),
+        );
+        // Units are normalized before subtracting: 50F is 10C
+        assert_close(
+            2.0,
+            dewpoint_depression(
+                &Measurement::new("wmoUnit:degC", 12.0),
+                &Measurement::new("wmoUnit:degF", 50.0),
+            ),
+        );
+        assert_close(
+            0.0,
+            dewpoint_depression(
+                &Measurement::new("wmoUnit:degF", 50.0),
+                &Measurement::new("wmoUnit:degC", 10.0),
+            ),
+        );
+
+        assert_eq!(
+            None,
+            dewpoint_depression(&null("wmoUnit:degC"), &Measurement::new("wmoUnit:degC", 11.2))
+        );
+        assert_eq!(
+            None,
+            dewpoint_depression(&Measurement::new("wmoUnit:degC", 16.7), &null("wmoUnit:degC"))
+        );
+        assert_eq!(
+            None,
+            dewpoint_depression(&Measurement::new("wmoUnit:degC", 16.7), &Measurement::absent())
+        );
+        // Measurements that aren't temperatures can't be compared
+        assert_eq!(
+            None,
+            dewpoint_depression(
+                &Measurement::new("wmoUnit:m", 290.0),
+                &Measurement::new("wmoUnit:degC", 11.2)
+            )
+        );
+    }
+
+    fn degc(v: f64) -> Measurement {
+        Measurement::new("wmoUnit:degC", v)
+    }
+
+    #[test]
+    fn test_relative_humidity() {
+        // Temperature, dewpoint, and relative humidity from the NWS relative humidity calculator
+        for (t, td, expected) in [
+            (20.0, 10.0, 52.5),
+            (30.0, 15.0, 40.2),
+            (0.0, -10.0, 46.9),
+            (35.0, 28.0, 67.2),
+            (25.0, 25.0, 100.0),
+        ] {
+            let rh = relative_humidity(&degc(t), &degc(td)).unwrap();
+            assert!(
+                (expected - rh).abs() < 0.5,
+                "T={} Td={}: expected {} got {}",
+                t,
+                td,
+                expected,
+                rh
+            );
+        }
+
+        // Dewpoint above the temperature (bad data) is clamped
+        assert_eq!(Some(100.0), relative_humidity(&degc(10.0), &degc(12.0)));
+        // Units are normalized
+        assert_close(
+            relative_humidity(&degc(20.0), &degc(10.0)).unwrap(),
+            relative_humidity(&Measurement::new("wmoUnit:degF", 68.0), &degc(10.0)),
+        );
+        assert_eq!(None, relative_humidity(&null("wmoUnit:degC"), &degc(10.0)));
+        assert_eq!(None, relative_humidity(&degc(20.0), &null("wmoUnit:degC")));
+    }
+
+    fn degf(v: f64) -> Measurement {
+        Measurement::new("wmoUnit:degF", v)
+    }
+
+    #[test]
+    fn test_heat_index() {
+        // Temperature (F), relative humidity, and heat index (F) from the NWS heat index chart
+        for (t, rh, expected) in [
+            (82.0, 40.0, 81.0),
+            (82.0, 80.0, 89.0),
+            (84.0, 70.0, 90.0),
+            (86.0, 90.0, 105.0),
+            (90.0, 50.0, 95.0),
+            (96.0, 65.0, 121.0),
+            (100.0, 40.0, 109.0),
+            (110.0, 40.0, 136.0),
+        ] {
+            let hi = fahrenheit(heat_index(&degf(t), rh).unwrap());
+            assert!(
+                (expected - hi).abs() <= 1.0,
+                "T={} RH={}: expected {} got {}",
+                t,
+                rh,
+                expected,
+                hi
+            );
+        }
+
+        // Low and high humidity adjustments
+        assert_close(from_fahrenheit(98.07), heat_index(&degf(104.0), 10.0));
+        assert_close(from_fahrenheit(93.97), heat_index(&degf(82.0), 95.0));
+
+        // Not reported below 26.7C (80F)
+        assert_eq!(None, heat_index(&degc(26.6), 90.0));
+        assert!(heat_index(&degc(26.7), 90.0).is_some());
+        assert_eq!(None, heat_index(&null("wmoUnit:degC"), 50.0));
+    }
+
+    fn kmh(v: f64) -> Measurement {
+        Measurement::new("wmoUnit:km_h-1", v)
+    }
+
+    #[test]
+    fn test_wind_chill() {
+        // Temperature (F), wind speed (mph), and wind chill (F) from the NWS wind chill chart
+        for (t, v, expected) in [
+            (40.0, 5.0, 36.0),
+            (35.0, 25.0, 23.0),
+            (30.0, 10.0, 21.0),
+            (20.0, 30.0, 1.0),
+            (10.0, 60.0, -19.0),
+            (0.0, 15.0, -19.0),
+            (-10.0, 20.0, -35.0),
+        ] {
+            let wc = fahrenheit(wind_chill(&degf(t), &Measurement::new("wmoUnit:mi_h-1", v)).unwrap());
+            assert!(
+                (expected - wc).abs() <= 1.0,
+                "T={} V={}: expected {} got {}",
+                t,
+                v,
+                expected,
+                wc
+            );
+        }
+
+        // Only defined at or below 10C and with at least 4.8 km/h of wind
+        assert!(wind_chill(&degc(10.0), &kmh(20.0)).is_some());
+        assert_eq!(None, wind_chill(&degc(10.1), &kmh(20.0)));
+        assert!(wind_chill(&degc(0.0), &kmh(4.8)).is_some());
+        assert_eq!(None, wind_chill(&degc(0.0), &kmh(4.7)));
+        assert_eq!(None, wind_chill(&degc(0.0), &kmh(0.0)));
+        assert_eq!(None, wind_chill(&null("wmoUnit:degC"), &kmh(20.0)));
+        assert_eq!(None, wind_chill(&degc(0.0), &null("wmoUnit:km_h-1")));
+    }
+
+    #[test]
+    fn test_apparent_temperature() {
+        // Hot: heat index, or the temperature when there isn't one
+        assert_eq!(35.0, apparent_temperature(30.0, Some(35.0), None));
+        assert_eq!(30.0, apparent_temperature(30.0, None, None));
+        assert_eq!(28.0, apparent_temperature(HEAT_INDEX_MIN_CELSIUS, Some(28.0), None));
+
+        // Mild: always the temperature
+        assert_eq!(26.6, apparent_temperature(26.6, Some(28.0), Some(20.0)));
+        assert_eq!(15.0, apparent_temperature(15.0, Some(17.0), Some(13.0)));
+        assert_eq!(10.1, apparent_temperature(10.1, None, Some(8.0)));
+
+        // Cold: wind chill, or the temperature when there isn't one
+        assert_eq!(8.0, apparent_temperature(WIND_CHILL_MAX_CELSIUS, None, Some(8.0)));
+        assert_eq!(-20.0, apparent_temperature(-10.0, None, Some(-20.0)));
+        assert_eq!(-10.0, apparent_temperature(-10.0, None, None));
+    }
+
+    #[test]
+    fn test_absolute_humidity() {
+        // Temperature (C), relative humidity, and absolute humidity (g/m3) reference values
+        for (t, rh, expected) in [
+            (0.0, 100.0, 4.85),
+            (20.0, 50.0, 8.65),
+            (25.0, 60.0, 13.8),
+            (30.0, 80.0, 24.3),
+        ] {
+            let ah = absolute_humidity(&degc(t), rh).unwrap();
+            assert!(
+                (expected - ah).abs() < 0.1,
+                "T={} RH={}: expected {} got {}",
+                t,
+                rh,
+                expected,
+                ah
+            );
+        }
+
+        assert_eq!(Some(0.0), absolute_humidity(&degc(20.0), 0.0));
+        assert_eq!(None, absolute_humidity(&null("wmoUnit:degC"), 50.0));
+    }
+
+    #[test]
+    fn test_wet_bulb_temperature() {
+        // Temperature (C), relative humidity, and wet bulb temperature (C) from psychrometric
+        // tables at sea level, 20C and 50% is the worked example from Stull (2011).
+        for (t, rh, expected) in [
+            (20.0, 50.0, 13.7),
+            (30.0, 50.0, 22.2),
+            (30.0, 80.0, 27.2),
+            (10.0, 90.0, 9.0),
+        ] {
+            let tw = wet_bulb_temperature(&degc(t), rh).unwrap();
+            assert!(
+                (expected - tw).abs() < 0.5,
+                "T={} RH={}: expected {} got {}",
+                t,
+                rh,
+                expected,
+                tw
+            );
+        }
+
+        // Skipped outside the range the approximation is valid for
+        assert!(wet_bulb_temperature(&degc(20.0), 5.0).is_some());
+        assert!(wet_bulb_temperature(&degc(20.0), 99.0).is_some());
+        assert_eq!(None, wet_bulb_temperature(&degc(20.0), 4.9));
+        assert_eq!(None, wet_bulb_temperature(&degc(20.0), 99.1));
+        assert!(wet_bulb_temperature(&degc(-20.0), 50.0).is_some());
+        assert!(wet_bulb_temperature(&degc(50.0), 50.0).is_some());
+        assert_eq!(None, wet_bulb_temperature(&degc(-20.1), 50.0));
+        assert_eq!(None, wet_bulb_temperature(&degc(50.1), 50.0));
+        assert_eq!(None, wet_bulb_temperature(&null("wmoUnit:degC"), 50.0));
+    }
+
+    #[test]
+    fn test_pressure_tendency() {
+        let t0 = Utc.with_ymd_and_hms(2023, 10, 21, 12, 0, 0).unwrap();
+        let t1 = t0 + chrono::Duration::minutes(30);
+        assert_eq!(Some(-200.0), pressure_tendency((t0, 101400.0), (t1, 101300.0)));
+        assert_eq!(Some(0.0), pressure_tendency((t0, 101400.0), (t1, 101400.0)));
+
+        // The same or an older observation doesn't have a rate
+        assert_eq!(None, pressure_tendency((t0, 101400.0), (t0, 101300.0)));
+        assert_eq!(None, pressure_tendency((t1, 101400.0), (t0, 101300.0)));
+    }
+
+    #[test]
+    fn test_wind_direction_components() {
+        assert_eq!((0.0, 1.0), wind_direction_components(0.0));
+        assert_eq!((1.0, 0.0), wind_direction_components(90.0));
+        assert_eq!((0.0, -1.0), wind_direction_components(180.0));
+        assert_eq!((-1.0, 0.0), wind_direction_components(270.0));
+        assert_eq!((0.0, 1.0), wind_direction_components(360.0));
+
+        // Directions on either side of north average to north
+        let (a, b) = (wind_direction_components(359.0), wind_direction_components(1.0));
+        assert!(((a.0 + b.0) / 2.0).abs() < 1e-12);
+        assert!((a.1 + b.1) / 2.0 > 0.99);
+    }
+
+    #[test]
+    fn test_condition_from_icon() {
+        for (icon, expected) in [
+            ("https://api.weather.gov/icons/land/day/sct?size=medium", Some("sct")),
+            ("https://api.weather.gov/icons/land/night/few?size=medium", Some("few")),
+            (
+                "https://api.weather.gov/icons/land/day/rain_showers?size=medium",
+                Some("rain_showers"),
+            ),
+            (
+                "https://api.weather.gov/icons/land/day/rain_showers,30?size=medium",
+                Some("rain_showers"),
+            ),
+            (
+                "https://api.weather.gov/icons/land/night/tsra,40/rain,20?size=medium",
+                Some("tsra"),
+            ),
+            ("https://api.weather.gov/icons/land/day/ovc/fog?size=small", Some("ovc")),
+            ("https://api.weather.gov/icons/land/day/bkn", Some("bkn")),
+            ("https://api.weather.gov/icons/land/day/skc#top", Some("skc")),
+            ("https://api.weather.gov/icons/land/day?size=medium", None),
+            ("https://api.weather.gov/icons/land/day/,30?size=medium", None),
+            ("https://api.weather.gov/icons/land/day/Sct?size=medium", None),
+            ("https://api.weather.gov/icons/land/sct?size=medium", None),
+            ("", None),
+        ] {
+            assert_eq!(expected, condition_from_icon(icon), "icon {}", icon);
+        }
+    }
+}
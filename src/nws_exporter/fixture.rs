@@ -0,0 +1,229 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::client::{ClientError, Observation, Station, StationProperties, Trigger, WeatherClient};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Error resulting from loading observation fixture files.
+#[derive(Debug)]
+pub enum FixtureError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, serde_json::Error),
+    InvalidStation(PathBuf, String),
+    Duplicate(PathBuf, String),
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(p, e) => write!(f, "cannot read {}: {}", p.display(), e),
+            Self::Parse(p, e) => write!(f, "cannot parse {}: {}", p.display(), e),
+            Self::InvalidStation(p, s) => write!(f, "cannot determine station ID from {} in {}", s, p.display()),
+            Self::Duplicate(p, s) => write!(f, "duplicate fixture for station {} in {}", s, p.display()),
+        }
+    }
+}
+
+impl error::Error for FixtureError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(_, e) => Some(e),
+            Self::Parse(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Client that returns observations loaded from files instead of making requests to the API.
+///
+/// Each fixture file contains the JSON response for a single station from the "latest
+/// observation" endpoint. The same observation is returned for a station every time it is
+/// requested. Station metadata is derived from the observation since it isn't part of the
+/// fixture.
+#[derive(Debug)]
+pub struct FixtureClient {
+    stations: Vec<String>,
+    observations: HashMap<String, Observation>,
+}
+
+impl FixtureClient {
+    /// Load an observation fixture from each of the provided paths.
+    ///
+    /// # Errors
+    ///
+    /// If any of the paths can't be read or parsed as an observation, or if more than one
+    /// path contains an observation for the same station, an error will be returned.
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Self, FixtureError> {
+        let mut stations = Vec::with_capacity(paths.len());
+        let mut observations = HashMap::with_capacity(paths.len());
+
+        for path in paths {
+            let path = path.as_ref();
+            let contents = fs::read(path).map_err(|e| FixtureError::Io(path.to_owned(), e))?;
+            let obs: Observation =
+                serde_json::from_slice(&contents).map_err(|e| FixtureError::Parse(path.to_owned(), e))?;
+
+            let id = station_id(&obs.properties.station)
+                .ok_or_else(|| FixtureError::InvalidStation(path.to_owned(), obs.properties.station.clone()))?;
+            if observations.contains_key(&id) {
+                return Err(FixtureError::Duplicate(path.to_owned(), id));
+            }
+
+            stations.push(id.clone());
+            observations.insert(id, obs);
+        }
+
+        Ok(Self { stations, observations })
+    }
+
+    /// IDs of stations with a fixture, in the order the fixtures were loaded.
+    pub fn stations(&self) -> &[String] {
+        &self.stations
+    }
+
+    fn get(&self, station: &str) -> Result<&Observation, ClientError> {
+        self.observations
+            .get(station)
+            .ok_or_else(|| ClientError::InvalidStation(station.to_owned()))
+    }
+}
+
+#[async_trait]
+impl WeatherClient for FixtureClient {
+    async fn station(&self, station: &str, _trigger: Trigger) -> Result<Station, ClientError> {
+        let obs = self.get(station)?;
+        Ok(Station {
+            id: obs.properties.station.clone(),
+            type_: "Feature".to_owned(),
+            geometry: obs.geometry.clone(),
+            properties: StationProperties {
+                id: obs.properties.station.clone(),
+                type_: "wx:ObservationStation".to_owned(),
+                elevation: obs.properties.elevation.clone(),
+                station_identifier: station.to_owned(),
+                name: station.to_owned(),
+                timezone: None,
+                forecast_zone: None,
+                county: None,
+            },
+        })
+    }
+
+    async fn observation(&self, station: &str, _trigger: Trigger) -> Result<Observation, ClientError> {
+        self.get(station).cloned()
+    }
+}
+
+/// Get the station ID from the last path segment of a station URL.
+fn station_id(station_url: &str) -> Option<String> {
+    station_url
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("ext/fixtures").join(name)
+    }
+
+    /// Write `contents` to a file unique to the calling test, returning its path.
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("nws_exporter_{}_{}.json", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let client = FixtureClient::load(&[fixture("KBOS.json")]).unwrap();
+        assert_eq!(&["KBOS".to_owned()], client.stations());
+
+        let station = client.station("KBOS", Trigger::Scheduled).await.unwrap();
+        assert_eq!("KBOS", station.properties.station_identifier);
+        assert_eq!("https://api.weather.gov/stations/KBOS", station.properties.id);
+
+        // The same observation is returned every time
+        let first = client.observation("KBOS", Trigger::Scheduled).await.unwrap();
+        let second = client.observation("KBOS", Trigger::Scheduled).await.unwrap();
+        assert_eq!(first.id, second.id);
+        assert_eq!(Some(16.7), first.properties.temperature.value);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_station() {
+        let client = FixtureClient::load(&[fixture("KBOS.json")]).unwrap();
+        assert!(matches!(
+            client.observation("KJFK", Trigger::Scheduled).await,
+            Err(ClientError::InvalidStation(s)) if s == "KJFK"
+        ));
+        assert!(matches!(
+            client.point(42.36, -71.01, Trigger::Scheduled).await,
+            Err(ClientError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_duplicate() {
+        let err = FixtureClient::load(&[fixture("KBOS.json"), fixture("KBOS_ft.json")]).unwrap_err();
+        assert!(matches!(&err, FixtureError::Duplicate(p, s) if *p == fixture("KBOS_ft.json") && s == "KBOS"));
+    }
+
+    #[test]
+    fn test_load_missing() {
+        let err = FixtureClient::load(&[fixture("KNONE.json")]).unwrap_err();
+        assert!(matches!(&err, FixtureError::Io(p, _) if *p == fixture("KNONE.json")));
+    }
+
+    #[test]
+    fn test_load_parse_error() {
+        let path = temp_file("parse_error", r#"{"id": "obs", "properties": {}}"#);
+        let err = FixtureClient::load(&[&path]).unwrap_err();
+        fs::remove_file(&path).unwrap();
+
+        // The path and the serde error are both part of the message
+        assert!(matches!(&err, FixtureError::Parse(p, _) if *p == path));
+        let message = err.to_string();
+        assert!(message.contains(&path.display().to_string()), "{}", message);
+        assert!(message.contains("missing field"), "{}", message);
+    }
+
+    #[test]
+    fn test_station_id() {
+        assert_eq!(
+            Some("KBOS".to_owned()),
+            station_id("https://api.weather.gov/stations/KBOS")
+        );
+        assert_eq!(
+            Some("KBOS".to_owned()),
+            station_id("https://api.weather.gov/stations/KBOS/")
+        );
+        assert_eq!(None, station_id(""));
+    }
+}
@@ -0,0 +1,304 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Longitude and latitude of a single GeoJSON position, in degrees.
+///
+/// GeoJSON positions are `[longitude, latitude]` arrays that may contain an optional
+/// third element for elevation which is ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    pub longitude: f64,
+    pub latitude: f64,
+}
+
+impl Coordinates {
+    pub fn new(longitude: f64, latitude: f64) -> Self {
+        Self { longitude, latitude }
+    }
+}
+
+impl Serialize for Coordinates {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tup = serializer.serialize_tuple(2)?;
+        tup.serialize_element(&self.longitude)?;
+        tup.serialize_element(&self.latitude)?;
+        tup.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Coordinates {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CoordinatesVisitor;
+
+        impl<'de> Visitor<'de> for CoordinatesVisitor {
+            type Value = Coordinates;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an array of [longitude, latitude]")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let longitude = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let latitude = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                // Consume (and ignore) elevation or any other trailing elements
+                while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+                Ok(Coordinates { longitude, latitude })
+            }
+        }
+
+        deserializer.deserialize_seq(CoordinatesVisitor)
+    }
+}
+
+/// Subset of GeoJSON geometry types returned by the API for stations and alerts.
+///
+/// Polygons are a list of linear rings: the first is the exterior of the polygon and
+/// any others are holes within it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "coordinates")]
+pub enum Geometry {
+    Point(Coordinates),
+    Polygon(Vec<Vec<Coordinates>>),
+    MultiPolygon(Vec<Vec<Vec<Coordinates>>>),
+}
+
+impl Geometry {
+    /// Return true if the point is inside this geometry. Points on the exact boundary of
+    /// a polygon may be considered inside or outside. `Point` geometries never contain anything.
+    pub fn contains(&self, point: Coordinates) -> bool {
+        match self {
+            Self::Point(_) => false,
+            Self::Polygon(rings) => polygon_contains(rings, point),
+            Self::MultiPolygon(polygons) => polygons.iter().any(|rings| polygon_contains(rings, point)),
+        }
+    }
+
+    /// Return the coordinates of this geometry if it is a single point.
+    pub fn point(&self) -> Option<Coordinates> {
+        match self {
+            Self::Point(c) => Some(*c),
+            _ => None,
+        }
+    }
+}
+
+fn polygon_contains(rings: &[Vec<Coordinates>], point: Coordinates) -> bool {
+    match rings.split_first() {
+        Some((exterior, holes)) => ring_contains(exterior, point) && !holes.iter().any(|h| ring_contains(h, point)),
+        None => false,
+    }
+}
+
+/// Ray casting test for a point in a linear ring, treating longitude as x and latitude as y.
+fn ring_contains(ring: &[Coordinates], point: Coordinates) -> bool {
+    if ring.len() < 3 {
+        return false;
+    }
+
+    let (x, y) = (point.longitude, point.latitude);
+    let mut inside = false;
+    let mut j = ring.len() - 1;
+
+    for i in 0..ring.len() {
+        let (xi, yi) = (ring[i].longitude, ring[i].latitude);
+        let (xj, yj) = (ring[j].longitude, ring[j].latitude);
+
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+
+        j = i;
+    }
+
+    inside
+}
+
+/// A location that area-based data like alerts can be matched against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub station: String,
+    pub coordinates: Option<Coordinates>,
+    pub zones: Vec<String>,
+}
+
+/// Find locations affected by something (an alert) covering the given zones or geometry.
+///
+/// Locations match if they share a zone with the alert or if their coordinates are within
+/// the geometry of the alert. Locations without zone metadata (buoys, for example) can only
+/// be matched by geometry. Alerts without zones or geometry never match anything.
+pub fn affected<'a>(locations: &'a [Location], zones: &[String], geometry: Option<&Geometry>) -> Vec<&'a Location> {
+    locations
+        .iter()
+        .filter(|loc| {
+            loc.zones.iter().any(|z| zones.contains(z))
+                || match (geometry, loc.coordinates) {
+                    (Some(g), Some(c)) => g.contains(c),
+                    _ => false,
+                }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(points: &[(f64, f64)]) -> Vec<Coordinates> {
+        points.iter().map(|(lon, lat)| Coordinates::new(*lon, *lat)).collect()
+    }
+
+    /// Square from (min, min) to (max, max), closed the same way GeoJSON rings are.
+    fn square(min: f64, max: f64) -> Vec<Coordinates> {
+        ring(&[(min, min), (max, min), (max, max), (min, max), (min, min)])
+    }
+
+    fn location(station: &str, coordinates: Option<(f64, f64)>, zones: &[&str]) -> Location {
+        Location {
+            station: station.to_owned(),
+            coordinates: coordinates.map(|(lon, lat)| Coordinates::new(lon, lat)),
+            zones: zones.iter().map(|z| (*z).to_owned()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_coordinates_deserialize() {
+        let c: Coordinates = serde_json::from_str("[-71.03, 42.37]").unwrap();
+        assert_eq!(Coordinates::new(-71.03, 42.37), c);
+
+        let c: Coordinates = serde_json::from_str("[-71.03, 42.37, 9.0]").unwrap();
+        assert_eq!(Coordinates::new(-71.03, 42.37), c);
+
+        assert!(serde_json::from_str::<Coordinates>("[-71.03]").is_err());
+        assert_eq!("[-71.03,42.37]", serde_json::to_string(&c).unwrap());
+    }
+
+    #[test]
+    fn test_polygon_contains() {
+        let g = Geometry::Polygon(vec![square(0.0, 10.0)]);
+        assert!(g.contains(Coordinates::new(5.0, 5.0)));
+        assert!(g.contains(Coordinates::new(0.5, 9.5)));
+        assert!(!g.contains(Coordinates::new(-1.0, 5.0)));
+        assert!(!g.contains(Coordinates::new(5.0, 11.0)));
+    }
+
+    #[test]
+    fn test_polygon_contains_concave() {
+        // U shape, open at the top between x = 4 and x = 6
+        let g = Geometry::Polygon(vec![ring(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (6.0, 10.0),
+            (6.0, 4.0),
+            (4.0, 4.0),
+            (4.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ])]);
+        assert!(g.contains(Coordinates::new(2.0, 8.0)));
+        assert!(g.contains(Coordinates::new(8.0, 8.0)));
+        assert!(g.contains(Coordinates::new(5.0, 2.0)));
+        assert!(!g.contains(Coordinates::new(5.0, 8.0)));
+    }
+
+    #[test]
+    fn test_polygon_with_hole() {
+        let g = Geometry::Polygon(vec![square(0.0, 10.0), square(4.0, 6.0)]);
+        assert!(g.contains(Coordinates::new(2.0, 2.0)));
+        assert!(g.contains(Coordinates::new(8.0, 5.0)));
+        assert!(!g.contains(Coordinates::new(5.0, 5.0)));
+        assert!(!g.contains(Coordinates::new(11.0, 5.0)));
+    }
+
+    #[test]
+    fn test_multi_polygon() {
+        let g = Geometry::MultiPolygon(vec![
+            vec![square(0.0, 10.0), square(4.0, 6.0)],
+            vec![square(20.0, 30.0)],
+        ]);
+        assert!(g.contains(Coordinates::new(2.0, 2.0)));
+        assert!(g.contains(Coordinates::new(25.0, 25.0)));
+        assert!(!g.contains(Coordinates::new(5.0, 5.0)));
+        assert!(!g.contains(Coordinates::new(15.0, 15.0)));
+        assert!(!Geometry::MultiPolygon(Vec::new()).contains(Coordinates::new(5.0, 5.0)));
+    }
+
+    #[test]
+    fn test_degenerate_rings() {
+        let point = Coordinates::new(0.5, 0.5);
+        assert!(!Geometry::Polygon(Vec::new()).contains(point));
+        assert!(!Geometry::Polygon(vec![Vec::new()]).contains(point));
+        assert!(!Geometry::Polygon(vec![ring(&[(0.0, 0.0), (1.0, 1.0)])]).contains(point));
+
+        // A hole with too few points to be a ring doesn't remove anything
+        let g = Geometry::Polygon(vec![square(0.0, 1.0), ring(&[(0.0, 0.0), (1.0, 1.0)])]);
+        assert!(g.contains(point));
+    }
+
+    #[test]
+    fn test_point_geometry() {
+        let c = Coordinates::new(-71.03, 42.37);
+        let g = Geometry::Point(c);
+        assert!(!g.contains(c));
+        assert_eq!(Some(c), g.point());
+        assert_eq!(None, Geometry::Polygon(vec![square(0.0, 1.0)]).point());
+    }
+
+    #[test]
+    fn test_geometry_deserialize() {
+        let g: Geometry = serde_json::from_str(
+            r#"{"type": "Polygon", "coordinates": [[[0, 0], [10, 0], [10, 10], [0, 10], [0, 0]]]}"#,
+        )
+        .unwrap();
+        assert_eq!(Geometry::Polygon(vec![square(0.0, 10.0)]), g);
+
+        let g: Geometry = serde_json::from_str(r#"{"type": "Point", "coordinates": [-71.03, 42.37]}"#).unwrap();
+        assert_eq!(Geometry::Point(Coordinates::new(-71.03, 42.37)), g);
+    }
+
+    #[test]
+    fn test_affected() {
+        let locations = vec![
+            location("KBOS", Some((5.0, 5.0)), &["MAZ015", "MAC025"]),
+            location("44013", Some((25.0, 25.0)), &[]),
+            location("KXYZ", None, &["MAZ016"]),
+            location("KNONE", None, &[]),
+        ];
+        let stations = |zones: &[&str], geometry: Option<&Geometry>| -> Vec<String> {
+            let zones: Vec<String> = zones.iter().map(|z| (*z).to_owned()).collect();
+            affected(&locations, &zones, geometry)
+                .into_iter()
+                .map(|l| l.station.clone())
+                .collect()
+        };
+
+        let polygon = Geometry::Polygon(vec![square(20.0, 30.0)]);
+        assert_eq!(vec!["KBOS"], stations(&["MAZ015"], None));
+        assert_eq!(vec!["KBOS", "KXYZ"], stations(&["MAC025", "MAZ016"], None));
+        assert_eq!(vec!["44013"], stations(&[], Some(&polygon)));
+        assert_eq!(vec!["KBOS", "44013"], stations(&["MAZ015"], Some(&polygon)));
+        assert!(stations(&["ANZ230"], None).is_empty());
+        assert!(stations(&[], None).is_empty());
+        assert!(stations(&[], Some(&Geometry::Point(Coordinates::new(5.0, 5.0)))).is_empty());
+    }
+}
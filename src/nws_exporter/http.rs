@@ -16,24 +16,182 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 //
 
-use axum::extract::State;
-use axum::http::header::CONTENT_TYPE;
-use axum::http::{HeaderMap, HeaderValue, StatusCode};
-use axum::response::IntoResponse;
+use axum::extract::{MatchedPath, State};
+use axum::http::header::{CONTENT_TYPE, RETRY_AFTER};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, SecondsFormat, Utc};
 use prometheus_client::encoding::text;
 use prometheus_client::registry::Registry;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::{normalize_station_id, StationConfig, UpdateConfig};
+use crate::metrics::HttpMetrics;
+use crate::status::StationStatus;
 
 const METRICS_TEXT: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+const LAST_UPDATE: HeaderName = HeaderName::from_static("x-nws-last-update");
+const OLDEST_STATION_UPDATE: HeaderName = HeaderName::from_static("x-nws-oldest-station-update");
 
 #[derive(Debug)]
 pub struct RequestState {
-    pub registry: Registry,
+    pub registry: Arc<Registry>,
+    pub stations: Vec<StationConfig>,
+    pub refresh: RefreshTrigger,
+    pub status: Arc<StationStatus>,
+    pub metrics: HttpMetrics,
+}
+
+/// Request for an immediate refresh of all stations or only the given stations.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub stations: Option<Vec<String>>,
+}
+
+impl RefreshRequest {
+    /// Combine another request into this one so that a single refresh satisfies both.
+    fn merge(&mut self, other: RefreshRequest) {
+        match other.stations {
+            Some(other) => {
+                // A request for all stations already includes any others
+                if let Some(stations) = self.stations.as_mut() {
+                    for id in other {
+                        if !stations.contains(&id) {
+                            stations.push(id);
+                        }
+                    }
+                }
+            }
+            None => self.stations = None,
+        }
+    }
+}
+
+/// Sending half of the channel used to ask the update task for a refresh outside its schedule.
+///
+/// Refreshes are limited to one per cooldown period. At most one refresh may be pending at a
+/// time, additional requests while one is pending are merged into it so that the pending
+/// refresh includes every station any of them asked for.
+#[derive(Debug)]
+pub struct RefreshTrigger {
+    tx: Sender<()>,
+    pending: Arc<Mutex<Option<RefreshRequest>>>,
+    cooldown: Duration,
+    last: Mutex<Option<Instant>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RefreshTrigger {
+    /// Create a new `RefreshTrigger` and the receiver that the update task should listen on.
+    pub fn new(config: &UpdateConfig) -> (Self, RefreshReceiver) {
+        let (tx, rx) = mpsc::channel(1);
+        let pending = Arc::new(Mutex::new(None));
+        (
+            Self {
+                tx,
+                pending: pending.clone(),
+                cooldown: config.cooldown(),
+                last: Mutex::new(None),
+                clock: Arc::new(SystemClock),
+            },
+            RefreshReceiver { rx, pending },
+        )
+    }
+
+    /// Use the provided `Clock` to enforce the cooldown between refreshes.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+/// Receiving half of the channel used to ask the update task for a refresh.
+#[derive(Debug)]
+pub struct RefreshReceiver {
+    rx: Receiver<()>,
+    pending: Arc<Mutex<Option<RefreshRequest>>>,
+}
+
+impl RefreshReceiver {
+    /// Wait for the next requested refresh, including any requests merged into it while it
+    /// was pending. `None` is returned once the `RefreshTrigger` has been dropped.
+    pub async fn recv(&mut self) -> Option<RefreshRequest> {
+        loop {
+            self.rx.recv().await?;
+            if let Some(req) = self.pending.lock().unwrap().take() {
+                return Some(req);
+            }
+        }
+    }
+
+    /// Take the pending refresh, if there is one, without waiting.
+    pub fn try_recv(&mut self) -> Option<RefreshRequest> {
+        self.rx.try_recv().ok()?;
+        self.pending.lock().unwrap().take()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MessageResponse {
+    message: String,
+}
+
+impl MessageResponse {
+    fn new<S: Into<String>>(msg: S) -> Json<Self> {
+        Json(Self { message: msg.into() })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct StatusResponse<'a> {
+    stations: &'a [StationConfig],
+}
+
+/// Headers describing how fresh the exported data is so that consumers can decide whether
+/// to parse the body. Each header is omitted when the time it describes isn't known yet.
+fn freshness_headers(status: &StationStatus) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, time) in [
+        (LAST_UPDATE, status.last_update()),
+        (OLDEST_STATION_UPDATE, status.oldest_station_update()),
+    ] {
+        if let Some(v) = time.and_then(|t| HeaderValue::from_str(&format_time(t)).ok()) {
+            headers.insert(name, v);
+        }
+    }
+
+    headers
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Middleware that records the number and duration of requests by the route they matched.
+pub async fn track_metrics<B>(State(state): State<Arc<RequestState>>, req: Request<B>, next: Next<B>) -> Response {
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_owned())
+        .unwrap_or_else(|| HttpMetrics::OTHER.to_owned());
+    let method = req.method().clone();
+
+    let start = Instant::now();
+    let res = next.run(req).await;
+    state.metrics.request(&path, &method, res.status(), start.elapsed());
+    res
 }
 
 pub async fn text_metrics_handler(State(state): State<Arc<RequestState>>) -> impl IntoResponse {
     let mut buf = String::new();
-    let mut headers = HeaderMap::new();
+    let mut headers = freshness_headers(&state.status);
 
     match text::encode(&mut buf, &state.registry) {
         Ok(_) => {
@@ -47,3 +205,328 @@ pub async fn text_metrics_handler(State(state): State<Arc<RequestState>>) -> imp
         }
     }
 }
+
+pub async fn status_handler(State(state): State<Arc<RequestState>>) -> impl IntoResponse {
+    let body = Json(StatusResponse {
+        stations: &state.stations,
+    });
+
+    (freshness_headers(&state.status), body).into_response()
+}
+
+pub async fn refresh_handler(
+    State(state): State<Arc<RequestState>>,
+    body: Option<Json<RefreshRequest>>,
+) -> impl IntoResponse {
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+    (freshness_headers(&state.status), request_refresh(&state, req))
+}
+
+fn request_refresh(state: &RequestState, mut req: RefreshRequest) -> Response {
+    for id in req.stations.iter_mut().flatten() {
+        *id = normalize_station_id(id);
+    }
+
+    if let Some(unknown) = req
+        .stations
+        .iter()
+        .flatten()
+        .find(|id| !state.stations.iter().any(|s| &s.id == *id))
+    {
+        let msg = MessageResponse::new(format!("unknown station {}", unknown));
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    let trigger = &state.refresh;
+    let mut last = trigger.last.lock().unwrap();
+    let now = trigger.clock.now_monotonic();
+    if let Some(elapsed) = last.map(|l| now.duration_since(l)).filter(|e| *e < trigger.cooldown) {
+        let retry = (trigger.cooldown - elapsed).as_secs() + 1;
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from(retry));
+        let msg = MessageResponse::new("refresh requested too recently");
+        return (StatusCode::TOO_MANY_REQUESTS, headers, msg).into_response();
+    }
+
+    // The update task takes the pending request when it's woken up so there is only ever
+    // a wakeup waiting in the channel if there's also a pending request to merge into.
+    let mut pending = trigger.pending.lock().unwrap();
+    if let Some(p) = pending.as_mut() {
+        p.merge(req);
+        return (StatusCode::ACCEPTED, MessageResponse::new("refresh already pending")).into_response();
+    }
+
+    *pending = Some(req);
+    match trigger.tx.try_send(()) {
+        Ok(_) => {
+            *last = Some(now);
+            (StatusCode::ACCEPTED, MessageResponse::new("refresh scheduled")).into_response()
+        }
+        Err(TrySendError::Full(_)) => {
+            (StatusCode::ACCEPTED, MessageResponse::new("refresh already pending")).into_response()
+        }
+        Err(TrySendError::Closed(_)) => {
+            *pending = None;
+            tracing::error!(message = "unable to request refresh, update task is not running");
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                MessageResponse::new("update task is not running"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::config::StationSource;
+    use axum::body::HttpBody;
+    use chrono::TimeZone;
+
+    fn state(stations: Vec<StationConfig>) -> (Arc<RequestState>, RefreshReceiver) {
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2023, 10, 21, 15, 0, 0).unwrap()));
+        state_with_clock(stations, clock)
+    }
+
+    fn state_with_clock(stations: Vec<StationConfig>, clock: Arc<ManualClock>) -> (Arc<RequestState>, RefreshReceiver) {
+        let mut reg = Registry::default();
+        let metrics = HttpMetrics::new(&mut reg);
+        let (refresh, rx) = RefreshTrigger::new(&UpdateConfig::default());
+        let refresh = refresh.with_clock(clock);
+        let status = Arc::new(StationStatus::new(&stations));
+        let state = RequestState {
+            registry: Arc::new(reg),
+            stations,
+            refresh,
+            status,
+            metrics,
+        };
+
+        (Arc::new(state), rx)
+    }
+
+    async fn body_json(res: Response) -> serde_json::Value {
+        let mut body = res.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_status_includes_source() {
+        let (state, _rx) = state(vec![
+            StationConfig::new("KBOS", StationSource::Cli),
+            StationConfig::new("KJFK", StationSource::Discovered),
+        ]);
+
+        let res = status_handler(State(state)).await.into_response();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(
+            serde_json::json!({
+                "stations": [
+                    {"id": "KBOS", "source": "cli"},
+                    {"id": "KJFK", "source": "discovered"},
+                ]
+            }),
+            body_json(res).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_hostile_station() {
+        let hostile = "K\"BOS\\\n\u{0}🌧";
+        let (state, _rx) = state(vec![StationConfig::new(hostile, StationSource::Config)]);
+
+        // Values are escaped by the JSON serializer and come back unchanged
+        let res = status_handler(State(state)).await.into_response();
+        let body = body_json(res).await;
+        assert_eq!(hostile, body["stations"][0]["id"]);
+    }
+
+    fn header<'a>(res: &'a Response, name: &HeaderName) -> Option<&'a str> {
+        res.headers().get(name).map(|v| v.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_freshness_headers() {
+        let (state, _rx) = state(vec![
+            StationConfig::new("KBOS", StationSource::Cli),
+            StationConfig::new("KJFK", StationSource::Cli),
+        ]);
+
+        // Nothing is known before the first successful fetch
+        let res = text_metrics_handler(State(state.clone())).await.into_response();
+        assert_eq!(None, header(&res, &LAST_UPDATE));
+        assert_eq!(None, header(&res, &OLDEST_STATION_UPDATE));
+
+        let start = Utc.with_ymd_and_hms(2023, 10, 21, 15, 0, 0).unwrap();
+        state.status.record_success("KBOS", start);
+        let res = text_metrics_handler(State(state.clone())).await.into_response();
+        assert_eq!(Some("2023-10-21T15:00:00Z"), header(&res, &LAST_UPDATE));
+        assert_eq!(None, header(&res, &OLDEST_STATION_UPDATE));
+
+        state
+            .status
+            .record_success("KJFK", start + chrono::Duration::milliseconds(90_500));
+        for res in [
+            text_metrics_handler(State(state.clone())).await.into_response(),
+            status_handler(State(state.clone())).await.into_response(),
+            refresh_handler(State(state.clone()), None).await.into_response(),
+        ] {
+            assert_eq!(Some("2023-10-21T15:01:30Z"), header(&res, &LAST_UPDATE));
+            assert_eq!(Some("2023-10-21T15:00:00Z"), header(&res, &OLDEST_STATION_UPDATE));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_cooldown() {
+        let start = Utc.with_ymd_and_hms(2023, 10, 21, 15, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(start));
+        let (state, mut rx) = state_with_clock(vec![StationConfig::new("KBOS", StationSource::Cli)], clock.clone());
+        let cooldown = UpdateConfig::default().cooldown();
+
+        let res = request_refresh(&state, RefreshRequest::default());
+        assert_eq!(StatusCode::ACCEPTED, res.status());
+        assert!(rx.try_recv().is_some());
+
+        let res = request_refresh(&state, RefreshRequest::default());
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        let retry = cooldown.as_secs() + 1;
+        assert_eq!(Some(retry.to_string().as_str()), header(&res, &RETRY_AFTER));
+
+        // Changes to the wall clock don't affect the cooldown
+        clock.set_wall(start + chrono::Duration::days(1));
+        let res = request_refresh(&state, RefreshRequest::default());
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+
+        clock.advance(cooldown - Duration::from_secs(1));
+        let res = request_refresh(&state, RefreshRequest::default());
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        assert_eq!(Some("2"), header(&res, &RETRY_AFTER));
+
+        clock.advance(Duration::from_secs(1));
+        let res = request_refresh(&state, RefreshRequest::default());
+        assert_eq!(StatusCode::ACCEPTED, res.status());
+        assert!(rx.try_recv().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_track_metrics() {
+        use axum::middleware;
+        use axum::routing::{get, post};
+        use axum::Router;
+
+        let (state, _rx) = state(vec![StationConfig::new("KBOS", StationSource::Cli)]);
+        let app = Router::new()
+            .route("/metrics", get(text_metrics_handler))
+            .route("/api/v1/refresh", post(refresh_handler))
+            .layer(middleware::from_fn_with_state(state.clone(), track_metrics))
+            .with_state(state);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+        let client = reqwest::Client::new();
+        for path in ["/metrics", "/metrics", "/nope", "/nope/again"] {
+            client.get(format!("{}{}", base, path)).send().await.unwrap();
+        }
+        client.post(format!("{}/api/v1/refresh", base)).send().await.unwrap();
+        let out = client
+            .get(format!("{}/metrics", base))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        // Counters for the scrape in progress aren't updated until after it completes
+        for (labels, count) in [
+            ("path=\"/metrics\",method=\"GET\",code=\"200\"", 2),
+            ("path=\"/api/v1/refresh\",method=\"POST\",code=\"202\"", 1),
+            ("path=\"other\",method=\"GET\",code=\"404\"", 2),
+        ] {
+            let line = out
+                .lines()
+                .find(|l| l.starts_with("nws_http_requests_total{") && labels.split(',').all(|label| l.contains(label)))
+                .unwrap_or_else(|| panic!("missing {} in {}", labels, out));
+            assert!(line.ends_with(&format!(" {}", count)), "{}", line);
+        }
+        assert!(out.contains("nws_http_request_duration_seconds_count{"), "{}", out);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_merged() {
+        let start = Utc.with_ymd_and_hms(2023, 10, 21, 15, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(start));
+        let stations = ["KBOS", "KJFK", "KORD"]
+            .iter()
+            .map(|id| StationConfig::new(*id, StationSource::Cli))
+            .collect();
+        let (state, mut rx) = state_with_clock(stations, clock.clone());
+        let cooldown = UpdateConfig::default().cooldown();
+        let only = |ids: &[&str]| RefreshRequest {
+            stations: Some(ids.iter().map(|id| id.to_string()).collect()),
+        };
+
+        let res = request_refresh(&state, only(&["KBOS"]));
+        assert_eq!(StatusCode::ACCEPTED, res.status());
+        assert_eq!("refresh scheduled", body_json(res).await["message"]);
+
+        // Requests made while one is pending add their stations to it instead of being dropped
+        clock.advance(cooldown);
+        let res = request_refresh(&state, only(&["kjfk", "KBOS"]));
+        assert_eq!(StatusCode::ACCEPTED, res.status());
+        assert_eq!("refresh already pending", body_json(res).await["message"]);
+        assert_eq!(Some(only(&["KBOS", "KJFK"])), rx.try_recv());
+        assert_eq!(None, rx.try_recv());
+
+        // A request for every station includes any others
+        clock.advance(cooldown);
+        request_refresh(&state, only(&["KORD"]));
+        clock.advance(cooldown);
+        request_refresh(&state, RefreshRequest::default());
+        clock.advance(cooldown);
+        request_refresh(&state, only(&["KJFK"]));
+        assert_eq!(Some(RefreshRequest::default()), rx.try_recv());
+
+        // Once taken, the next request is scheduled on its own
+        clock.advance(cooldown);
+        let res = request_refresh(&state, only(&["KORD"]));
+        assert_eq!("refresh scheduled", body_json(res).await["message"]);
+        assert_eq!(Some(only(&["KORD"])), rx.try_recv());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_receiver_closed() {
+        let (state, rx) = state(vec![StationConfig::new("KBOS", StationSource::Cli)]);
+        drop(rx);
+
+        let res = request_refresh(&state, RefreshRequest::default());
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, res.status());
+        assert!(state.refresh.pending.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_refresh_request_merge() {
+        let only = |ids: &[&str]| RefreshRequest {
+            stations: Some(ids.iter().map(|id| id.to_string()).collect()),
+        };
+
+        let mut req = only(&["KBOS"]);
+        req.merge(only(&["KJFK", "KBOS"]));
+        assert_eq!(only(&["KBOS", "KJFK"]), req);
+
+        req.merge(RefreshRequest::default());
+        assert_eq!(RefreshRequest::default(), req);
+
+        req.merge(only(&["KORD"]));
+        assert_eq!(RefreshRequest::default(), req);
+    }
+}
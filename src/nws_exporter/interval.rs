@@ -0,0 +1,359 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Error resulting from parsing an `Interval`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntervalError {
+    Format(String),
+    Start(String, String),
+    Duration(String),
+}
+
+impl fmt::Display for IntervalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Format(s) => write!(f, "interval {} must be a start time and duration separated by '/'", s),
+            Self::Start(s, e) => write!(f, "invalid start time {}: {}", s, e),
+            Self::Duration(s) => write!(f, "invalid or unsupported duration {}", s),
+        }
+    }
+}
+
+impl std::error::Error for IntervalError {}
+
+/// ISO 8601 time interval made of a start time and duration, e.g. `2024-01-05T18:00:00+00:00/PT6H`.
+///
+/// This is the format the API uses for the time each value of gridpoint data is valid for.
+/// Only durations of weeks, days, hours, minutes, and seconds are supported since years and
+/// months don't have a fixed length (and aren't used by the API).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interval {
+    raw: String,
+    start: DateTime<Utc>,
+    duration: Duration,
+}
+
+impl Interval {
+    /// Parse an interval made of a start time and duration separated by `/`.
+    pub fn parse(raw: &str) -> Result<Self, IntervalError> {
+        let (start, duration) = raw
+            .split_once('/')
+            .ok_or_else(|| IntervalError::Format(raw.to_owned()))?;
+        let start = DateTime::parse_from_rfc3339(start)
+            .map_err(|e| IntervalError::Start(start.to_owned(), e.to_string()))?
+            .with_timezone(&Utc);
+        let duration = parse_duration(duration).ok_or_else(|| IntervalError::Duration(duration.to_owned()))?;
+
+        Ok(Self {
+            raw: raw.to_owned(),
+            start,
+            duration,
+        })
+    }
+
+    /// The interval exactly as it was returned.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// Start of the interval, inclusive.
+    pub fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    /// End of the interval, exclusive.
+    pub fn end(&self) -> DateTime<Utc> {
+        self.start + self.duration
+    }
+
+    /// Length of the interval.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Return true if the given time is within this interval.
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.start <= time && time < self.end()
+    }
+
+    /// Get the length of the part of this interval between `start` (inclusive) and `end`
+    /// (exclusive), zero if they don't overlap.
+    pub fn overlap(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Duration {
+        let from = self.start.max(start);
+        let to = self.end().min(end);
+        if to > from {
+            to - from
+        } else {
+            Duration::zero()
+        }
+    }
+
+    /// Get the fraction (0-1) of this interval that is between `start` (inclusive) and
+    /// `end` (exclusive). Empty intervals are entirely between the times if they start
+    /// between them.
+    pub fn overlap_fraction(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> f64 {
+        if self.duration.is_zero() {
+            return if start <= self.start && self.start < end {
+                1.0
+            } else {
+                0.0
+            };
+        }
+
+        self.overlap(start, end).num_milliseconds() as f64 / self.duration.num_milliseconds() as f64
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl Serialize for Interval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Self::parse(&raw).map_err(de::Error::custom)
+    }
+}
+
+/// Sum values that are amounts over an interval (e.g. precipitation) for the time between
+/// `start` (inclusive) and `end` (exclusive) or `None` if no intervals overlap it.
+///
+/// Values for intervals that only partially overlap the time are prorated by the fraction of
+/// the interval that overlaps it, assuming the amount is spread evenly over the interval.
+pub fn prorated_sum<'a, I>(values: I, start: DateTime<Utc>, end: DateTime<Utc>) -> Option<f64>
+where
+    I: IntoIterator<Item = (&'a Interval, f64)>,
+{
+    values
+        .into_iter()
+        .map(|(i, v)| (i.overlap_fraction(start, end), v))
+        .filter(|(fraction, _)| *fraction > 0.0)
+        .map(|(fraction, v)| v * fraction)
+        .fold(None, |acc, v| Some(acc.unwrap_or(0.0) + v))
+}
+
+/// Parse an ISO 8601 duration of weeks, days, hours, minutes, and seconds (e.g. `P1DT6H` or
+/// `PT30M`). Durations with years or months, fractional values, or no values at all aren't
+/// supported.
+fn parse_duration(raw: &str) -> Option<Duration> {
+    let rest = raw.strip_prefix('P')?;
+    let (date, time) = match rest.split_once('T') {
+        Some((_, "")) => return None,
+        Some((d, t)) => (d, Some(t)),
+        None => (rest, None),
+    };
+
+    let mut total: i64 = 0;
+    let mut components = 0;
+    let parts = [
+        (date, &[('W', 7 * 86400), ('D', 86400)][..]),
+        (time.unwrap_or(""), &[('H', 3600), ('M', 60), ('S', 1)][..]),
+    ];
+
+    for (part, designators) in parts {
+        // Designators must be in order and each may only be used once.
+        let mut allowed = designators.iter();
+        let mut remaining = part;
+        while !remaining.is_empty() {
+            let end = remaining.find(|c: char| !c.is_ascii_digit())?;
+            let (digits, tail) = remaining.split_at(end);
+            let designator = tail.chars().next()?;
+            let (_, secs) = allowed.by_ref().find(|(d, _)| *d == designator)?;
+            let n: i64 = digits.parse().ok()?;
+
+            total = n.checked_mul(*secs).and_then(|v| total.checked_add(v))?;
+            components += 1;
+            remaining = &tail[designator.len_utf8()..];
+        }
+    }
+
+    // Durations are stored as milliseconds so anything longer can't be represented.
+    if components == 0 || total > i64::MAX / 1000 {
+        None
+    } else {
+        Some(Duration::seconds(total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn time(h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 5, h, m, 0).unwrap()
+    }
+
+    fn interval(raw: &str) -> Interval {
+        Interval::parse(raw).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let i = interval("2024-01-05T18:00:00+00:00/PT6H");
+        assert_eq!("2024-01-05T18:00:00+00:00/PT6H", i.raw());
+        assert_eq!(time(18, 0), i.start());
+        assert_eq!(Duration::hours(6), i.duration());
+        assert_eq!(time(18, 0) + Duration::hours(6), i.end());
+
+        // Start times with an offset are converted to UTC
+        let i = interval("2024-01-05T13:00:00-05:00/PT1H");
+        assert_eq!(time(18, 0), i.start());
+
+        for (raw, expected) in [
+            ("P1D", Duration::days(1)),
+            ("P1W", Duration::weeks(1)),
+            ("P1DT6H", Duration::hours(30)),
+            ("PT30M", Duration::minutes(30)),
+            ("PT1H30M15S", Duration::seconds(5415)),
+            ("P2W3D", Duration::days(17)),
+            ("PT0S", Duration::zero()),
+        ] {
+            let i = interval(&format!("2024-01-05T18:00:00+00:00/{}", raw));
+            assert_eq!(expected, i.duration(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let start = "2024-01-05T18:00:00+00:00";
+        assert_eq!(Err(IntervalError::Format(start.to_owned())), Interval::parse(start));
+        assert!(matches!(
+            Interval::parse("yesterday/PT1H"),
+            Err(IntervalError::Start(ref s, _)) if s == "yesterday"
+        ));
+
+        // Years and months don't have a fixed length, and components must be in order
+        for duration in [
+            "",
+            "P",
+            "PT",
+            "1H",
+            "PT1",
+            "PTH",
+            "P1Y",
+            "P1M",
+            "P1DT",
+            "PT1.5H",
+            "PT30M1H",
+            "PT1H1H",
+            "P1D1W",
+            "PT-1H",
+            "P99999999999999W",
+        ] {
+            assert_eq!(
+                Err(IntervalError::Duration(duration.to_owned())),
+                Interval::parse(&format!("{}/{}", start, duration)),
+                "{}",
+                duration
+            );
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        let i: Interval = serde_json::from_str("\"2024-01-05T18:00:00+00:00/PT6H\"").unwrap();
+        assert_eq!(time(18, 0), i.start());
+        assert_eq!("\"2024-01-05T18:00:00+00:00/PT6H\"", serde_json::to_string(&i).unwrap());
+        assert!(serde_json::from_str::<Interval>("\"2024-01-05T18:00:00+00:00/P1M\"").is_err());
+    }
+
+    #[test]
+    fn test_contains() {
+        let i = interval("2024-01-05T06:00:00+00:00/PT6H");
+        assert!(!i.contains(time(5, 59)));
+        assert!(i.contains(time(6, 0)));
+        assert!(i.contains(time(11, 59)));
+        assert!(!i.contains(time(12, 0)));
+
+        // Nothing is within an empty interval, not even its start
+        assert!(!interval("2024-01-05T06:00:00+00:00/PT0S").contains(time(6, 0)));
+    }
+
+    #[test]
+    fn test_overlap() {
+        let i = interval("2024-01-05T06:00:00+00:00/PT6H");
+        for (start, end, expected, fraction) in [
+            // Entirely within
+            (time(0, 0), time(18, 0), Duration::hours(6), 1.0),
+            (time(6, 0), time(12, 0), Duration::hours(6), 1.0),
+            // Partially within, from either side
+            (time(9, 0), time(18, 0), Duration::hours(3), 0.5),
+            (time(0, 0), time(7, 30), Duration::minutes(90), 0.25),
+            (time(8, 0), time(10, 0), Duration::hours(2), 1.0 / 3.0),
+            // Touching the interval at either end isn't overlapping it
+            (time(0, 0), time(6, 0), Duration::zero(), 0.0),
+            (time(12, 0), time(18, 0), Duration::zero(), 0.0),
+            // Nothing is between times that are backwards
+            (time(10, 0), time(8, 0), Duration::zero(), 0.0),
+        ] {
+            assert_eq!(expected, i.overlap(start, end), "{} to {}", start, end);
+            assert!(
+                (fraction - i.overlap_fraction(start, end)).abs() < 1e-9,
+                "{} to {}",
+                start,
+                end
+            );
+        }
+
+        // Empty intervals are entirely within times that include their start
+        let empty = interval("2024-01-05T06:00:00+00:00/PT0S");
+        assert_eq!(1.0, empty.overlap_fraction(time(6, 0), time(7, 0)));
+        assert_eq!(1.0, empty.overlap_fraction(time(5, 0), time(7, 0)));
+        assert_eq!(0.0, empty.overlap_fraction(time(5, 0), time(6, 0)));
+        assert_eq!(0.0, empty.overlap_fraction(time(6, 1), time(7, 0)));
+    }
+
+    #[test]
+    fn test_prorated_sum() {
+        // Consecutive six hour amounts of 6, 12, and 3
+        let intervals = [
+            (interval("2024-01-05T00:00:00+00:00/PT6H"), 6.0),
+            (interval("2024-01-05T06:00:00+00:00/PT6H"), 12.0),
+            (interval("2024-01-05T12:00:00+00:00/PT6H"), 3.0),
+        ];
+        let sum = |start, end| prorated_sum(intervals.iter().map(|(i, v)| (i, *v)), start, end);
+
+        assert_eq!(Some(21.0), sum(time(0, 0), time(18, 0)));
+        assert_eq!(Some(21.0), sum(time(0, 0), time(23, 0)));
+        assert_eq!(Some(12.0), sum(time(6, 0), time(12, 0)));
+        // Half of the first, all of the second, and a third of the last
+        assert_eq!(Some(3.0 + 12.0 + 1.0), sum(time(3, 0), time(14, 0)));
+        // Within a single interval
+        assert_eq!(Some(4.0), sum(time(7, 0), time(9, 0)));
+        // Nothing overlaps
+        assert_eq!(None, sum(time(18, 0), time(23, 0)));
+        assert_eq!(None, prorated_sum(std::iter::empty(), time(0, 0), time(18, 0)));
+
+        // An overlapping interval with no amount is still a total of zero rather than no total
+        let dry = interval("2024-01-05T00:00:00+00:00/PT6H");
+        assert_eq!(Some(0.0), prorated_sum([(&dry, 0.0)], time(0, 0), time(1, 0)));
+    }
+}
@@ -22,16 +22,207 @@
 //!
 //! `nws_exporter` fetches weather information for a particular [NWS station] using the [api.weather.gov] API and emits
 //! it as Prometheus metrics. Users must pick a particular station to fetch weather information from. The following
-//! metrics are emitted when available (not all fields are available for all stations).
+//! metrics are emitted when available (not all fields are available for all stations). Metrics for a field are
+//! removed when the station stops reporting a value for it rather than left at the last reported value. All weather
+//! metrics for a station are removed after fetching its observations fails `--stale-after-failures` times in a row
+//! (3 by default) and return once fetching succeeds again. Values are converted to the unit in the metric name
+//! from whichever unit the station reports them in, values in units that can't be converted are skipped. `$STATION`
+//! is the full URL of the station by default, use `--station-label-format id` to use only its identifier
+//! (e.g. `KBOS`) for every metric instead.
 //!
-//! * `nws_station{station=$STATION, station_id=$STATION_ID, station_name=$STATION_NAME}` - Station metadata
-//! * `nws_elevation_meters{station=$STATION}` - Elevation of the station, in meters.
+//! * `nws_station{station=$STATION, station_id=$STATION_ID, station_name=$STATION_NAME, timezone=$TIMEZONE}` - Station
+//!   metadata. The timezone is empty if the station doesn't have one. Metadata is fetched again every
+//!   `--metadata-refresh-secs` (24 hours by default) and the previous series is replaced if it changed.
+//! * `nws_exporter_station_source{station=$STATION, source=$SOURCE}` - Where the station was configured from
+//!   (`cli`, `env`, `config`, `discovered`, `api`, or `fixture`).
+//! * `nws_conditions{station=$STATION, description=$DESCRIPTION}` - Text description of current conditions
+//!   (e.g. `Partly Cloudy`), always 1. Absent when no description is reported.
+//! * `nws_condition{station=$STATION, condition=$CONDITION}` - NWS condition code from the observation icon
+//!   (e.g. `rain_showers`), always 1. Absent when there is no icon.
+//! * `nws_observation_timestamp_seconds{station=$STATION}` - Time of the most recent observation, as a unix
+//!   timestamp.
+//! * `nws_elevation_meters{station=$STATION}` - Elevation of the station, in meters. Taken from station metadata
+//!   when available, otherwise from observations.
+//! * `nws_station_latitude_degrees{station=$STATION}` - Latitude of the station, in degrees.
+//! * `nws_station_longitude_degrees{station=$STATION}` - Longitude of the station, in degrees.
+//! * `nws_station_utc_offset_seconds{station=$STATION}` - Offset of the station timezone from UTC at the time of
+//!   the most recent observation, in seconds. Absent if the station doesn't have a timezone.
 //! * `nws_temperature_degrees{station=$STATION}` - Temperature, in degrees celsius.
+//! * `nws_temperature_today_max_degrees{station=$STATION}` and `nws_temperature_today_min_degrees{station=$STATION}` -
+//!   Highest and lowest temperature observed today, in degrees celsius. Days start at midnight in the timezone
+//!   of the station, or UTC if the station doesn't have a timezone. Observations from the last `--backfill-hours`
+//!   are included at startup so that these aren't reset by restarting the exporter.
 //! * `nws_dewpoint_degrees{station=$STATION}` - Dewpoint, in degrees celsius.
+//! * `nws_dewpoint_depression_degrees{station=$STATION}` - Temperature minus dewpoint, in degrees celsius.
 //! * `nws_barometric_pressure_pascals{station=$STATION}` - Barometric pressure, in pascals.
+//! * `nws_sea_level_pressure_pascals{station=$STATION}` - Sea level pressure, in pascals.
+//! * `nws_pressure_tendency_pascals_per_hour{station=$STATION}` - Rate of change of barometric pressure between
+//!   the two most recent observations, in pascals per hour. With `--backfill-hours`, this is set from the first
+//!   observation after startup instead of the second.
 //! * `nws_visibility_meters{station=$STATION}` - Visibility, in meters.
+//! * `nws_visibility_capped{station=$STATION}` - 1 if visibility is at the 10 mile (16093 meter) maximum that stations
+//!   report, 0 otherwise. Use `--clamp-visibility` to export visibility at the maximum as exactly 16093 meters.
 //! * `nws_relative_humidity{station=$STATION}` - Relative humidity (0-100).
-//! * `nws_wind_chill_degrees{station=$STATION}` - Temperature with wind chill, in degrees celsius.
+//! * `nws_relative_humidity_derived{station=$STATION}` - Relative humidity (0-100) computed from temperature and
+//!   dewpoint. Only exported with `--compute-derived` when the API doesn't report relative humidity.
+//! * `nws_absolute_humidity_grams_per_cubic_meter{station=$STATION}` - Absolute humidity, in grams per cubic
+//!   meter. Only exported with `--compute-derived`.
+//! * `nws_wind_chill_degrees{station=$STATION}` - Temperature with wind chill, in degrees celsius. Computed when
+//!   not reported if `--compute-derived` is used, it is at most 10 degrees, and the wind is at least 4.8 km/h.
+//! * `nws_apparent_temperature_degrees{station=$STATION}` - Temperature it feels like, in degrees celsius. This
+//!   is the heat index at or above 26.7 degrees, wind chill at or below 10 degrees, and the temperature otherwise.
+//! * `nws_wet_bulb_temperature_degrees{station=$STATION}` - Wet bulb temperature, in degrees celsius. Only exported
+//!   with `--compute-derived` for temperatures between -20 and 50 degrees and relative humidity between 5 and 99.
+//! * `nws_heat_index_degrees{station=$STATION}` - Heat index, in degrees celsius. Absent when not reported, unless
+//!   `--compute-derived` is used and it is at least 26.7 degrees.
+//! * `nws_wind_speed_kph{station=$STATION}` - Wind speed, in kilometers per hour.
+//! * `nws_wind_gust_kph{station=$STATION}` - Wind gust speed, in kilometers per hour.
+//! * `nws_wind_direction_degrees{station=$STATION}` - Direction the wind is blowing from, in degrees
+//!   (0-360, north is always 0). Absent when the wind is calm.
+//! * `nws_wind_direction_sin{station=$STATION}` and `nws_wind_direction_cos{station=$STATION}` - Sine and cosine
+//!   of the wind direction, which can be averaged over time unlike the direction. Absent when the wind is calm.
+//! * `nws_cloud_layer_base_meters{station=$STATION, layer=$LAYER}` - Height of the base of each cloud layer,
+//!   in meters. Layers are numbered from 0, lowest first. Absent for layers without a base (clear skies).
+//! * `nws_cloud_layer_amount{station=$STATION, layer=$LAYER, amount=$AMOUNT}` - Amount of sky covered by each
+//!   cloud layer (`CLR`, `FEW`, `SCT`, `BKN`, `OVC`, or `VV`), always 1.
+//! * `nws_sky_cover_oktas{station=$STATION}` - Sky cover of the cloud layer with the highest coverage, in oktas
+//!   (0-8, or 9 when the sky is obscured). Absent when no cloud layers are reported.
+//! * `nws_cloud_layers{station=$STATION}` - Number of cloud layers, not counting clear skies.
+//! * `nws_cloud_ceiling_meters{station=$STATION}` - Height of the base of the lowest broken, overcast, or obscured
+//!   cloud layer, in meters. Absent when there is no ceiling.
+//! * `nws_precipitation_meters_total{station=$STATION}` - Precipitation, in meters. Incremented by the precipitation
+//!   for the last hour each time a station reports a new observation.
+//! * `nws_heat_risk_level{station=$STATION, level=$LEVEL}` - NWS heat index risk level (`caution`,
+//!   `extreme_caution`, `danger`, or `extreme_danger`). The current level is 1 and all others are 0. Absent
+//!   when the heat index is unavailable or below the lowest level.
+//! * `nws_cold_risk_level{station=$STATION, level=$LEVEL}` - Cold risk level (`advisory` or `warning`) based
+//!   on wind chill, or temperature when the wind is calm. The current level is 1 and all others are 0. Absent
+//!   when neither is available or it is warmer than the advisory threshold.
+//! * `nws_forecast_period_start_timestamp_seconds{station=$STATION, period=$PERIOD}` - Time each forecast period
+//!   starts, as a unix timestamp. Periods are numbered from 0 for the current period. Forecast metrics are only
+//!   exported with `--forecast-periods` set to the number of periods to export (up to 6) and are fetched for the
+//!   location of each station every `--forecast-refresh-secs` (1 hour by default).
+//! * `nws_forecast_temperature_degrees{station=$STATION, period=$PERIOD}` - Forecast temperature, in degrees celsius.
+//! * `nws_forecast_wind_speed_kph{station=$STATION, period=$PERIOD}` - Forecast wind speed, in kilometers per hour.
+//!   The highest speed is used when the forecast is a range like "5 to 10 mph".
+//! * `nws_forecast_precipitation_probability{station=$STATION, period=$PERIOD}` - Forecast probability of
+//!   precipitation, in percent (0-100). Absent when not forecast.
+//! * `nws_forecast_temperature_next_hours_degrees{station=$STATION, hours_ahead=$HOURS}` - Temperature from the
+//!   hourly forecast for 1, 3, 6, and 12 hours after the forecast was fetched, in degrees celsius. The hourly
+//!   forecast is fetched along with the forecast when `--forecast-periods` is set.
+//! * `nws_forecast_precipitation_probability_next_hours{station=$STATION, hours_ahead=$HOURS}` - Probability of
+//!   precipitation from the hourly forecast for 1, 3, 6, and 12 hours after the forecast was fetched, in percent.
+//! * `nws_forecast_precipitation_next_24h_meters{station=$STATION}` - Total precipitation forecast for the 24 hours
+//!   after the forecast was fetched, in meters. Fetched from raw gridpoint data along with the forecast. Intervals
+//!   of the forecast that are only partially within the 24 hours count the same fraction of their precipitation.
+//! * `nws_forecast_snowfall_next_24h_meters{station=$STATION}` - Total snowfall forecast for the 24 hours after the
+//!   forecast was fetched, in meters.
+//! * `nws_forecast_sky_cover_percent{station=$STATION}` - Forecast percent of the sky covered by clouds when the
+//!   forecast was fetched (0-100).
+//! * `nws_alert{station=$STATION, zone=$ZONE, zone_name=$NAME, event=$EVENT, severity=$SEVERITY}` - Each active
+//!   alert for the zone or station, always 1 (e.g. `event="Winter Storm Warning", severity="Severe"`). Alerts are
+//!   only fetched for zones given with `--alert-zone`, with an empty `station` label, and for the location of each
+//!   station with `--alerts`, with empty `zone` and `zone_name` labels. They are fetched every `--alert-refresh-secs`
+//!   (5 minutes by default). Alert zones are looked up at startup to get their names and the exporter exits if any
+//!   don't exist. Series are removed when alerts expire or are cancelled.
+//! * `nws_active_alerts{station=$STATION, zone=$ZONE, zone_name=$NAME, severity=$SEVERITY}` - Number of active
+//!   alerts for the zone or station with each severity (`Extreme`, `Severe`, `Moderate`, `Minor`, or `Unknown`).
+//!   All are 0 when there are no alerts.
+//! * `nws_alert_onset_timestamp_seconds{station=$STATION, zone=$ZONE, zone_name=$NAME, event=$EVENT,
+//!   severity=$SEVERITY}` - Time the event of each active alert is expected to begin, as a unix timestamp. Absent
+//!   for alerts without an onset.
+//! * `nws_alert_expires_timestamp_seconds{station=$STATION, zone=$ZONE, zone_name=$NAME, event=$EVENT,
+//!   severity=$SEVERITY}` - Time the event of each active alert is expected to end, or the alert expires when the
+//!   end isn't known, as a unix timestamp.
+//! * `nws_station_reporting{station=$STATION}` - 1 if observations for the station are advancing, 0 if the
+//!   latest observation hasn't changed for longer than `--flatline-secs` despite successful fetches.
+//! * `nws_up{station=$STATION}` - 1 if the most recent observation for the station was fetched successfully, 0 if
+//!   fetching it failed.
+//! * `nws_last_fetch_success_timestamp_seconds{station=$STATION}` - Time an observation for the station was last
+//!   fetched successfully, as a unix timestamp.
+//! * `nws_observation_age_seconds{station=$STATION}` - Age of the most recent observation for the station when
+//!   it was fetched, in seconds. Measured from the time in the `Date` header of the API response when there is
+//!   one rather than the local clock. Observations with timestamps in the future are reported as zero.
+//!   Observations with unparseable timestamps fail to decode entirely and are counted as fetch errors with
+//!   reason `decode`.
+//! * `nws_future_timestamps_total{station=$STATION}` - Observations timestamped further in the future than
+//!   `--future-tolerance-secs` (5 minutes by default). These are exported with an age of zero, or skipped and
+//!   counted as failed fetches with `--reject-future-observations`.
+//! * `nws_fetch_errors_total{station=$STATION, reason=$REASON, code=$CODE}` - Number of failed attempts to fetch an
+//!   observation for the station. `reason` is one of `timeout`, `connect`, `decode`, `invalid_station`,
+//!   `no_observations`, `unexpected_status`, `redirect`, `body_too_large`, or `internal`. `code` is the HTTP
+//!   status code for `unexpected_status` and `redirect` errors and empty otherwise.
+//! * `nws_consecutive_fetch_failures{station=$STATION}` - Number of consecutive failed attempts to fetch an
+//!   observation for the station. Reset to 0 after a successful fetch.
+//! * `nws_stale_observations_total{station=$STATION}` - Observations skipped for being older than
+//!   `--max-observation-age-secs`. Skipped observations count as failed fetches.
+//! * `nws_unchanged_observations_total{station=$STATION}` - Fetches that returned the same observation as the
+//!   previous fetch for the station. Weather metrics aren't updated again from unchanged observations.
+//! * `nws_forecast_fetch_errors_total{station=$STATION, reason=$REASON}` - Number of failed attempts to fetch a
+//!   forecast, hourly forecast, or gridpoint data for the location of the station. `reason` is the same as for
+//!   `nws_fetch_errors_total`.
+//! * `nws_alert_fetch_errors_total{station=$STATION, zone=$ZONE, reason=$REASON}` - Number of failed attempts to
+//!   fetch alerts for the zone or station. `reason` is the same as for `nws_fetch_errors_total`, or `invalid_zone`
+//!   for zones that don't exist.
+//! * `nws_last_observation_info{station=$STATION, observation_id=$ID}` - ID of the most recent observation
+//!   for the station, always 1. Only exported with `--expose-observation-id` since every observation creates a new
+//!   series.
+//! * `nws_measurement_quality{station=$STATION, field=$FIELD, qc=$QC}` - Quality control code for each measurement
+//!   in the most recent observation (e.g. `V` for verified or `X` for rejected), always 1.
+//! * `nws_inconsistent_observations_total{station=$STATION, check=$CHECK}` - Observations with measurements that
+//!   aren't physically possible together (`dewpoint_above_temperature`, `humidity_out_of_range`,
+//!   `wind_chill_above_temperature`, or `gust_below_speed`). Use `--reject-inconsistent` to skip exporting them.
+//! * `nws_qc_rejected_total{station=$STATION, field=$FIELD, qc=$QC}` - Measurements not exported because their
+//!   quality control code isn't accepted. Only `V`, `C`, and `S` are accepted by default. Use
+//!   `--qc-accept V,C,S,Z` to also export preliminary values that haven't been checked yet. Measurements with
+//!   unknown codes are always exported.
+//! * `nws_invalid_values_total{station=$STATION, field=$FIELD}` - Values not exported because they are NaN or
+//!   infinite.
+//! * `nws_implausible_values_total{station=$STATION, field=$FIELD}` - Values not exported because they are outside
+//!   of the plausible range for the field, usually sensor sentinels like -99900 for pressure. For example, a
+//!   temperature outside of -90 to 60 degrees celsius or a relative humidity outside of 0 to 100 percent. Ranges
+//!   may be changed in the config file.
+//! * `nws_missing_fields_total{station=$STATION, field=$FIELD}` - Measurements left out of observations entirely
+//!   instead of reported without a value, usually because the station doesn't have the sensor. Observations missing
+//!   some fields are still exported.
+//! * `nws_exporter_suppressed_warnings_total{station=$STATION, category=$CATEGORY}` - Repeated warnings that
+//!   were not logged.
+//! * `nws_exporter_api_redirects_total{station=$STATION}` - API requests that were redirected, usually
+//!   because a station ID is stale.
+//! * `nws_exporter_api_observation_fallbacks_total{station=$STATION}` - Times the latest observation for a
+//!   station wasn't found (404) and the most recent observation from its list of observations was used instead.
+//! * `nws_exporter_api_request_retries_total{station=$STATION}` - API requests that were made again after timing
+//!   out, failing to connect, or failing with a 429 or 5xx status. Requests are made up to `--max-attempts` times
+//!   in total.
+//! * `nws_exporter_series_count{family=$FAMILY}` - Number of series currently exported for each metric family.
+//! * `nws_exporter_station_mismatch_total{station=$STATION}` - Observations that claimed to be from a different
+//!   station than the one requested. These are still attributed to the requested station.
+//! * `nws_exporter_api_requests_total{station=$STATION, endpoint=$ENDPOINT, trigger=$TRIGGER}` - API requests
+//!   made, by endpoint and what triggered them (`scheduled`, `scrape`, `probe`, `manual`, or `backfill`).
+//! * `nws_exporter_api_request_duration_seconds{station=$STATION, endpoint=$ENDPOINT, trigger=$TRIGGER}` -
+//!   Histogram of the time taken for the API to respond to requests, with buckets from 10ms up to the request
+//!   timeout.
+//! * `nws_exporter_api_responses_total{endpoint=$ENDPOINT, code=$CODE}` - Responses received from the API by
+//!   endpoint and HTTP status code, including redirects and errors.
+//! * `nws_exporter_api_up` - 1 if the API responded to at least one request during the most recent refresh, 0
+//!   if every request failed because of a connection problem or server error (5xx).
+//! * `nws_exporter_api_consecutive_failures` - Number of consecutive refreshes where the API was down.
+//! * `nws_refresh_cycle_duration_seconds` - Time taken to fetch observations for every station during the most
+//!   recent scheduled refresh. Values close to the refresh interval mean refreshes are falling behind.
+//! * `nws_stations_configured` - Number of stations observations are fetched for.
+//! * `nws_exporter_config{refresh_secs=$SECS, api_url=$URL, stations=$COUNT}` - Always 1, labeled with how the
+//!   exporter is configured. Credentials and query strings are removed from the API URL.
+//! * `nws_http_requests_total{path=$PATH, method=$METHOD, code=$CODE}` - Requests handled by the exporter's HTTP
+//!   server. Requests for unknown paths are recorded with the path `other`.
+//! * `nws_http_request_duration_seconds{path=$PATH, method=$METHOD}` - Histogram of the time taken to handle
+//!   requests made to the exporter's HTTP server.
+//! * `process_cpu_seconds_total`, `process_resident_memory_bytes`, `process_open_fds`, `process_start_time_seconds` -
+//!   Resource usage of the exporter process. Only available on platforms with `/proc` (Linux).
+//! * `nws_runtime_workers`, `nws_runtime_alive_tasks`, `nws_runtime_global_queue_depth` - Worker threads, alive
+//!   tasks, and globally queued tasks of the async runtime. Only exported with `--runtime-metrics`. Building with
+//!   the `unstable-runtime-metrics` feature and `RUSTFLAGS="--cfg tokio_unstable"` also exports
+//!   `nws_runtime_blocking_threads`, `nws_runtime_idle_blocking_threads`, `nws_runtime_blocking_queue_depth`, and
+//!   `nws_runtime_local_queue_depth`.
 //!
 //! [NWS station]: https://www.weather.gov/documentation/services-web-api#/default/obs_stations
 //! [api.weather.gov]: https://www.weather.gov/documentation/services-web-api
@@ -80,6 +271,14 @@
 //! ./nws_exporter KBOS
 //! ```
 //!
+//! Alternatively, `nws_exporter` can find the station nearest to a location when it starts, using `--latitude` and
+//! `--longitude` in degrees. The station it picked is logged and labeled with the `discovered` source. Stations
+//! may be given as well to fetch observations for them too.
+//!
+//! ```text
+//! ./nws_exporter --latitude 42.3601 --longitude -71.0589
+//! ```
+//!
 //! ### Run
 //!
 //! You can run `nws_exporter` as a Systemd service using the [provided unit file](ext/nws_exporter.service). This
@@ -119,5 +318,25 @@
 //!
 
 pub mod client;
+pub mod clock;
+pub mod config;
+pub mod consistency;
+pub mod derive;
+pub mod fixture;
+pub mod geo;
 pub mod http;
+pub mod interval;
+pub mod limits;
 pub mod metrics;
+pub mod process;
+pub mod quality;
+pub mod reporting;
+pub mod risk;
+pub mod runtime;
+pub mod sanitize;
+pub mod sink;
+pub mod sky;
+pub mod status;
+pub mod units;
+pub mod update;
+pub mod warn;
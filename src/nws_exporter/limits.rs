@@ -0,0 +1,295 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+use crate::client::ObservationProperties;
+use crate::units::{convert, Unit};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Default range of plausible values for measurements and derived values by field, in the unit
+/// the value is exported in. Values outside of these are sensor sentinels (like -99900 for
+/// pressure or -999 for elevation) or errors from the API (or a bug computing a derived value)
+/// rather than weather and are never exported. Ranges may be overridden in the config file for
+/// stations with unusual weather.
+const LIMITS: [(&str, Unit, Range); 17] = [
+    ("elevation", Unit::Meters, Range::new(-500.0, 9000.0)),
+    ("temperature", Unit::Celsius, Range::new(-90.0, 60.0)),
+    ("dewpoint", Unit::Celsius, Range::new(-90.0, 60.0)),
+    ("wind_direction", Unit::Degrees, Range::new(0.0, 360.0)),
+    ("wind_speed", Unit::KilometersPerHour, Range::new(0.0, 540.0)),
+    ("wind_gust", Unit::KilometersPerHour, Range::new(0.0, 540.0)),
+    ("barometric_pressure", Unit::Pascals, Range::new(50_000.0, 110_000.0)),
+    ("sea_level_pressure", Unit::Pascals, Range::new(80_000.0, 110_000.0)),
+    ("visibility", Unit::Meters, Range::new(0.0, 200_000.0)),
+    ("precipitation_last_hour", Unit::Meters, Range::new(0.0, 0.5)),
+    ("relative_humidity", Unit::Percent, Range::new(0.0, 100.0)),
+    ("wind_chill", Unit::Celsius, Range::new(-90.0, 60.0)),
+    ("heat_index", Unit::Celsius, Range::new(-90.0, 90.0)),
+    ("derived_relative_humidity", Unit::Percent, Range::new(0.0, 100.0)),
+    ("dewpoint_depression", Unit::Celsius, Range::new(-5.0, 150.0)),
+    ("wet_bulb_temperature", Unit::Celsius, Range::new(-90.0, 60.0)),
+    ("apparent_temperature", Unit::Celsius, Range::new(-90.0, 90.0)),
+];
+
+/// Inclusive range of plausible values for a field.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Range {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Range {
+    pub const fn new(min: f64, max: f64) -> Self {
+        Self { min, max }
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        value >= self.min && value <= self.max
+    }
+}
+
+/// Return true if the field has a default range and so may be overridden.
+pub fn is_known(field: &str) -> bool {
+    LIMITS.iter().any(|(f, _, _)| *f == field)
+}
+
+/// Reason that a value isn't exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Invalid {
+    NotFinite,
+    Implausible,
+}
+
+impl Invalid {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotFinite => "not_finite",
+            Self::Implausible => "implausible",
+        }
+    }
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Check that a value for a field is finite and, if there is a range for the field, within it.
+/// Ranges in `overrides` are used instead of the default range for a field. Values must be in
+/// the unit the field is exported in.
+pub fn check(field: &str, value: f64, overrides: &HashMap<String, Range>) -> Result<f64, Invalid> {
+    if !value.is_finite() {
+        return Err(Invalid::NotFinite);
+    }
+
+    let range = overrides
+        .get(field)
+        .or_else(|| LIMITS.iter().find(|(f, _, _)| *f == field).map(|(_, _, r)| r));
+    match range {
+        Some(r) if !r.contains(value) => Err(Invalid::Implausible),
+        _ => Ok(value),
+    }
+}
+
+/// Measurement in an observation with a value that isn't finite or isn't plausible for the
+/// field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub field: &'static str,
+    pub value: f64,
+    pub reason: Invalid,
+}
+
+impl Violation {
+    /// Find all measurements with an invalid value. Measurements are converted to the unit of
+    /// their range before being checked, measurements in units that can't be converted are
+    /// left for the caller to handle.
+    pub fn find(props: &ObservationProperties, overrides: &HashMap<String, Range>) -> Vec<Self> {
+        props
+            .measurements()
+            .into_iter()
+            .filter_map(|(field, m)| {
+                let value = match LIMITS.iter().find(|(f, _, _)| *f == field) {
+                    Some((_, unit, _)) => convert(m, *unit)?,
+                    None => m.value?,
+                };
+
+                check(field, value, overrides)
+                    .err()
+                    .map(|reason| Self { field, value, reason })
+            })
+            .collect()
+    }
+
+    /// Remove the value of the invalid measurement.
+    pub fn suppress(&self, props: &mut ObservationProperties) {
+        if let Some((_, m)) = props.measurements_mut().into_iter().find(|(f, _)| *f == self.field) {
+            m.value = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Observation;
+
+    fn properties() -> ObservationProperties {
+        let obs: Observation = serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap();
+        obs.properties
+    }
+
+    #[test]
+    fn test_check_not_finite() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            // Even fields without a range must be finite
+            for field in ["temperature", "unknown"] {
+                assert_eq!(
+                    Err(Invalid::NotFinite),
+                    check(field, value, &HashMap::new()),
+                    "{} {}",
+                    field,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_range() {
+        let none = HashMap::new();
+        for (field, value, expected) in [
+            ("temperature", -90.0, Ok(-90.0)),
+            ("temperature", 60.0, Ok(60.0)),
+            ("temperature", -90.1, Err(Invalid::Implausible)),
+            ("temperature", 60.1, Err(Invalid::Implausible)),
+            ("relative_humidity", 0.0, Ok(0.0)),
+            ("relative_humidity", 100.0, Ok(100.0)),
+            ("relative_humidity", -0.1, Err(Invalid::Implausible)),
+            ("relative_humidity", 100.1, Err(Invalid::Implausible)),
+            ("barometric_pressure", -99900.0, Err(Invalid::Implausible)),
+            ("elevation", -999.0, Err(Invalid::Implausible)),
+            ("unknown", 1e300, Ok(1e300)),
+        ] {
+            assert_eq!(expected, check(field, value, &none), "{} {}", field, value);
+        }
+    }
+
+    #[test]
+    fn test_check_overrides() {
+        let overrides = HashMap::from([("temperature".to_owned(), Range::new(-100.0, 70.0))]);
+        assert_eq!(Ok(65.0), check("temperature", 65.0, &overrides));
+        assert_eq!(Err(Invalid::Implausible), check("temperature", 71.0, &overrides));
+        assert_eq!(Err(Invalid::NotFinite), check("temperature", f64::NAN, &overrides));
+        assert_eq!(Err(Invalid::Implausible), check("dewpoint", 65.0, &overrides));
+
+        assert!(is_known("temperature"));
+        assert!(is_known("apparent_temperature"));
+        assert!(!is_known("unknown"));
+    }
+
+    #[test]
+    fn test_find() {
+        assert!(Violation::find(&properties(), &HashMap::new()).is_empty());
+
+        let mut props = properties();
+        props.temperature.value = Some(f64::NAN);
+        props.relative_humidity.value = Some(250.0);
+        props.barometric_pressure.value = Some(f64::NEG_INFINITY);
+        let violations = Violation::find(&props, &HashMap::new());
+        let found: Vec<(&str, Invalid)> = violations.iter().map(|v| (v.field, v.reason)).collect();
+        assert_eq!(
+            vec![
+                ("temperature", Invalid::NotFinite),
+                ("barometric_pressure", Invalid::NotFinite),
+                ("relative_humidity", Invalid::Implausible),
+            ],
+            found
+        );
+
+        for v in violations.iter() {
+            v.suppress(&mut props);
+        }
+        assert!(Violation::find(&props, &HashMap::new()).is_empty());
+        assert_eq!(None, props.temperature.value);
+        assert_eq!(None, props.relative_humidity.value);
+        assert_eq!(None, props.barometric_pressure.value);
+        assert_eq!(Some(6.1), props.dewpoint.value);
+    }
+
+    #[test]
+    fn test_find_converted() {
+        // Ranges are checked in the unit the field is exported in
+        let mut props = properties();
+        props.temperature.unit_code = "wmoUnit:degF".to_owned();
+        props.temperature.value = Some(130.0);
+        assert!(Violation::find(&props, &HashMap::new()).is_empty());
+
+        props.temperature.value = Some(150.0);
+        let violations = Violation::find(&props, &HashMap::new());
+        assert_eq!(1, violations.len());
+        assert_eq!("temperature", violations[0].field);
+        assert_eq!(Invalid::Implausible, violations[0].reason);
+    }
+
+    #[test]
+    fn test_check_every_boundary() {
+        let none = HashMap::new();
+        for (field, _, range) in LIMITS.iter() {
+            // Ranges are inclusive at both ends
+            assert_eq!(Ok(range.min), check(field, range.min, &none), "{} min", field);
+            assert_eq!(Ok(range.max), check(field, range.max, &none), "{} max", field);
+
+            let step = (range.max - range.min) * 1e-6;
+            assert_eq!(
+                Err(Invalid::Implausible),
+                check(field, range.min - step, &none),
+                "{} below min",
+                field
+            );
+            assert_eq!(
+                Err(Invalid::Implausible),
+                check(field, range.max + step, &none),
+                "{} above max",
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_sentinels() {
+        let none = HashMap::new();
+        for (field, value) in [
+            ("barometric_pressure", -99900.0),
+            ("sea_level_pressure", -99900.0),
+            ("sea_level_pressure", 75_000.0),
+            ("elevation", -999.0),
+            ("temperature", -999.0),
+            ("wind_speed", 9999.0),
+        ] {
+            assert_eq!(
+                Err(Invalid::Implausible),
+                check(field, value, &none),
+                "{} {}",
+                field,
+                value
+            );
+        }
+    }
+}
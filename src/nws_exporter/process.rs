@@ -0,0 +1,199 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use prometheus_client::collector::Collector;
+use prometheus_client::metrics::counter::ConstCounter;
+use prometheus_client::metrics::gauge::ConstGauge;
+use prometheus_client::registry::{Descriptor, LocalMetric};
+use prometheus_client::MaybeOwned;
+use std::borrow::Cow;
+use std::io;
+
+/// Collector for the resource usage of the exporter process itself.
+///
+/// Values are read from `/proc/self` each time metrics are encoded. On platforms without
+/// `/proc` (or if it can't be read) no metrics are produced.
+#[derive(Debug, Default)]
+pub struct ProcessCollector;
+
+impl ProcessCollector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Collector for ProcessCollector {
+    fn collect<'a>(
+        &'a self,
+    ) -> Box<dyn Iterator<Item = (Cow<'a, Descriptor>, MaybeOwned<'a, Box<dyn LocalMetric>>)> + 'a> {
+        let stats = match ProcessStats::read() {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::debug!(message = "unable to read process stats", error = %e);
+                return Box::new(std::iter::empty());
+            }
+        };
+
+        let metrics: Vec<(Descriptor, Box<dyn LocalMetric>)> = vec![
+            (
+                descriptor("process_cpu_seconds", "Total user and system CPU time spent in seconds"),
+                Box::new(ConstCounter::new(stats.cpu_seconds)),
+            ),
+            (
+                descriptor("process_resident_memory_bytes", "Resident memory size in bytes"),
+                Box::new(ConstGauge::new(stats.resident_memory_bytes)),
+            ),
+            (
+                descriptor("process_open_fds", "Number of open file descriptors"),
+                Box::new(ConstGauge::new(stats.open_fds)),
+            ),
+            (
+                descriptor(
+                    "process_start_time_seconds",
+                    "Start time of the process since unix epoch in seconds",
+                ),
+                Box::new(ConstGauge::new(stats.start_time_seconds)),
+            ),
+        ];
+
+        Box::new(metrics.into_iter().map(|(d, m)| (Cow::Owned(d), MaybeOwned::Owned(m))))
+    }
+}
+
+fn descriptor(name: &str, help: &str) -> Descriptor {
+    Descriptor::new(name, help, None, None, Vec::new())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ProcessStats {
+    cpu_seconds: f64,
+    resident_memory_bytes: f64,
+    open_fds: i64,
+    start_time_seconds: f64,
+}
+
+impl ProcessStats {
+    #[cfg(unix)]
+    fn read() -> io::Result<Self> {
+        use std::fs;
+
+        let stat = fs::read_to_string("/proc/self/stat")?;
+        // The command name (the second field) is in parentheses and may contain spaces so
+        // fields are counted from the end of it, starting with the third field "state".
+        let fields: Vec<&str> = stat
+            .rsplit_once(')')
+            .map(|(_, rest)| rest.split_whitespace().collect())
+            .unwrap_or_default();
+        let field = |num: usize| -> io::Result<f64> {
+            fields
+                .get(num - 3)
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| invalid_data(format!("missing field {} in /proc/self/stat", num)))
+        };
+
+        let ticks = sysconf(libc::_SC_CLK_TCK)?;
+        let page_size = sysconf(libc::_SC_PAGESIZE)?;
+        let boot_time: f64 = fs::read_to_string("/proc/stat")?
+            .lines()
+            .find_map(|l| l.strip_prefix("btime "))
+            .and_then(|v| v.trim().parse().ok())
+            .ok_or_else(|| invalid_data("missing btime in /proc/stat".to_owned()))?;
+        let open_fds = fs::read_dir("/proc/self/fd")?.count() as i64;
+
+        Ok(Self {
+            cpu_seconds: (field(14)? + field(15)?) / ticks,
+            resident_memory_bytes: field(24)? * page_size,
+            open_fds,
+            start_time_seconds: boot_time + field(22)? / ticks,
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn read() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "process stats are only available on unix",
+        ))
+    }
+}
+
+#[cfg(unix)]
+fn sysconf(name: libc::c_int) -> io::Result<f64> {
+    // SAFETY: sysconf has no preconditions and only returns a value or -1 on error
+    let res = unsafe { libc::sysconf(name) };
+    if res > 0 {
+        Ok(res as f64)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(unix)]
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus_client::encoding::text;
+    use prometheus_client::registry::Registry;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_stats() {
+        let stats = ProcessStats::read().unwrap();
+        assert!(stats.cpu_seconds >= 0.0, "{:?}", stats);
+        assert!(stats.resident_memory_bytes > 0.0, "{:?}", stats);
+        assert!(stats.open_fds > 0, "{:?}", stats);
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64();
+        assert!(
+            stats.start_time_seconds > 0.0 && stats.start_time_seconds <= now + 1.0,
+            "{:?}",
+            stats
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_collector_registered() {
+        let mut reg = Registry::default();
+        reg.register_collector(Box::new(ProcessCollector::new()));
+        let mut out = String::new();
+        text::encode(&mut out, &reg).unwrap();
+
+        for name in [
+            "process_cpu_seconds_total",
+            "process_resident_memory_bytes",
+            "process_open_fds",
+            "process_start_time_seconds",
+        ] {
+            let value: f64 = out
+                .lines()
+                .find_map(|l| l.strip_prefix(&format!("{} ", name)))
+                .unwrap_or_else(|| panic!("missing {} in {}", name, out))
+                .parse()
+                .unwrap();
+            assert!(value >= 0.0, "{} = {}", name, value);
+        }
+    }
+}
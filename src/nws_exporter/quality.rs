@@ -0,0 +1,165 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::client::ObservationProperties;
+
+/// Quality control codes this exporter knows the meaning of. Values with one of these codes
+/// are only exported if the code is accepted. "V" (verified), "C" (coarse checks passed), and
+/// "S" (screened) are accepted by default. "Z" (preliminary, no checks done) and "X" (failed
+/// checks) are rejected by default.
+const KNOWN_CODES: [&str; 5] = ["V", "C", "S", "Z", "X"];
+
+/// Measurement in an observation with a quality control code that isn't accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub field: &'static str,
+    pub qc: String,
+}
+
+impl Rejection {
+    /// Find all measurements with a value and a known quality control code that isn't one of
+    /// the `accept` codes. Measurements with unknown codes are logged and never rejected.
+    pub fn find(props: &ObservationProperties, accept: &[String]) -> Vec<Self> {
+        props
+            .measurements()
+            .into_iter()
+            .filter(|(_, m)| m.value.is_some())
+            .filter_map(|(field, m)| {
+                let qc = m.quality_control.as_deref()?;
+                if accept.iter().any(|a| a == qc) {
+                    None
+                } else if KNOWN_CODES.contains(&qc) {
+                    Some(Self {
+                        field,
+                        qc: qc.to_owned(),
+                    })
+                } else {
+                    tracing::debug!(
+                        message = "accepting measurement with unknown quality control code",
+                        field = field,
+                        qc = qc
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Remove the value of the rejected measurement.
+    pub fn suppress(&self, props: &mut ObservationProperties) {
+        if let Some((_, m)) = props.measurements_mut().into_iter().find(|(f, _)| *f == self.field) {
+            m.value = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Observation;
+
+    fn default_accept() -> Vec<String> {
+        ["V", "C", "S"].map(String::from).to_vec()
+    }
+
+    /// Properties from the KBOS fixture with every measurement accepted except temperature,
+    /// which has the given quality control code.
+    fn properties(qc: Option<&str>) -> ObservationProperties {
+        let mut json: serde_json::Value = serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap();
+        for (_, m) in json["properties"].as_object_mut().unwrap().iter_mut() {
+            if m.get("qualityControl").is_some() {
+                m["qualityControl"] = "V".into();
+            }
+        }
+        json["properties"]["temperature"]["qualityControl"] = qc.into();
+        let obs: Observation = serde_json::from_value(json).unwrap();
+        obs.properties
+    }
+
+    #[test]
+    fn test_find_accepted() {
+        for qc in ["V", "C", "S"] {
+            assert_eq!(
+                Vec::<Rejection>::new(),
+                Rejection::find(&properties(Some(qc)), &default_accept()),
+                "{}",
+                qc
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_rejected() {
+        for qc in ["Z", "X"] {
+            assert_eq!(
+                vec![Rejection {
+                    field: "temperature",
+                    qc: qc.to_owned(),
+                }],
+                Rejection::find(&properties(Some(qc)), &default_accept()),
+                "{}",
+                qc
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_unknown_or_missing() {
+        for qc in [Some("Q"), Some(""), None] {
+            assert_eq!(
+                Vec::<Rejection>::new(),
+                Rejection::find(&properties(qc), &default_accept()),
+                "{:?}",
+                qc
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_custom_policy() {
+        // Accepting preliminary values but nothing screened
+        let accept = ["V", "C", "Z"].map(String::from).to_vec();
+        assert!(Rejection::find(&properties(Some("Z")), &accept).is_empty());
+        assert_eq!(
+            vec![Rejection {
+                field: "temperature",
+                qc: "S".to_owned(),
+            }],
+            Rejection::find(&properties(Some("S")), &accept)
+        );
+    }
+
+    #[test]
+    fn test_find_without_value() {
+        let mut props = properties(Some("X"));
+        props.temperature.value = None;
+        assert!(Rejection::find(&props, &default_accept()).is_empty());
+    }
+
+    #[test]
+    fn test_suppress() {
+        let mut props = properties(Some("X"));
+        let rejections = Rejection::find(&props, &default_accept());
+        for r in rejections.iter() {
+            r.suppress(&mut props);
+        }
+
+        assert_eq!(None, props.temperature.value);
+        assert_eq!(Some(6.1), props.dewpoint.value);
+    }
+}
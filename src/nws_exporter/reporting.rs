@@ -0,0 +1,414 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::ReportingConfig;
+use crate::sanitize::label_value;
+use crate::sink::{OutputSink, StationObservation};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::registry::Registry;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct Labels {
+    station: String,
+}
+
+/// Most recent observation seen for a station and when it was first seen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct StationState {
+    observation: DateTime<Utc>,
+    advanced_at: DateTime<Utc>,
+    reporting: bool,
+}
+
+/// Sink that detects stations whose observations have stopped advancing.
+///
+/// Some stations keep returning the same observation instead of an error when they stop
+/// reporting (e.g. when a station has been replaced by one with a new ID). Stations are
+/// considered flatlined when the observation timestamp hasn't changed for the configured
+/// threshold despite successful fetches. This is exported as `nws_station_reporting` and
+/// a warning is logged when a station stops reporting. If a state file is configured, the
+/// most recent observation for each station is persisted across restarts. The file is only
+/// written at the end of a refresh cycle in which the state of a station changed.
+#[derive(Debug)]
+pub struct ReportingSink {
+    reporting: Family<Labels, Gauge>,
+    states: Mutex<HashMap<String, StationState>>,
+    changed: AtomicBool,
+    threshold: Duration,
+    state_file: Option<PathBuf>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ReportingSink {
+    /// Create a new `ReportingSink`, register its metric with the provided `Registry`, and
+    /// load previous state from the state file if one is configured and exists.
+    pub fn new(reg: &mut Registry, config: &ReportingConfig) -> Self {
+        let reporting = Family::<Labels, Gauge>::default();
+        reg.register(
+            "nws_station_reporting",
+            "1 if observations for the station are advancing, 0 if they haven't changed recently",
+            reporting.clone(),
+        );
+
+        let states = config.state_file.as_deref().map(load_state).unwrap_or_default();
+        Self {
+            reporting,
+            states: Mutex::new(states),
+            changed: AtomicBool::new(false),
+            threshold: config.flatline(),
+            state_file: config.state_file.clone(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Use the provided `Clock` to determine how long observations haven't advanced.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Update the state of a station from an observation, returning true if it changed.
+    fn update(&self, states: &mut HashMap<String, StationState>, o: &StationObservation, now: DateTime<Utc>) -> bool {
+        let observation = o.observation.properties.observed_at();
+        let mut changed = !states.contains_key(&o.station);
+
+        let state = states.entry(o.station.clone()).or_insert_with(|| StationState {
+            observation,
+            advanced_at: now,
+            reporting: true,
+        });
+
+        if observation > state.observation {
+            if !state.reporting {
+                tracing::info!(message = "station resumed reporting", station_id = %o.station, observation = %observation);
+            }
+
+            state.observation = observation;
+            state.advanced_at = now;
+            state.reporting = true;
+            changed = true;
+        } else if state.reporting && (now - state.advanced_at).to_std().unwrap_or_default() >= self.threshold {
+            tracing::warn!(
+                message = "station observations have not advanced",
+                station_id = %o.station,
+                last_observation = %state.observation,
+                since = %state.advanced_at,
+            );
+            state.reporting = false;
+            changed = true;
+        }
+
+        let labels = Labels {
+            station: label_value(&o.label),
+        };
+        self.reporting.get_or_create(&labels).set(i64::from(state.reporting));
+        changed
+    }
+}
+
+#[async_trait]
+impl OutputSink for ReportingSink {
+    fn name(&self) -> &'static str {
+        "reporting"
+    }
+
+    async fn on_observations(&self, batch: &[StationObservation]) {
+        let now = self.clock.now_wall();
+        let mut states = self.states.lock().unwrap();
+        for o in batch {
+            if self.update(&mut states, o, now) {
+                self.changed.store(true, Ordering::Release);
+            }
+        }
+    }
+
+    async fn on_cycle_end(&self) {
+        let path = match &self.state_file {
+            Some(p) if self.changed.swap(false, Ordering::AcqRel) => p.clone(),
+            _ => return,
+        };
+
+        // Writing the file blocks so it's done off of the async runtime. Waiting for it means
+        // there's never more than one write to the file at a time. Failed writes are tried
+        // again at the end of the next cycle.
+        let states = self.states.lock().unwrap().clone();
+        let saved = tokio::task::spawn_blocking(move || save_state(&path, &states))
+            .await
+            .unwrap_or(false);
+        if !saved {
+            self.changed.store(true, Ordering::Release);
+        }
+    }
+
+    async fn on_station_stale(&self, station: &str, label: &str) {
+        // The station starts over as if it were new if it recovers, and stations that never
+        // do aren't kept in the state file forever.
+        if self.states.lock().unwrap().remove(station).is_some() {
+            self.changed.store(true, Ordering::Release);
+        }
+
+        let labels = Labels {
+            station: label_value(label),
+        };
+        self.reporting.remove(&labels);
+    }
+}
+
+/// Load persisted state, logging and ignoring any problems since the state can be rebuilt.
+fn load_state(path: &Path) -> HashMap<String, StationState> {
+    match fs::read(path) {
+        Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|e| {
+            tracing::warn!(message = "ignoring invalid state file", path = %path.display(), error = %e);
+            HashMap::new()
+        }),
+        Err(e) => {
+            tracing::debug!(message = "not loading state file", path = %path.display(), error = %e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Persist state by writing to a temporary file and renaming it so that a partially
+/// written file is never loaded. Returns false, after logging, if it couldn't be written.
+fn save_state(path: &Path, states: &HashMap<String, StationState>) -> bool {
+    let tmp = path.with_extension("tmp");
+    let res = serde_json::to_vec(states)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| fs::write(&tmp, bytes).map_err(|e| e.to_string()))
+        .and_then(|_| fs::rename(&tmp, path).map_err(|e| e.to_string()));
+
+    if let Err(e) = &res {
+        tracing::warn!(message = "unable to write state file", path = %path.display(), error = %e);
+    }
+
+    res.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use chrono::TimeZone;
+    use prometheus_client::encoding::text;
+
+    const KBOS: &str = "https://api.weather.gov/stations/KBOS";
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 10, 21, 15, 0, 0).unwrap()
+    }
+
+    /// KBOS fixture observation, changed to have been made at the given time.
+    fn observation(time: DateTime<Utc>) -> StationObservation {
+        let mut json: serde_json::Value = serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap();
+        json["properties"]["timestamp"] = time.to_rfc3339().into();
+        StationObservation {
+            station: "KBOS".to_owned(),
+            label: KBOS.to_owned(),
+            observation: serde_json::from_value(json).unwrap(),
+            changed: true,
+        }
+    }
+
+    fn reporting_sink(config: &ReportingConfig, clock: Arc<ManualClock>) -> (ReportingSink, Registry) {
+        let mut reg = Registry::default();
+        let sink = ReportingSink::new(&mut reg, config).with_clock(clock);
+        (sink, reg)
+    }
+
+    fn reporting(reg: &Registry) -> Option<i64> {
+        let mut out = String::new();
+        text::encode(&mut out, reg).unwrap();
+        let prefix = format!("nws_station_reporting{{station=\"{}\"}} ", KBOS);
+        out.lines()
+            .find_map(|l| l.strip_prefix(&prefix))
+            .map(|v| v.parse().unwrap())
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("nws_exporter_{}_{}.json", name, std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn test_flatline() {
+        let clock = Arc::new(ManualClock::new(start()));
+        let config = ReportingConfig {
+            flatline_secs: 3 * 3600,
+            state_file: None,
+        };
+        let (sink, reg) = reporting_sink(&config, clock.clone());
+        assert_eq!(None, reporting(&reg));
+
+        // The same observation is returned every time after the station stops reporting
+        let batch = vec![observation(start())];
+        for _ in 0..3 {
+            sink.on_observations(&batch).await;
+            assert_eq!(Some(1), reporting(&reg));
+            clock.advance(Duration::from_secs(3600) - Duration::from_secs(1));
+        }
+
+        clock.advance(Duration::from_secs(3));
+        sink.on_observations(&batch).await;
+        assert_eq!(Some(0), reporting(&reg));
+        sink.on_observations(&batch).await;
+        assert_eq!(Some(0), reporting(&reg));
+
+        // Any newer observation means it's reporting again
+        sink.on_observations(&[observation(start() + chrono::Duration::hours(3))])
+            .await;
+        assert_eq!(Some(1), reporting(&reg));
+    }
+
+    #[tokio::test]
+    async fn test_flatline_advancing() {
+        let clock = Arc::new(ManualClock::new(start()));
+        let config = ReportingConfig {
+            flatline_secs: 3600,
+            state_file: None,
+        };
+        let (sink, reg) = reporting_sink(&config, clock.clone());
+
+        for i in 0..5 {
+            sink.on_observations(&[observation(start() + chrono::Duration::hours(i))])
+                .await;
+            assert_eq!(Some(1), reporting(&reg));
+            clock.advance(Duration::from_secs(3600));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_state_file() {
+        let path = temp_path("reporting_state");
+        let clock = Arc::new(ManualClock::new(start()));
+        let config = ReportingConfig {
+            flatline_secs: 3600,
+            state_file: Some(path.clone()),
+        };
+
+        let (sink, _reg) = reporting_sink(&config, clock.clone());
+        sink.on_observations(&[observation(start())]).await;
+        sink.on_cycle_end().await;
+        assert!(path.exists());
+
+        // After a restart, the station is still known to have been stuck since before it
+        clock.advance(Duration::from_secs(3600));
+        let (restarted, reg) = reporting_sink(&config, clock.clone());
+        restarted.on_observations(&[observation(start())]).await;
+        assert_eq!(Some(0), reporting(&reg));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_state_file_invalid() {
+        let path = temp_path("reporting_invalid");
+        fs::write(&path, "not json").unwrap();
+        let clock = Arc::new(ManualClock::new(start()));
+        let config = ReportingConfig {
+            flatline_secs: 3600,
+            state_file: Some(path.clone()),
+        };
+
+        // Invalid state is ignored and replaced
+        let (sink, reg) = reporting_sink(&config, clock.clone());
+        sink.on_observations(&[observation(start())]).await;
+        sink.on_cycle_end().await;
+        assert_eq!(Some(1), reporting(&reg));
+        assert_eq!(1, load_state(&path).len());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_state_file_only_written_on_change() {
+        let path = temp_path("reporting_unchanged");
+        let clock = Arc::new(ManualClock::new(start()));
+        let config = ReportingConfig {
+            flatline_secs: 3600,
+            state_file: Some(path.clone()),
+        };
+        let (sink, _reg) = reporting_sink(&config, clock.clone());
+
+        // Nothing is written before there's any state
+        sink.on_cycle_end().await;
+        assert!(!path.exists());
+
+        sink.on_observations(&[observation(start())]).await;
+        sink.on_cycle_end().await;
+        assert!(path.exists());
+
+        // The same observation again doesn't change anything
+        fs::remove_file(&path).unwrap();
+        clock.advance(Duration::from_secs(60));
+        sink.on_observations(&[observation(start())]).await;
+        sink.on_cycle_end().await;
+        assert!(!path.exists());
+
+        // Stopping reporting and newer observations do
+        clock.advance(Duration::from_secs(3600));
+        sink.on_observations(&[observation(start())]).await;
+        sink.on_cycle_end().await;
+        assert!(!load_state(&path)["KBOS"].reporting);
+
+        fs::remove_file(&path).unwrap();
+        sink.on_observations(&[observation(start() + chrono::Duration::hours(1))])
+            .await;
+        sink.on_cycle_end().await;
+        assert!(load_state(&path)["KBOS"].reporting);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_station_stale() {
+        let path = temp_path("reporting_stale");
+        let clock = Arc::new(ManualClock::new(start()));
+        let config = ReportingConfig {
+            flatline_secs: 3600,
+            state_file: Some(path.clone()),
+        };
+        let (sink, reg) = reporting_sink(&config, clock.clone());
+        sink.on_observations(&[observation(start())]).await;
+        sink.on_cycle_end().await;
+        assert_eq!(1, load_state(&path).len());
+
+        // The series and persisted state are removed
+        sink.on_station_stale("KBOS", KBOS).await;
+        sink.on_cycle_end().await;
+        assert_eq!(None, reporting(&reg));
+        assert!(load_state(&path).is_empty());
+
+        // A station that recovers starts over instead of being flatlined right away
+        clock.advance(Duration::from_secs(2 * 3600));
+        sink.on_observations(&[observation(start())]).await;
+        assert_eq!(Some(1), reporting(&reg));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::client::Observation;
+use crate::config::ColdThresholds;
+use crate::derive::{celsius, kilometers_per_hour};
+use std::fmt;
+
+/// Heat-related risk level based on the NWS heat index thresholds.
+///
+/// See https://www.weather.gov/ama/heatindex for the thresholds and the effects of
+/// prolonged exposure at each level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeatRisk {
+    Caution,
+    ExtremeCaution,
+    Danger,
+    ExtremeDanger,
+}
+
+impl HeatRisk {
+    pub const ALL: [Self; 4] = [Self::Caution, Self::ExtremeCaution, Self::Danger, Self::ExtremeDanger];
+
+    /// Lower bound of each level, in degrees celsius (80, 90, 103, and 125 fahrenheit).
+    const THRESHOLDS: [(f64, Self); 4] = [
+        (51.7, Self::ExtremeDanger),
+        (39.4, Self::Danger),
+        (32.2, Self::ExtremeCaution),
+        (26.7, Self::Caution),
+    ];
+
+    /// Get the risk level for a heat index in degrees celsius or `None` if the heat index is
+    /// below the lowest level.
+    pub fn from_heat_index(celsius: f64) -> Option<Self> {
+        Self::THRESHOLDS
+            .iter()
+            .find(|(min, _)| celsius >= *min)
+            .map(|(_, level)| *level)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Caution => "caution",
+            Self::ExtremeCaution => "extreme_caution",
+            Self::Danger => "danger",
+            Self::ExtremeDanger => "extreme_danger",
+        }
+    }
+}
+
+impl fmt::Display for HeatRisk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Cold-related risk level based on wind chill advisory and warning thresholds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColdRisk {
+    Advisory,
+    Warning,
+}
+
+impl ColdRisk {
+    pub const ALL: [Self; 2] = [Self::Advisory, Self::Warning];
+
+    /// Wind speed below which conditions are considered calm, in kilometers per hour (3 mph).
+    const CALM_KMH: f64 = 4.8;
+
+    /// Get the risk level for a wind chill in degrees celsius or `None` if the wind chill
+    /// is warmer than the advisory threshold.
+    pub fn from_wind_chill(celsius: f64, thresholds: &ColdThresholds) -> Option<Self> {
+        if celsius <= thresholds.warning {
+            Some(Self::Warning)
+        } else if celsius <= thresholds.advisory {
+            Some(Self::Advisory)
+        } else {
+            None
+        }
+    }
+
+    /// Get the risk level for an observation using the wind chill if available or the
+    /// temperature if the wind is calm, since wind chill isn't reported without wind.
+    pub fn from_observation(obs: &Observation, thresholds: &ColdThresholds) -> Option<Self> {
+        let props = &obs.properties;
+        let calm = kilometers_per_hour(&props.wind_speed)
+            .map(|v| v < Self::CALM_KMH)
+            .unwrap_or(false);
+        celsius(&props.wind_chill)
+            .or_else(|| celsius(&props.temperature).filter(|_| calm))
+            .and_then(|v| Self::from_wind_chill(v, thresholds))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Advisory => "advisory",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+impl fmt::Display for ColdRisk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heat_risk_thresholds() {
+        for (heat_index, expected) in [
+            (f64::NAN, None),
+            (-10.0, None),
+            (26.6, None),
+            (26.7, Some(HeatRisk::Caution)),
+            (32.1, Some(HeatRisk::Caution)),
+            (32.2, Some(HeatRisk::ExtremeCaution)),
+            (39.3, Some(HeatRisk::ExtremeCaution)),
+            (39.4, Some(HeatRisk::Danger)),
+            (51.6, Some(HeatRisk::Danger)),
+            (51.7, Some(HeatRisk::ExtremeDanger)),
+            (70.0, Some(HeatRisk::ExtremeDanger)),
+        ] {
+            assert_eq!(
+                expected,
+                HeatRisk::from_heat_index(heat_index),
+                "heat index {}",
+                heat_index
+            );
+        }
+    }
+
+    #[test]
+    fn test_heat_risk_names() {
+        let names: Vec<String> = HeatRisk::ALL.iter().map(HeatRisk::to_string).collect();
+        assert_eq!(vec!["caution", "extreme_caution", "danger", "extreme_danger"], names);
+    }
+
+    fn observation(temperature: f64, wind_speed: f64, wind_chill: Option<f64>) -> Observation {
+        let mut json: serde_json::Value = serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap();
+        let props = &mut json["properties"];
+        props["temperature"]["value"] = temperature.into();
+        props["windSpeed"]["value"] = wind_speed.into();
+        props["windChill"] = serde_json::json!({"unitCode": "wmoUnit:degC", "value": wind_chill});
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_cold_risk_thresholds() {
+        let thresholds = ColdThresholds::default();
+        for (wind_chill, expected) in [
+            (f64::NAN, None),
+            (0.0, None),
+            (-28.8, None),
+            (-28.9, Some(ColdRisk::Advisory)),
+            (-37.1, Some(ColdRisk::Advisory)),
+            (-37.2, Some(ColdRisk::Warning)),
+            (-50.0, Some(ColdRisk::Warning)),
+        ] {
+            assert_eq!(
+                expected,
+                ColdRisk::from_wind_chill(wind_chill, &thresholds),
+                "wind chill {}",
+                wind_chill
+            );
+        }
+
+        let northern = ColdThresholds {
+            advisory: -34.4,
+            warning: -40.0,
+        };
+        assert_eq!(None, ColdRisk::from_wind_chill(-30.0, &northern));
+        assert_eq!(Some(ColdRisk::Advisory), ColdRisk::from_wind_chill(-38.0, &northern));
+        assert_eq!(Some(ColdRisk::Warning), ColdRisk::from_wind_chill(-40.0, &northern));
+    }
+
+    #[test]
+    fn test_cold_risk_observation() {
+        let thresholds = ColdThresholds::default();
+
+        // Wind chill is used when it's reported
+        let obs = observation(-20.0, 30.0, Some(-32.0));
+        assert_eq!(Some(ColdRisk::Advisory), ColdRisk::from_observation(&obs, &thresholds));
+
+        // Temperature is used when it's calm since there's no wind chill without wind
+        let obs = observation(-38.0, 0.0, None);
+        assert_eq!(Some(ColdRisk::Warning), ColdRisk::from_observation(&obs, &thresholds));
+
+        // But not when it's windy and wind chill is missing
+        let obs = observation(-38.0, 30.0, None);
+        assert_eq!(None, ColdRisk::from_observation(&obs, &thresholds));
+    }
+}
@@ -0,0 +1,154 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::registry::Registry;
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+/// Holder for metrics about the tokio runtime the exporter runs on.
+///
+/// All metrics are created and registered upon call to `RuntimeMetrics::new()`, share the
+/// prefix "nws_runtime_", and are updated periodically by `RuntimeMetrics::run()`. Building
+/// with the `unstable-runtime-metrics` feature and `RUSTFLAGS="--cfg tokio_unstable"` adds
+/// metrics that rely on unstable tokio APIs.
+#[derive(Debug)]
+pub struct RuntimeMetrics {
+    handle: Handle,
+    workers: Gauge,
+    alive_tasks: Gauge,
+    global_queue_depth: Gauge,
+    #[cfg(all(feature = "unstable-runtime-metrics", tokio_unstable))]
+    unstable: UnstableMetrics,
+}
+
+impl RuntimeMetrics {
+    /// Create a new `RuntimeMetrics` for the runtime of `handle` and register each metric
+    /// with the provided `Registry`.
+    pub fn new(reg: &mut Registry, handle: Handle) -> Self {
+        let workers = Gauge::default();
+        let alive_tasks = Gauge::default();
+        let global_queue_depth = Gauge::default();
+
+        reg.register(
+            "nws_runtime_workers",
+            "Number of worker threads used by the runtime",
+            workers.clone(),
+        );
+        reg.register(
+            "nws_runtime_alive_tasks",
+            "Number of tasks currently alive in the runtime",
+            alive_tasks.clone(),
+        );
+        reg.register(
+            "nws_runtime_global_queue_depth",
+            "Number of tasks currently scheduled in the global queue of the runtime",
+            global_queue_depth.clone(),
+        );
+
+        Self {
+            handle,
+            workers,
+            alive_tasks,
+            global_queue_depth,
+            #[cfg(all(feature = "unstable-runtime-metrics", tokio_unstable))]
+            unstable: UnstableMetrics::new(reg),
+        }
+    }
+
+    /// Update each metric from the current state of the runtime.
+    pub fn update(&self) {
+        let metrics = self.handle.metrics();
+        self.workers.set(metrics.num_workers() as i64);
+        self.alive_tasks.set(metrics.num_alive_tasks() as i64);
+        self.global_queue_depth.set(metrics.global_queue_depth() as i64);
+
+        #[cfg(all(feature = "unstable-runtime-metrics", tokio_unstable))]
+        self.unstable.update(&metrics);
+    }
+
+    /// Update each metric at the given interval forever.
+    pub async fn run(self, interval: Duration) -> ! {
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+            self.update();
+        }
+    }
+}
+
+/// Metrics that rely on unstable tokio APIs.
+#[cfg(all(feature = "unstable-runtime-metrics", tokio_unstable))]
+#[derive(Debug)]
+struct UnstableMetrics {
+    blocking_threads: Gauge,
+    idle_blocking_threads: Gauge,
+    blocking_queue_depth: Gauge,
+    local_queue_depth: Gauge,
+}
+
+#[cfg(all(feature = "unstable-runtime-metrics", tokio_unstable))]
+impl UnstableMetrics {
+    fn new(reg: &mut Registry) -> Self {
+        let blocking_threads = Gauge::default();
+        let idle_blocking_threads = Gauge::default();
+        let blocking_queue_depth = Gauge::default();
+        let local_queue_depth = Gauge::default();
+
+        reg.register(
+            "nws_runtime_blocking_threads",
+            "Number of additional threads spawned by the runtime for blocking operations",
+            blocking_threads.clone(),
+        );
+        reg.register(
+            "nws_runtime_idle_blocking_threads",
+            "Number of additional threads for blocking operations that are idle",
+            idle_blocking_threads.clone(),
+        );
+        reg.register(
+            "nws_runtime_blocking_queue_depth",
+            "Number of tasks currently scheduled in the blocking thread pool",
+            blocking_queue_depth.clone(),
+        );
+        reg.register(
+            "nws_runtime_local_queue_depth",
+            "Number of tasks currently scheduled in the local queues of all worker threads",
+            local_queue_depth.clone(),
+        );
+
+        Self {
+            blocking_threads,
+            idle_blocking_threads,
+            blocking_queue_depth,
+            local_queue_depth,
+        }
+    }
+
+    fn update(&self, metrics: &tokio::runtime::RuntimeMetrics) {
+        let local: usize = (0..metrics.num_workers())
+            .map(|w| metrics.worker_local_queue_depth(w))
+            .sum();
+
+        self.blocking_threads.set(metrics.num_blocking_threads() as i64);
+        self.idle_blocking_threads
+            .set(metrics.num_idle_blocking_threads() as i64);
+        self.blocking_queue_depth.set(metrics.blocking_queue_depth() as i64);
+        self.local_queue_depth.set(local as i64);
+    }
+}
@@ -0,0 +1,117 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+/// Maximum length of a label value in bytes before escaping. Longer values are truncated.
+pub const MAX_LABEL_VALUE_BYTES: usize = 256;
+
+/// Escape a value from external data for use as a label value in the Prometheus and
+/// OpenMetrics text formats.
+///
+/// The prometheus-client encoder writes label values as-is so quotes, backslashes, and
+/// newlines must be escaped before a value is used in a label set. Other control characters
+/// are replaced with spaces since they have no escape sequence. Values longer than
+/// `MAX_LABEL_VALUE_BYTES` are truncated at a character boundary. Values written to the
+/// JSON endpoints don't need this since they are escaped by the JSON serializer.
+pub fn label_value(value: &str) -> String {
+    let truncated = truncate(value, MAX_LABEL_VALUE_BYTES);
+    let mut out = String::with_capacity(truncated.len());
+    for c in truncated.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => out.push(' '),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Truncate a string to at most `max` bytes without splitting a character.
+fn truncate(value: &str, max: usize) -> &str {
+    if value.len() <= max {
+        return value;
+    }
+
+    let mut end = max;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &value[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_value_escapes() {
+        for (value, expected) in [
+            (
+                "Boston, Logan International Airport",
+                "Boston, Logan International Airport",
+            ),
+            (r#"say "hi""#, r#"say \"hi\""#),
+            (r"C:\weather", r"C:\\weather"),
+            ("line one\nline two", r"line one\nline two"),
+            ("tab\tcarriage\rbell\u{7}", "tab carriage bell "),
+            (r#"\"}\n"#, r#"\\\"}\\n"#),
+            ("🌧️ Rain ⛈", "🌧️ Rain ⛈"),
+            ("", ""),
+        ] {
+            assert_eq!(expected, label_value(value), "escaping {:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_label_value_no_raw_specials() {
+        // Escaped values never contain anything that could end the label value or the line
+        let hostile = "a\"b\\c\nd\r\u{0}e\u{1b}[31m\"}\n# TYPE evil gauge\nevil 1";
+        let escaped = label_value(hostile);
+        assert!(!escaped.contains('\n'));
+        assert!(!escaped.contains('\r'));
+        assert!(!escaped.chars().any(char::is_control));
+
+        let mut chars = escaped.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => assert!(matches!(chars.next(), Some('\\' | '"' | 'n'))),
+                '"' => panic!("unescaped quote in {}", escaped),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_label_value_truncated() {
+        let long = "x".repeat(MAX_LABEL_VALUE_BYTES * 4);
+        assert_eq!(MAX_LABEL_VALUE_BYTES, label_value(&long).len());
+
+        // Multi-byte characters aren't split
+        let emoji = "🌧".repeat(MAX_LABEL_VALUE_BYTES);
+        let truncated = label_value(&emoji);
+        assert!(truncated.len() <= MAX_LABEL_VALUE_BYTES);
+        assert_eq!(MAX_LABEL_VALUE_BYTES / 4, truncated.chars().count());
+
+        // Truncation happens before escaping so escapes can't be cut in half
+        let quotes = "\"".repeat(MAX_LABEL_VALUE_BYTES + 1);
+        assert_eq!("\\\"".repeat(MAX_LABEL_VALUE_BYTES), label_value(&quotes));
+    }
+}
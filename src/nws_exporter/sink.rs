@@ -0,0 +1,214 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::client::{Alert, Forecast, Gridpoint, Observation, Station};
+use crate::config::StationSource;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Observation successfully fetched for a configured station during a refresh cycle.
+///
+/// `station` is the ID of the station as configured and `label` is the full URL or the
+/// identifier of the station (from its metadata, depending on the `StationLabelFormat`) used
+/// to identify it in metrics. Observations are always
+/// attributed to the station that was requested, even if the observation itself claims
+/// to be from a different station. `changed` is false when the observation is the same one
+/// that was fetched for the station during the previous cycle.
+#[derive(Debug, Clone)]
+pub struct StationObservation {
+    pub station: String,
+    pub label: String,
+    pub observation: Observation,
+    pub changed: bool,
+}
+
+/// Forecast successfully fetched for the location of a configured station.
+///
+/// `station` and `label` are the same as for a `StationObservation` of the station. `hourly`
+/// and `gridpoint` are the hourly forecast and raw forecast data for the same location, if
+/// they could be fetched, and `time` is when the forecasts were fetched.
+#[derive(Debug, Clone)]
+pub struct StationForecast {
+    pub station: String,
+    pub label: String,
+    pub forecast: Forecast,
+    pub hourly: Option<Forecast>,
+    pub gridpoint: Option<Gridpoint>,
+    pub time: DateTime<Utc>,
+}
+
+/// Area that alerts are fetched for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlertArea {
+    /// Configured forecast or county zone, by its ID and name. The name is empty if it
+    /// couldn't be looked up.
+    Zone(String, String),
+    /// Location of a configured station, by the ID of the station as configured and the
+    /// label used to identify it in metrics (the same as for a `StationObservation`).
+    Station(String, String),
+}
+
+impl AlertArea {
+    /// The zone ID or the ID of the station as configured.
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Zone(zone, _) => zone,
+            Self::Station(id, _) => id,
+        }
+    }
+}
+
+impl fmt::Display for AlertArea {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Zone(zone, _) => write!(f, "zone {}", zone),
+            Self::Station(id, _) => write!(f, "station {}", id),
+        }
+    }
+}
+
+/// Active alerts successfully fetched for a configured alert zone or station.
+///
+/// `alerts` includes every alert returned by the API, even those that have expired or been
+/// cancelled, and `time` is when the alerts were fetched.
+#[derive(Debug, Clone)]
+pub struct AreaAlerts {
+    pub area: AlertArea,
+    pub alerts: Vec<Alert>,
+    pub time: DateTime<Utc>,
+}
+
+/// Destination for station metadata and observations fetched by the exporter.
+///
+/// Sinks are driven by the update loop: `on_station` is called for each station when its
+/// metadata is fetched, `on_observations` is called once per refresh cycle with every
+/// observation fetched during that cycle, and `on_cycle_end` is called after that.
+/// `on_station_stale` is called during a cycle when fetching observations for a station has
+/// failed too many times in a row. If forecasts are enabled, `on_forecasts` is called with
+/// every forecast fetched each time they're refreshed, independent of observations. The same
+/// goes for `on_alerts` if there are alert zones or alerts are enabled for stations. If
+/// backfilling is enabled, `on_backfill` is called once per station at startup, after its
+/// metadata and before any observations.
+#[async_trait]
+pub trait OutputSink: Send + Sync {
+    /// Short name of this sink for logging.
+    fn name(&self) -> &'static str;
+
+    /// Handle metadata for a configured station. `label` identifies the station in metrics
+    /// and is the same label used for its observations.
+    async fn on_station(&self, _station: &Station, _label: &str, _source: StationSource) {}
+
+    /// Handle all observations fetched during a refresh cycle. Stations that could not
+    /// be fetched are not included.
+    async fn on_observations(&self, batch: &[StationObservation]);
+
+    /// Handle observations made before the exporter started for a single station, oldest
+    /// first. These are only meant to restore state kept between observations and do not
+    /// include the most recent observation, which is part of the first refresh cycle.
+    async fn on_backfill(&self, _batch: &[StationObservation]) {}
+
+    /// Handle the end of a refresh cycle.
+    async fn on_cycle_end(&self) {}
+
+    /// Handle all forecasts fetched during a forecast refresh. Stations that forecasts could
+    /// not be fetched for are not included.
+    async fn on_forecasts(&self, _batch: &[StationForecast]) {}
+
+    /// Handle alerts for all zones and stations fetched during an alert refresh. Zones and
+    /// stations that alerts could not be fetched for are not included.
+    async fn on_alerts(&self, _batch: &[AreaAlerts]) {}
+
+    /// Handle a station that observations can no longer be fetched for by removing anything
+    /// exported for it. `station` is the ID of the station as configured and `label` is the
+    /// same label used for its observations. The station may recover and be included in later
+    /// cycles.
+    async fn on_station_stale(&self, _station: &str, _label: &str) {}
+}
+
+/// Sink that discards everything.
+#[derive(Debug, Default)]
+pub struct NoopSink;
+
+#[async_trait]
+impl OutputSink for NoopSink {
+    fn name(&self) -> &'static str {
+        "noop"
+    }
+
+    async fn on_observations(&self, _batch: &[StationObservation]) {}
+}
+
+/// Sink that keeps everything it is given, for testing.
+#[derive(Debug, Default)]
+pub struct RecordingSink {
+    stations: Mutex<Vec<(String, StationSource)>>,
+    batches: Mutex<Vec<Vec<StationObservation>>>,
+    alerts: Mutex<Vec<Vec<AreaAlerts>>>,
+    cycles: AtomicUsize,
+}
+
+impl RecordingSink {
+    /// IDs and sources of stations seen so far, in order.
+    pub fn stations(&self) -> Vec<(String, StationSource)> {
+        self.stations.lock().unwrap().clone()
+    }
+
+    /// Each batch of observations seen so far, in order.
+    pub fn batches(&self) -> Vec<Vec<StationObservation>> {
+        self.batches.lock().unwrap().clone()
+    }
+
+    /// Each batch of alerts seen so far, in order.
+    pub fn alerts(&self) -> Vec<Vec<AreaAlerts>> {
+        self.alerts.lock().unwrap().clone()
+    }
+
+    /// Number of completed refresh cycles.
+    pub fn cycles(&self) -> usize {
+        self.cycles.load(Ordering::Acquire)
+    }
+}
+
+#[async_trait]
+impl OutputSink for RecordingSink {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    async fn on_station(&self, station: &Station, _label: &str, source: StationSource) {
+        self.stations
+            .lock()
+            .unwrap()
+            .push((station.properties.station_identifier.clone(), source));
+    }
+
+    async fn on_observations(&self, batch: &[StationObservation]) {
+        self.batches.lock().unwrap().push(batch.to_vec());
+    }
+
+    async fn on_cycle_end(&self) {
+        self.cycles.fetch_add(1, Ordering::AcqRel);
+    }
+
+    async fn on_alerts(&self, batch: &[AreaAlerts]) {
+        self.alerts.lock().unwrap().push(batch.to_vec());
+    }
+}
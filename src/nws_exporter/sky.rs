@@ -0,0 +1,122 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::client::CloudLayer;
+use crate::units::{convert, Unit};
+
+/// Get the number of oktas (eighths of the sky) covered for a METAR cloud amount or `None`
+/// if the amount isn't recognized.
+///
+/// Amounts that cover a range of oktas (e.g. `BKN` is 5-7) are mapped to a single value in
+/// that range. Obscured skies (`VV`, vertical visibility) are 9 as in WMO code table 2700.
+pub fn oktas(amount: &str) -> Option<u8> {
+    match amount {
+        "CLR" | "SKC" => Some(0),
+        "FEW" => Some(2),
+        "SCT" => Some(4),
+        "BKN" => Some(6),
+        "OVC" => Some(8),
+        "VV" => Some(9),
+        _ => None,
+    }
+}
+
+/// Get the sky cover in oktas for the cloud layer with the highest coverage or `None` if
+/// there are no layers with a recognized amount. Unrecognized amounts are ignored.
+pub fn sky_cover(layers: &[CloudLayer]) -> Option<u8> {
+    layers
+        .iter()
+        .filter_map(|l| {
+            let oktas = oktas(&l.amount);
+            if oktas.is_none() {
+                tracing::debug!(message = "ignoring unknown cloud layer amount", amount = %l.amount);
+            }
+            oktas
+        })
+        .max()
+}
+
+/// Get the number of cloud layers, not counting layers that report clear skies.
+pub fn cloud_layers(layers: &[CloudLayer]) -> usize {
+    layers.iter().filter(|l| oktas(&l.amount) != Some(0)).count()
+}
+
+/// Get the height of the base of the lowest broken (`BKN`), overcast (`OVC`), or obscured
+/// (`VV`) layer in meters or `None` if there isn't one. This is the definition of ceiling
+/// used for aviation.
+pub fn ceiling(layers: &[CloudLayer]) -> Option<f64> {
+    layers
+        .iter()
+        .filter(|l| matches!(l.amount.as_str(), "BKN" | "OVC" | "VV"))
+        .filter_map(|l| convert(&l.base, Unit::Meters))
+        .reduce(f64::min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Measurement;
+
+    fn layer(amount: &str, base: f64) -> CloudLayer {
+        CloudLayer {
+            base: Measurement::new("wmoUnit:m", base),
+            amount: amount.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_oktas() {
+        for (amount, expected) in [
+            ("CLR", Some(0)),
+            ("SKC", Some(0)),
+            ("FEW", Some(2)),
+            ("SCT", Some(4)),
+            ("BKN", Some(6)),
+            ("OVC", Some(8)),
+            ("VV", Some(9)),
+            ("", None),
+            ("ovc", None),
+            ("NSC", None),
+        ] {
+            assert_eq!(expected, oktas(amount), "amount {:?}", amount);
+        }
+    }
+
+    #[test]
+    fn test_sky_cover() {
+        assert_eq!(None, sky_cover(&[]));
+        assert_eq!(Some(0), sky_cover(&[layer("CLR", 0.0)]));
+        assert_eq!(
+            Some(8),
+            sky_cover(&[layer("FEW", 900.0), layer("OVC", 1500.0), layer("SCT", 3000.0)])
+        );
+        // Unknown amounts are ignored rather than treated as clear or covered
+        assert_eq!(Some(4), sky_cover(&[layer("???", 900.0), layer("SCT", 1500.0)]));
+        assert_eq!(None, sky_cover(&[layer("???", 900.0)]));
+    }
+
+    #[test]
+    fn test_cloud_layers_and_ceiling() {
+        let layers = [layer("FEW", 900.0), layer("BKN", 3000.0), layer("OVC", 1500.0)];
+        assert_eq!(3, cloud_layers(&layers));
+        assert_eq!(Some(1500.0), ceiling(&layers));
+
+        assert_eq!(0, cloud_layers(&[layer("CLR", 0.0)]));
+        assert_eq!(None, ceiling(&[layer("FEW", 900.0), layer("SCT", 1500.0)]));
+    }
+}
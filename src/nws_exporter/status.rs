@@ -0,0 +1,161 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::StationConfig;
+use crate::sink::{OutputSink, StationObservation};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Time of the most recent successful observation fetch for each configured station.
+///
+/// This is shared between the update task, which records successes via `StatusSink`, and
+/// the HTTP server, which reports how fresh the exported data is.
+#[derive(Debug)]
+pub struct StationStatus {
+    stations: Vec<String>,
+    last_success: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl StationStatus {
+    /// Create a new `StationStatus` for the given stations, none of which have been fetched yet.
+    pub fn new(stations: &[StationConfig]) -> Self {
+        Self {
+            stations: stations.iter().map(|s| s.id.clone()).collect(),
+            last_success: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a successful observation fetch for a station at the given time.
+    pub fn record_success(&self, station: &str, at: DateTime<Utc>) {
+        self.last_success.lock().unwrap().insert(station.to_owned(), at);
+    }
+
+    /// Time of the last successful fetch for a station, if there has been one.
+    pub fn last_success(&self, station: &str) -> Option<DateTime<Utc>> {
+        self.last_success.lock().unwrap().get(station).copied()
+    }
+
+    /// Time of the most recent successful fetch across all stations, if there has been one.
+    pub fn last_update(&self) -> Option<DateTime<Utc>> {
+        self.last_success.lock().unwrap().values().max().copied()
+    }
+
+    /// Time of the last successful fetch of the station that was updated least recently.
+    ///
+    /// This is `None` until every configured station has been fetched successfully at least
+    /// once since a station that has never been fetched is staler than any that has.
+    pub fn oldest_station_update(&self) -> Option<DateTime<Utc>> {
+        let last_success = self.last_success.lock().unwrap();
+        let mut oldest: Option<DateTime<Utc>> = None;
+        for s in self.stations.iter() {
+            let t = *last_success.get(s)?;
+            oldest = Some(oldest.map_or(t, |o| o.min(t)));
+        }
+
+        oldest
+    }
+}
+
+/// Sink that records successful observation fetches in a shared `StationStatus`.
+#[derive(Debug)]
+pub struct StatusSink {
+    status: Arc<StationStatus>,
+    clock: Arc<dyn Clock>,
+}
+
+impl StatusSink {
+    pub fn new(status: Arc<StationStatus>) -> Self {
+        Self {
+            status,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Use the provided `Clock` for the time of each successful fetch.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+#[async_trait]
+impl OutputSink for StatusSink {
+    fn name(&self) -> &'static str {
+        "status"
+    }
+
+    async fn on_observations(&self, batch: &[StationObservation]) {
+        let now = self.clock.now_wall();
+        for o in batch {
+            self.status.record_success(&o.station, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::config::StationSource;
+    use chrono::TimeZone;
+    use std::time::Duration;
+
+    fn observation(station: &str) -> StationObservation {
+        StationObservation {
+            station: station.to_owned(),
+            label: format!("https://api.weather.gov/stations/{}", station),
+            observation: serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap(),
+            changed: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_sink() {
+        let start = Utc.with_ymd_and_hms(2023, 10, 21, 15, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(start));
+        let status = Arc::new(StationStatus::new(&[
+            StationConfig::new("KBOS", StationSource::Cli),
+            StationConfig::new("KJFK", StationSource::Cli),
+        ]));
+        let sink = StatusSink::new(status.clone()).with_clock(clock.clone());
+        assert_eq!(None, status.last_update());
+        assert_eq!(None, status.oldest_station_update());
+
+        sink.on_observations(&[observation("KBOS")]).await;
+        assert_eq!(Some(start), status.last_success("KBOS"));
+        assert_eq!(None, status.last_success("KJFK"));
+        assert_eq!(Some(start), status.last_update());
+        // KJFK has never been fetched so it's staler than anything
+        assert_eq!(None, status.oldest_station_update());
+
+        clock.advance(Duration::from_secs(300));
+        sink.on_observations(&[observation("KJFK")]).await;
+        let later = start + chrono::Duration::seconds(300);
+        assert_eq!(Some(later), status.last_update());
+        assert_eq!(Some(start), status.oldest_station_update());
+
+        clock.advance(Duration::from_secs(300));
+        sink.on_observations(&[observation("KBOS"), observation("KJFK")]).await;
+        let latest = start + chrono::Duration::seconds(600);
+        assert_eq!(Some(latest), status.last_update());
+        assert_eq!(Some(latest), status.oldest_station_update());
+    }
+}
@@ -0,0 +1,215 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+use crate::client::Measurement;
+use std::fmt;
+
+/// Canonical unit that measurements are converted to before being exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Celsius,
+    Meters,
+    Pascals,
+    KilometersPerHour,
+    Degrees,
+    Percent,
+}
+
+impl Unit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Celsius => "celsius",
+            Self::Meters => "meters",
+            Self::Pascals => "pascals",
+            Self::KilometersPerHour => "kilometers_per_hour",
+            Self::Degrees => "degrees",
+            Self::Percent => "percent",
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Supported unit codes without a namespace prefix, the canonical unit each can be converted to, and the scale and
+/// offset to apply (`value * scale + offset`) to convert to it. Every supported conversion
+/// is linear so this is enough to describe all of them.
+const CONVERSIONS: [(&str, Unit, f64, f64); 20] = [
+    ("degC", Unit::Celsius, 1.0, 0.0),
+    ("degF", Unit::Celsius, 5.0 / 9.0, -32.0 * 5.0 / 9.0),
+    ("K", Unit::Celsius, 1.0, -273.15),
+    ("m", Unit::Meters, 1.0, 0.0),
+    ("mm", Unit::Meters, 0.001, 0.0),
+    ("cm", Unit::Meters, 0.01, 0.0),
+    ("km", Unit::Meters, 1000.0, 0.0),
+    ("ft", Unit::Meters, 0.3048, 0.0),
+    ("in", Unit::Meters, 0.0254, 0.0),
+    ("mi", Unit::Meters, 1609.344, 0.0),
+    ("Pa", Unit::Pascals, 1.0, 0.0),
+    ("hPa", Unit::Pascals, 100.0, 0.0),
+    ("mbar", Unit::Pascals, 100.0, 0.0),
+    ("kPa", Unit::Pascals, 1000.0, 0.0),
+    ("km_h-1", Unit::KilometersPerHour, 1.0, 0.0),
+    ("m_s-1", Unit::KilometersPerHour, 3.6, 0.0),
+    ("kn", Unit::KilometersPerHour, 1.852, 0.0),
+    ("mi_h-1", Unit::KilometersPerHour, 1.609344, 0.0),
+    ("degree_(angle)", Unit::Degrees, 1.0, 0.0),
+    ("percent", Unit::Percent, 1.0, 0.0),
+];
+
+/// Get the value of a measurement converted to the given unit or `None` if the value is
+/// missing or the unit code of the measurement can't be converted to the given unit.
+pub fn convert(m: &Measurement, unit: Unit) -> Option<f64> {
+    let value = m.value?;
+    let conversion = CONVERSIONS
+        .iter()
+        .find(|(code, to, _, _)| *code == m.unit() && *to == unit);
+
+    match conversion {
+        Some((_, _, scale, offset)) => Some(value * scale + offset),
+        None => {
+            tracing::debug!(message = "unable to convert measurement", unit = %m.unit_code, to = %unit, value = value);
+            None
+        }
+    }
+}
+
+/// Return true if the unit code of the measurement can be converted to any canonical unit.
+pub fn is_supported(m: &Measurement) -> bool {
+    CONVERSIONS.iter().any(|(code, _, _, _)| *code == m.unit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_converted(expected: f64, unit_code: &str, value: f64, unit: Unit) {
+        let actual = convert(&Measurement::new(unit_code, value), unit).unwrap();
+        assert!(
+            (expected - actual).abs() < 1e-9,
+            "{} {}: expected {} got {}",
+            value,
+            unit_code,
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_convert_speed() {
+        assert_converted(36.0, "wmoUnit:km_h-1", 36.0, Unit::KilometersPerHour);
+        assert_converted(36.0, "wmoUnit:m_s-1", 10.0, Unit::KilometersPerHour);
+        assert_converted(18.52, "wmoUnit:kn", 10.0, Unit::KilometersPerHour);
+        assert_converted(16.09344, "wmoUnit:mi_h-1", 10.0, Unit::KilometersPerHour);
+    }
+
+    #[test]
+    fn test_convert_other() {
+        assert_converted(-40.0, "wmoUnit:degF", -40.0, Unit::Celsius);
+        assert_converted(0.0, "wmoUnit:K", 273.15, Unit::Celsius);
+        assert_converted(6.096, "wmoUnit:ft", 20.0, Unit::Meters);
+        assert_converted(101325.0, "wmoUnit:hPa", 1013.25, Unit::Pascals);
+        assert_converted(240.0, "wmoUnit:degree_(angle)", 240.0, Unit::Degrees);
+    }
+
+    #[test]
+    fn test_convert_prefixes() {
+        assert_converted(36.0, "unit:m_s-1", 10.0, Unit::KilometersPerHour);
+        assert_converted(36.0, "m_s-1", 10.0, Unit::KilometersPerHour);
+        for code in ["wmoUnit:degC", "unit:degC", "degC"] {
+            assert_converted(16.7, code, 16.7, Unit::Celsius);
+            assert!(is_supported(&Measurement::new(code, 16.7)), "{}", code);
+        }
+        for code in ["wmoUnit:degF", "unit:degF", "degF"] {
+            assert_converted(100.0, code, 212.0, Unit::Celsius);
+        }
+    }
+
+    #[test]
+    fn test_convert_unsupported() {
+        // Unknown units and units for a different kind of measurement can't be converted
+        assert_eq!(
+            None,
+            convert(
+                &Measurement::new("wmoUnit:furlong_fortnight-1", 10.0),
+                Unit::KilometersPerHour
+            )
+        );
+        assert_eq!(
+            None,
+            convert(&Measurement::new("wmoUnit:m", 10.0), Unit::KilometersPerHour)
+        );
+        assert_eq!(None, convert(&Measurement::absent(), Unit::KilometersPerHour));
+
+        assert!(is_supported(&Measurement::new("wmoUnit:kn", 10.0)));
+        assert!(!is_supported(&Measurement::new("wmoUnit:furlong_fortnight-1", 10.0)));
+    }
+
+    #[test]
+    fn test_convert_every_pair() {
+        let expected = [
+            ("degC", 20.0, Unit::Celsius, 20.0),
+            ("degF", 212.0, Unit::Celsius, 100.0),
+            ("K", 300.0, Unit::Celsius, 26.85),
+            ("m", 1500.0, Unit::Meters, 1500.0),
+            ("mm", 25.4, Unit::Meters, 0.0254),
+            ("cm", 150.0, Unit::Meters, 1.5),
+            ("km", 16.0, Unit::Meters, 16000.0),
+            ("ft", 1000.0, Unit::Meters, 304.8),
+            ("in", 12.0, Unit::Meters, 0.3048),
+            ("mi", 10.0, Unit::Meters, 16093.44),
+            ("Pa", 101420.0, Unit::Pascals, 101420.0),
+            ("hPa", 1014.2, Unit::Pascals, 101420.0),
+            ("mbar", 1014.2, Unit::Pascals, 101420.0),
+            ("kPa", 101.42, Unit::Pascals, 101420.0),
+            ("km_h-1", 22.224, Unit::KilometersPerHour, 22.224),
+            ("m_s-1", 5.0, Unit::KilometersPerHour, 18.0),
+            ("kn", 12.0, Unit::KilometersPerHour, 22.224),
+            ("mi_h-1", 25.0, Unit::KilometersPerHour, 40.2336),
+            ("degree_(angle)", 240.0, Unit::Degrees, 240.0),
+            ("percent", 49.4, Unit::Percent, 49.4),
+        ];
+        assert_eq!(CONVERSIONS.len(), expected.len());
+
+        let units = [
+            Unit::Celsius,
+            Unit::Meters,
+            Unit::Pascals,
+            Unit::KilometersPerHour,
+            Unit::Degrees,
+            Unit::Percent,
+        ];
+        for (code, value, unit, converted) in expected {
+            let unit_code = format!("wmoUnit:{}", code);
+            assert_converted(converted, &unit_code, value, unit);
+
+            // Measurements can only be converted to the kind of unit they measure
+            for other in units.iter().filter(|u| **u != unit) {
+                assert_eq!(
+                    None,
+                    convert(&Measurement::new(&unit_code, value), *other),
+                    "{} to {}",
+                    code,
+                    other
+                );
+            }
+        }
+    }
+}
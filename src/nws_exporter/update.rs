@@ -0,0 +1,2669 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::client::{Alert, ClientError, PointProperties, Station, Trigger, WeatherClient};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{StationConfig, StationLabelFormat, UpdateConfig};
+use crate::geo::{self, Geometry, Location};
+use crate::http::RefreshReceiver;
+use crate::metrics::ExporterMetrics;
+use crate::sink::{AlertArea, AreaAlerts, OutputSink, StationForecast, StationObservation};
+use crate::warn::{Warn, WarnOnce};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+use tracing::{Instrument, Level};
+
+/// Task for periodically updating forecast metrics for multiple stations.
+///
+/// Perform initialization of station metadata and periodically fetch forecasts for
+/// a list of stations, handing them to each configured output sink, until this exporter
+/// is stopped. Station metadata is also fetched again at a much lower frequency, and forecasts
+/// for the location of each station are fetched at their own interval if enabled, as are
+/// alerts for any alert zones or the location of each station.
+pub struct UpdateTask {
+    stations: Vec<StationConfig>,
+    labels: HashMap<String, String>,
+    failures: HashMap<String, u64>,
+    observations: HashMap<String, String>,
+    locations: HashMap<String, Location>,
+    points: HashMap<String, PointProperties>,
+    sinks: Vec<Box<dyn OutputSink>>,
+    metrics: ExporterMetrics,
+    warnings: WarnOnce,
+    client: Box<dyn WeatherClient>,
+    interval: Duration,
+    metadata_interval: Option<Duration>,
+    forecast_interval: Option<Duration>,
+    alert_interval: Option<Duration>,
+    alert_zones: Vec<String>,
+    zone_names: HashMap<String, String>,
+    station_alerts: bool,
+    backfill: Option<chrono::Duration>,
+    stale_after_failures: u64,
+    max_observation_age: Option<chrono::Duration>,
+    future_tolerance: chrono::Duration,
+    reject_future_observations: bool,
+    label_format: StationLabelFormat,
+    expose_observation_id: bool,
+    clock: Arc<dyn Clock>,
+}
+
+impl UpdateTask {
+    const FETCH_ERROR: &'static str = "fetch_observation";
+    const FORECAST_ERROR: &'static str = "fetch_forecast";
+    const HOURLY_FORECAST_ERROR: &'static str = "fetch_hourly_forecast";
+    const GRIDPOINT_ERROR: &'static str = "fetch_gridpoint";
+    const ALERTS_ERROR: &'static str = "fetch_alerts";
+    const ALERTS_NO_LOCATION: &'static str = "alerts_no_location";
+    const NO_LOCATION: &'static str = "no_location";
+    const STATION_MISMATCH: &'static str = "station_mismatch";
+    const FUTURE_OBSERVATION: &'static str = "future_observation";
+    const STALE_OBSERVATION: &'static str = "stale_observation";
+
+    /// Create a new `UpdateTask` for the given stations. Stations are passed separately from
+    /// the rest of the config since they may be resolved from another source, such as fixture
+    /// files, before the task is created.
+    pub fn new(
+        config: &UpdateConfig,
+        stations: Vec<StationConfig>,
+        sinks: Vec<Box<dyn OutputSink>>,
+        metrics: ExporterMetrics,
+        warnings: WarnOnce,
+        client: Box<dyn WeatherClient>,
+    ) -> Self {
+        metrics.stations_configured(stations.len());
+        Self {
+            stations,
+            labels: HashMap::new(),
+            failures: HashMap::new(),
+            observations: HashMap::new(),
+            locations: HashMap::new(),
+            points: HashMap::new(),
+            sinks,
+            metrics,
+            warnings,
+            client,
+            interval: config.interval(),
+            metadata_interval: config.metadata_interval(),
+            forecast_interval: config.forecast_interval(),
+            alert_interval: config.alert_interval(),
+            alert_zones: config.alert_zones.clone(),
+            zone_names: HashMap::new(),
+            station_alerts: config.station_alerts,
+            backfill: config.backfill(),
+            stale_after_failures: config.stale_after_failures,
+            max_observation_age: config.max_observation_age(),
+            future_tolerance: config.future_tolerance(),
+            reject_future_observations: config.reject_future_observations,
+            label_format: config.station_label,
+            expose_observation_id: config.expose_observation_id,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Use the provided `Clock` for the time of each successful fetch.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Hand everything fetched from now on to `sink` as well. Sinks added after `initialize()`
+    /// only get station metadata the next time it's refreshed.
+    pub fn add_sink(&mut self, sink: Box<dyn OutputSink>) {
+        self.sinks.push(sink);
+    }
+
+    /// Stations being updated. Once `initialize()` has been called these are identified by
+    /// the ID the API uses for each station and stations configured more than once are only
+    /// included once.
+    pub fn stations(&self) -> &[StationConfig] {
+        &self.stations
+    }
+
+    /// Pass station metadata to each sink or return an error if station or alert zone metadata
+    /// could not be fetched
+    ///
+    /// The ID of each station is replaced with the identifier from its metadata, if they're
+    /// different, so that stations are identified the same way the API identifies them. Stations
+    /// that turn out to be the same as a station earlier in the list are removed. Alert zones
+    /// are looked up to make sure they exist and to get their names.
+    pub async fn initialize(&mut self) -> Result<(), ClientError> {
+        let mut duplicates = Vec::new();
+        self.labels.clear();
+        for (i, s) in self.stations.iter_mut().enumerate() {
+            let station = self
+                .client
+                .station(&s.id, Trigger::Scheduled)
+                .instrument(tracing::span!(Level::DEBUG, "nws_station", trigger = %Trigger::Scheduled))
+                .await?;
+
+            let canonical = &station.properties.station_identifier;
+            if !canonical.is_empty() && *canonical != s.id {
+                tracing::info!(message = "using station identifier from the API", station_id = %s.id, canonical = %canonical);
+                s.id = canonical.clone();
+            }
+            if self.labels.contains_key(&s.id) {
+                tracing::info!(message = "ignoring duplicate station", station_id = %s.id);
+                duplicates.push(i);
+                continue;
+            }
+
+            let label = self.label_format.label(&station.properties.id);
+            for sink in self.sinks.iter() {
+                sink.on_station(&station, &label, s.source).await;
+            }
+            Self::update_location(&mut self.locations, &mut self.points, &s.id, &station);
+            self.labels.insert(s.id.clone(), label);
+        }
+
+        for i in duplicates.into_iter().rev() {
+            self.stations.remove(i);
+        }
+
+        self.metrics.stations_configured(self.stations.len());
+
+        self.zone_names.clear();
+        for z in self.alert_zones.iter() {
+            let res = self
+                .client
+                .zone(z, Trigger::Scheduled)
+                .instrument(tracing::span!(Level::DEBUG, "nws_zone", trigger = %Trigger::Scheduled))
+                .await;
+
+            match res {
+                Ok(zone) => {
+                    tracing::info!(message = "found alert zone", zone = %z, name = %zone.properties.name);
+                    self.zone_names.insert(z.clone(), zone.properties.name);
+                }
+                // Clients that can't look up zones can't fetch alerts for them either, which
+                // is logged each time alerts are fetched.
+                Err(ClientError::Unsupported(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replay observations made during the backfill period before startup to each sink so that
+    /// state kept between observations isn't lost when the exporter restarts, if enabled.
+    ///
+    /// The most recent observation for each station is left out since it's fetched and exported
+    /// by the first refresh. Errors are logged and otherwise ignored since the exporter works
+    /// without any history, it just takes longer for some metrics to be correct.
+    pub async fn backfill(&self) {
+        let period = match self.backfill {
+            Some(p) => p,
+            None => return,
+        };
+
+        let start = self.clock.now_wall() - period;
+        for s in self.stations.iter() {
+            let id = &s.id;
+            let res = self
+                .client
+                .observations_since(id, start, Trigger::Backfill)
+                .instrument(tracing::span!(Level::DEBUG, "nws_observations", trigger = %Trigger::Backfill))
+                .await;
+
+            let mut observations = match res {
+                Ok(c) => c.chronological(),
+                // Clients that can't fetch past observations can't for any station.
+                Err(ClientError::Unsupported(_)) => return,
+                Err(e) => {
+                    tracing::warn!(message = "failed to fetch observations for backfill", station_id = %id, error = %e);
+                    continue;
+                }
+            };
+
+            observations.pop();
+            let label = self.labels.get(id).cloned().unwrap_or_else(|| id.clone());
+            let batch: Vec<StationObservation> = observations
+                .into_iter()
+                .map(|obs| StationObservation {
+                    station: id.clone(),
+                    label: label.clone(),
+                    observation: obs,
+                    changed: true,
+                })
+                .collect();
+
+            tracing::info!(message = "backfilled observations", station_id = %id, count = batch.len());
+            for sink in self.sinks.iter() {
+                sink.on_backfill(&batch).await;
+            }
+        }
+    }
+
+    /// Update station forecast metrics for all stations in a loop forever, logging any errors.
+    ///
+    /// Stations are also updated outside the regular interval when requested on the refresh
+    /// channel. Requests that arrive while an update is running are run once it finishes since
+    /// it may have already fetched the stations they asked for.
+    pub async fn run(mut self, mut refresh: RefreshReceiver) -> ! {
+        let mut interval = tokio::time::interval(self.interval);
+        // Metadata was just fetched during initialization so the first fetch is after a full period.
+        let mut metadata = self.metadata_interval.map(|period| {
+            let mut i = tokio::time::interval_at(Instant::now() + period, period);
+            i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            i
+        });
+        let mut forecast = self.forecast_interval.map(|period| {
+            let mut i = tokio::time::interval(period);
+            i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            i
+        });
+        let mut alerts = self.alert_interval.map(|period| {
+            let mut i = tokio::time::interval(period);
+            i.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            i
+        });
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    self.refresh(Trigger::Scheduled, None).await;
+                }
+                _ = next_tick(metadata.as_mut()) => {
+                    self.refresh_stations().await;
+                }
+                _ = next_tick(forecast.as_mut()) => {
+                    self.refresh_forecasts().await;
+                }
+                _ = next_tick(alerts.as_mut()) => {
+                    self.refresh_alerts().await;
+                }
+                Some(req) = refresh.recv() => {
+                    tracing::info!(message = "running requested refresh", stations = ?req.stations);
+                    self.refresh(Trigger::Manual, req.stations.as_deref()).await;
+                }
+            }
+        }
+    }
+
+    /// Fetch metadata for all stations again and pass it to each sink, logging any errors.
+    ///
+    /// Stations keep the label they were initialized with so that their metadata continues to
+    /// match their observations. Stations that metadata can't be fetched for keep their existing
+    /// metadata and observations are fetched for them as usual.
+    pub async fn refresh_stations(&mut self) {
+        for s in self.stations.iter() {
+            let id = &s.id;
+            match self
+                .client
+                .station(id, Trigger::Scheduled)
+                .instrument(tracing::span!(Level::DEBUG, "nws_station", trigger = %Trigger::Scheduled))
+                .await
+            {
+                Ok(station) => {
+                    tracing::debug!(message = "refreshed station metadata", station_id = %id);
+                    let label = self
+                        .labels
+                        .get(id)
+                        .cloned()
+                        .unwrap_or_else(|| self.label_format.label(&station.properties.id));
+                    for sink in self.sinks.iter() {
+                        sink.on_station(&station, &label, s.source).await;
+                    }
+                    Self::update_location(&mut self.locations, &mut self.points, id, &station);
+                }
+                Err(e) => {
+                    tracing::warn!(message = "failed to refresh station metadata", station_id = %id, error = %e);
+                }
+            }
+        }
+    }
+
+    /// Fetch daily and hourly forecasts and raw gridpoint data for the location of every station
+    /// and pass them to each sink, logging any errors. Stations without a location are skipped.
+    /// Stations are still included if only their hourly forecast or gridpoint data couldn't be
+    /// fetched.
+    pub async fn refresh_forecasts(&mut self) {
+        let stations: Vec<(String, String)> = self
+            .stations
+            .iter()
+            .filter_map(|s| self.labels.get(&s.id).map(|l| (s.id.clone(), l.clone())))
+            .collect();
+        let mut batch = Vec::with_capacity(stations.len());
+
+        for (id, label) in stations {
+            let grid = match self.grid(&id).await {
+                Ok(Some(grid)) => grid,
+                Ok(None) => {
+                    if self.warnings.check(&id, Self::NO_LOCATION) == Warn::First {
+                        tracing::warn!(message = "skipping forecast for station without a location", station_id = %id);
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    self.forecast_failed(&id, &label, Self::FORECAST_ERROR, &e);
+                    continue;
+                }
+            };
+
+            let forecast = match self
+                .client
+                .forecast(&grid.grid_id, grid.grid_x, grid.grid_y, Trigger::Scheduled)
+                .instrument(tracing::span!(Level::DEBUG, "nws_forecast", trigger = %Trigger::Scheduled))
+                .await
+            {
+                Ok(forecast) => forecast,
+                Err(e) => {
+                    self.forecast_failed(&id, &label, Self::FORECAST_ERROR, &e);
+                    continue;
+                }
+            };
+
+            tracing::debug!(message = "fetched forecast", station_id = %id, periods = forecast.properties.periods.len());
+            if let Some(suppressed) = self.warnings.reset(&id, Self::FORECAST_ERROR) {
+                tracing::info!(message = "recovered from forecast errors", station_id = %id, suppressed = suppressed);
+            }
+
+            let hourly = match self
+                .client
+                .hourly_forecast(&grid.grid_id, grid.grid_x, grid.grid_y, Trigger::Scheduled)
+                .instrument(tracing::span!(Level::DEBUG, "nws_hourly_forecast", trigger = %Trigger::Scheduled))
+                .await
+            {
+                Ok(hourly) => {
+                    tracing::debug!(message = "fetched hourly forecast", station_id = %id, periods = hourly.properties.periods.len());
+                    if let Some(suppressed) = self.warnings.reset(&id, Self::HOURLY_FORECAST_ERROR) {
+                        tracing::info!(message = "recovered from hourly forecast errors", station_id = %id, suppressed = suppressed);
+                    }
+                    Some(hourly)
+                }
+                Err(e) => {
+                    self.forecast_failed(&id, &label, Self::HOURLY_FORECAST_ERROR, &e);
+                    None
+                }
+            };
+
+            let gridpoint = match self
+                .client
+                .gridpoint(&grid.grid_id, grid.grid_x, grid.grid_y, Trigger::Scheduled)
+                .instrument(tracing::span!(Level::DEBUG, "nws_gridpoint", trigger = %Trigger::Scheduled))
+                .await
+            {
+                Ok(gridpoint) => {
+                    tracing::debug!(message = "fetched gridpoint", station_id = %id);
+                    if let Some(suppressed) = self.warnings.reset(&id, Self::GRIDPOINT_ERROR) {
+                        tracing::info!(message = "recovered from gridpoint errors", station_id = %id, suppressed = suppressed);
+                    }
+                    Some(gridpoint)
+                }
+                Err(e) => {
+                    self.forecast_failed(&id, &label, Self::GRIDPOINT_ERROR, &e);
+                    None
+                }
+            };
+
+            batch.push(StationForecast {
+                station: id,
+                label,
+                forecast,
+                hourly,
+                gridpoint,
+                time: self.clock.now_wall(),
+            });
+        }
+
+        for sink in self.sinks.iter() {
+            sink.on_forecasts(&batch).await;
+        }
+    }
+
+    /// Fetch active alerts for every alert zone and the location of every station, if enabled,
+    /// and pass them to each sink, logging any errors.
+    ///
+    /// Alerts for stations are the alerts for their location along with any other fetched
+    /// alert that covers one of their zones or whose polygon contains them. Stations without
+    /// coordinates aren't fetched for but are still matched by their zones. Stations without
+    /// coordinates or zones are skipped. Alerts without zones or a polygon are only exported
+    /// for the zone or station they were fetched for.
+    pub async fn refresh_alerts(&self) {
+        let mut areas: Vec<AlertArea> = self
+            .alert_zones
+            .iter()
+            .map(|z| AlertArea::Zone(z.clone(), self.zone_names.get(z).cloned().unwrap_or_default()))
+            .collect();
+        if self.station_alerts {
+            areas.extend(self.stations.iter().filter_map(|s| {
+                self.labels
+                    .get(&s.id)
+                    .map(|l| AlertArea::Station(s.id.clone(), l.clone()))
+            }));
+        }
+
+        let mut batch = Vec::with_capacity(areas.len());
+        for area in areas {
+            let res = match &area {
+                AlertArea::Zone(zone, _) => {
+                    self.client
+                        .alerts_for_zone(zone, Trigger::Scheduled)
+                        .instrument(tracing::span!(Level::DEBUG, "nws_alerts", trigger = %Trigger::Scheduled))
+                        .await
+                }
+                AlertArea::Station(id, _) => match self.locations.get(id) {
+                    Some(Location {
+                        coordinates: Some(c), ..
+                    }) => {
+                        self.client
+                            .alerts_for_point(c.latitude, c.longitude, Trigger::Scheduled)
+                            .instrument(tracing::span!(Level::DEBUG, "nws_alerts", trigger = %Trigger::Scheduled))
+                            .await
+                    }
+                    Some(l) if !l.zones.is_empty() => {
+                        // Only alerts matching the zones of the station, fetched for other areas.
+                        batch.push(AreaAlerts {
+                            area,
+                            alerts: Vec::new(),
+                            time: self.clock.now_wall(),
+                        });
+                        continue;
+                    }
+                    _ => {
+                        if self.warnings.check(id, Self::ALERTS_NO_LOCATION) == Warn::First {
+                            tracing::warn!(message = "skipping alerts for station without a location", station_id = %id);
+                        }
+                        continue;
+                    }
+                },
+            };
+
+            match res {
+                Ok(alerts) => {
+                    tracing::debug!(message = "fetched alerts", area = %area, alerts = alerts.features.len());
+                    if let Some(suppressed) = self.warnings.reset(area.id(), Self::ALERTS_ERROR) {
+                        tracing::info!(message = "recovered from alert errors", area = %area, suppressed = suppressed);
+                    }
+
+                    batch.push(AreaAlerts {
+                        area,
+                        alerts: alerts.features,
+                        time: self.clock.now_wall(),
+                    });
+                }
+                Err(e) => {
+                    self.metrics.alert_failed(&area, &e);
+                    match self.warnings.check(area.id(), Self::ALERTS_ERROR) {
+                        Warn::First => {
+                            tracing::error!(message = "failed to fetch alerts", area = %area, error = %e);
+                        }
+                        Warn::Summary(suppressed) => {
+                            tracing::error!(message = "failed to fetch alerts", area = %area, error = %e, suppressed = suppressed);
+                        }
+                        Warn::Suppressed => {
+                            tracing::debug!(message = "failed to fetch alerts", area = %area, error = %e);
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.station_alerts {
+            self.match_alerts(&mut batch);
+        }
+
+        for sink in self.sinks.iter() {
+            sink.on_alerts(&batch).await;
+        }
+    }
+
+    /// Add every alert fetched for any area to the alerts of each station in the batch that
+    /// the alert covers, by zone or by polygon, unless the station already has it.
+    fn match_alerts(&self, batch: &mut [AreaAlerts]) {
+        let locations: Vec<Location> = batch
+            .iter()
+            .filter_map(|a| match &a.area {
+                AlertArea::Station(id, _) => self.locations.get(id).cloned(),
+                AlertArea::Zone(..) => None,
+            })
+            .collect();
+
+        let mut matched: HashMap<String, Vec<Alert>> = HashMap::new();
+        let mut seen = HashSet::new();
+        for alert in batch.iter().flat_map(|a| a.alerts.iter()) {
+            if !seen.insert(alert.id.as_str()) {
+                continue;
+            }
+
+            let zones = alert.properties.zones();
+            if zones.is_empty() && alert.geometry.is_none() {
+                tracing::debug!(message = "alert has no zones or geometry to match stations", alert = %alert.id);
+                continue;
+            }
+
+            for loc in geo::affected(&locations, &zones, alert.geometry.as_ref()) {
+                matched.entry(loc.station.clone()).or_default().push(alert.clone());
+            }
+        }
+
+        for area in batch.iter_mut() {
+            if let AlertArea::Station(id, _) = &area.area {
+                for alert in matched.remove(id).unwrap_or_default() {
+                    if !area.alerts.iter().any(|a| a.id == alert.id) {
+                        area.alerts.push(alert);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Get the forecast office and grid square for the location of a station, or `None` if
+    /// the station doesn't have a location. They're only looked up the first time since they
+    /// rarely change.
+    async fn grid(&mut self, id: &str) -> Result<Option<PointProperties>, ClientError> {
+        if let Some(p) = self.points.get(id) {
+            return Ok(Some(p.clone()));
+        }
+
+        let c = match self.locations.get(id).and_then(|l| l.coordinates) {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        let point = self
+            .client
+            .point(c.latitude, c.longitude, Trigger::Scheduled)
+            .instrument(tracing::span!(Level::DEBUG, "nws_point", trigger = %Trigger::Scheduled))
+            .await?
+            .properties;
+        tracing::info!(
+            message = "found forecast grid for station",
+            station_id = %id,
+            office = %point.grid_id,
+            grid_x = point.grid_x,
+            grid_y = point.grid_y,
+        );
+        self.points.insert(id.to_owned(), point.clone());
+        Ok(Some(point))
+    }
+
+    /// Count and log a failure to fetch a forecast, suppressing repeated warnings for the
+    /// station and kind of forecast.
+    fn forecast_failed(&self, id: &str, label: &str, category: &'static str, e: &ClientError) {
+        self.metrics.forecast_failed(label, e);
+        match self.warnings.check(id, category) {
+            Warn::First => {
+                tracing::error!(message = "failed to fetch forecast", station_id = %id, kind = category, error = %e);
+            }
+            Warn::Summary(suppressed) => {
+                tracing::error!(message = "failed to fetch forecast", station_id = %id, kind = category, error = %e, suppressed = suppressed);
+            }
+            Warn::Suppressed => {
+                tracing::debug!(message = "failed to fetch forecast", station_id = %id, kind = category, error = %e);
+            }
+        }
+    }
+
+    /// Remember the location and zones of a station from its metadata, forgetting anything
+    /// looked up for the previous location if it moved.
+    fn update_location(
+        locations: &mut HashMap<String, Location>,
+        points: &mut HashMap<String, PointProperties>,
+        id: &str,
+        station: &Station,
+    ) {
+        let location = Location {
+            station: id.to_owned(),
+            coordinates: station.geometry.as_ref().and_then(Geometry::point),
+            zones: station.properties.zones(),
+        };
+        if locations.get(id).and_then(|l| l.coordinates) != location.coordinates {
+            points.remove(id);
+        }
+
+        locations.insert(id.to_owned(), location);
+    }
+
+    /// Update station forecast metrics for all stations or only the given stations, logging any errors
+    pub async fn refresh(&mut self, trigger: Trigger, only: Option<&[String]>) {
+        let mut batch = Vec::with_capacity(self.stations.len());
+        let mut requests = 0;
+        let mut unavailable = 0;
+        let start = self.clock.now_monotonic();
+
+        for s in self.stations.iter() {
+            if !only.map(|ids| ids.contains(&s.id)).unwrap_or(true) {
+                continue;
+            }
+
+            let id = &s.id;
+            requests += 1;
+            match self
+                .client
+                .observation(id, trigger)
+                .instrument(tracing::span!(Level::DEBUG, "nws_observation", trigger = %trigger))
+                .await
+            {
+                Ok(obs) => {
+                    // Stations usually report much less often than they're fetched so most fetches
+                    // return the same observation as last time. Observations are always treated as
+                    // changed after a station is removed for failing so that its metrics return.
+                    let failures = self.failures.get(id).copied().unwrap_or_default();
+                    let removed = self.stale_after_failures > 0 && failures >= self.stale_after_failures;
+                    let changed = removed || self.observations.get(id) != Some(&obs.id);
+                    if changed {
+                        tracing::info!(message = "fetched new forecast", station_id = %id, observation = %obs.id);
+                    } else {
+                        tracing::debug!(message = "fetched unchanged forecast", station_id = %id, observation = %obs.id);
+                    }
+                    if let Some(suppressed) = self.warnings.reset(id, Self::FETCH_ERROR) {
+                        tracing::info!(message = "recovered from forecast errors", station_id = %id, suppressed = suppressed);
+                    }
+
+                    // Both are compared in the same format since the observation always
+                    // identifies its station by the full URL.
+                    let actual = self.label_format.label(&obs.properties.station);
+                    let label = self.labels.get(id).cloned().unwrap_or_else(|| actual.clone());
+                    if actual != label {
+                        self.metrics.station_mismatch(&label);
+                        if self.warnings.check(id, Self::STATION_MISMATCH) == Warn::First {
+                            tracing::warn!(
+                                message = "observation is for a different station than requested",
+                                station_id = %id,
+                                expected = %label,
+                                actual = %obs.properties.station,
+                            );
+                        }
+                    } else {
+                        self.warnings.reset(id, Self::STATION_MISMATCH);
+                    }
+
+                    let now = self.clock.now_wall();
+                    // Ages are relative to the time the API says it responded when possible so
+                    // that they're still correct when the local clock is wrong, such as on hosts
+                    // without a real time clock that haven't synced it yet.
+                    let reference = obs.server_time.unwrap_or(now);
+                    let age = reference - obs.properties.observed_at();
+                    // The API occasionally returns observations a few minutes in the future because
+                    // of clock skew on its end. Those are expected but anything further in the future
+                    // is probably wrong.
+                    if -age > self.future_tolerance {
+                        self.metrics.future_timestamp(&label);
+                        if self.warnings.check(id, Self::FUTURE_OBSERVATION) == Warn::First {
+                            tracing::warn!(
+                                message = "observation timestamp is in the future beyond the tolerance",
+                                station_id = %id,
+                                timestamp = %obs.properties.timestamp,
+                                now = %reference,
+                                tolerance_secs = self.future_tolerance.num_seconds(),
+                                rejected = self.reject_future_observations,
+                            );
+                        }
+
+                        if self.reject_future_observations {
+                            let failures = self.failures.entry(id.clone()).or_default();
+                            *failures += 1;
+                            let failures = *failures;
+                            self.station_failed(id, &label, failures).await;
+                            continue;
+                        }
+                    } else {
+                        self.warnings.reset(id, Self::FUTURE_OBSERVATION);
+                    }
+
+                    self.metrics.observation_age(&label, age.max(chrono::Duration::zero()));
+
+                    // Old observations are treated like failed fetches so that a station that has
+                    // stopped reporting isn't exported as if its last observation were current.
+                    if let Some(max) = self.max_observation_age {
+                        if age > max {
+                            self.metrics.stale_observation(&label);
+                            if self.warnings.check(id, Self::STALE_OBSERVATION) == Warn::First {
+                                tracing::warn!(
+                                    message = "skipping observation older than the maximum age",
+                                    station_id = %id,
+                                    timestamp = %obs.properties.timestamp,
+                                    max_age_secs = max.num_seconds(),
+                                );
+                            }
+
+                            let failures = self.failures.entry(id.clone()).or_default();
+                            *failures += 1;
+                            let failures = *failures;
+                            self.station_failed(id, &label, failures).await;
+                            continue;
+                        }
+                    }
+
+                    self.warnings.reset(id, Self::STALE_OBSERVATION);
+                    self.metrics.fetch_succeeded(&label, now);
+                    self.failures.insert(id.clone(), 0);
+                    self.metrics.station_up(&label, true);
+                    self.metrics.consecutive_failures(&label, 0);
+                    if !changed {
+                        self.metrics.unchanged_observation(&label);
+                    }
+                    if self.expose_observation_id {
+                        self.metrics.last_observation(&label, &obs.id);
+                    }
+
+                    self.observations.insert(id.clone(), obs.id.clone());
+                    batch.push(StationObservation {
+                        station: id.clone(),
+                        label,
+                        observation: obs,
+                        changed,
+                    });
+                }
+                Err(e) => {
+                    let label = self.labels.get(id).map(String::as_str).unwrap_or(id);
+                    self.metrics.fetch_failed(label, &e);
+                    let failures = self.failures.entry(id.clone()).or_default();
+                    *failures += 1;
+                    let failures = *failures;
+                    self.station_failed(id, label, failures).await;
+                    if e.is_unavailable() {
+                        unavailable += 1;
+                    }
+
+                    match self.warnings.check(id, Self::FETCH_ERROR) {
+                        Warn::First => {
+                            tracing::error!(message = "failed to fetch forecast", station_id = %id, error = %e);
+                        }
+                        Warn::Summary(suppressed) => {
+                            tracing::error!(message = "failed to fetch forecast", station_id = %id, error = %e, suppressed = suppressed);
+                        }
+                        Warn::Suppressed => {
+                            tracing::debug!(message = "failed to fetch forecast", station_id = %id, error = %e);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Only passes over every station are recorded since a refresh of some stations
+        // requested by a user isn't representative of how long a scheduled refresh takes.
+        if only.is_none() {
+            self.metrics
+                .cycle_duration(self.clock.now_monotonic().duration_since(start));
+        }
+
+        // The API is only considered down if every request failed because of the API rather
+        // than because of a problem with a particular station.
+        if requests > 0 {
+            self.metrics.api_available(unavailable < requests);
+        }
+
+        for sink in self.sinks.iter() {
+            sink.on_observations(&batch).await;
+            sink.on_cycle_end().await;
+        }
+    }
+
+    /// Mark a station as down after a failed refresh, removing its metrics from each sink
+    /// once it has failed too many times in a row.
+    async fn station_failed(&self, id: &str, label: &str, failures: u64) {
+        self.metrics.station_up(label, false);
+        self.metrics.consecutive_failures(label, failures);
+        if self.stale_after_failures > 0 && failures == self.stale_after_failures {
+            tracing::warn!(
+                message = "removing metrics for station after consecutive failures",
+                station_id = %id,
+                failures = failures,
+            );
+            self.metrics.clear_station(label);
+            for sink in self.sinks.iter() {
+                sink.on_station_stale(id, label).await;
+            }
+        }
+    }
+}
+
+/// Wait for the next tick of an optional interval, or forever if there isn't one.
+async fn next_tick(interval: Option<&mut Interval>) {
+    match interval {
+        Some(i) => {
+            i.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{AlertCollection, Forecast, Gridpoint, Observation, ObservationCollection, Point, Zone};
+    use crate::clock::ManualClock;
+    use crate::config::{ColdRiskConfig, OutputConfig, ReportingConfig, StationSource};
+    use crate::geo::Coordinates;
+    use crate::metrics::{AlertMetrics, ForecastMetrics, ForecastPeriodMetrics};
+    use crate::reporting::ReportingSink;
+    use crate::sink::RecordingSink;
+    use async_trait::async_trait;
+    use chrono::{DateTime, TimeZone, Utc};
+    use prometheus_client::encoding::text;
+    use prometheus_client::registry::Registry;
+    use reqwest::StatusCode;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    /// Responses returned by a `MockClient`, which may be changed between refreshes.
+    #[derive(Debug, Default)]
+    struct MockState {
+        stations: HashMap<String, Station>,
+        observations: HashMap<String, Observation>,
+        unavailable: HashSet<String>,
+        zone_alerts: HashMap<String, Vec<Alert>>,
+        point_alerts: Vec<Alert>,
+        requests: Vec<String>,
+        delay: Option<(Arc<ManualClock>, Duration)>,
+        forecast: Option<Forecast>,
+        hourly: Option<Forecast>,
+        gridpoint: Option<Gridpoint>,
+        /// Names of zones by ID, or `None` if zones can't be looked up.
+        zones: Option<HashMap<String, String>>,
+        /// Past observations by station ID, or `None` if they can't be fetched.
+        history: Option<HashMap<String, Vec<Observation>>>,
+    }
+
+    /// Client that returns canned responses. Clones share the same responses.
+    #[derive(Debug, Clone, Default)]
+    struct MockClient {
+        state: Arc<Mutex<MockState>>,
+    }
+
+    impl MockClient {
+        fn with<F: FnOnce(&mut MockState)>(&self, f: F) {
+            f(&mut self.state.lock().unwrap());
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.state.lock().unwrap().requests.clone()
+        }
+
+        /// Record a request for a forecast resource and return it if there is one.
+        fn grid_resource<T: Clone>(
+            &self,
+            kind: &str,
+            office: &str,
+            grid_x: i64,
+            grid_y: i64,
+            resource: impl FnOnce(&MockState) -> Option<T>,
+        ) -> Result<T, ClientError> {
+            let mut state = self.state.lock().unwrap();
+            state
+                .requests
+                .push(format!("{} {}/{},{}", kind, office, grid_x, grid_y));
+            resource(&state).ok_or_else(|| {
+                let url = format!("https://api.weather.gov/gridpoints/{}/{},{}", office, grid_x, grid_y);
+                ClientError::Unexpected(StatusCode::SERVICE_UNAVAILABLE, url.parse().unwrap(), Box::default())
+            })
+        }
+    }
+
+    #[async_trait]
+    impl WeatherClient for MockClient {
+        async fn station(&self, station: &str, _trigger: Trigger) -> Result<Station, ClientError> {
+            let mut state = self.state.lock().unwrap();
+            state.requests.push(format!("station {}", station));
+            state
+                .stations
+                .get(station)
+                .cloned()
+                .ok_or_else(|| ClientError::InvalidStation(station.to_owned()))
+        }
+
+        async fn observation(&self, station: &str, _trigger: Trigger) -> Result<Observation, ClientError> {
+            let mut state = self.state.lock().unwrap();
+            state.requests.push(format!("observation {}", station));
+            if let Some((clock, delay)) = &state.delay {
+                clock.advance(*delay);
+            }
+            if state.unavailable.contains(station) {
+                let url = format!("https://api.weather.gov/stations/{}/observations/latest", station);
+                return Err(ClientError::Unexpected(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    url.parse().unwrap(),
+                    Box::default(),
+                ));
+            }
+
+            state
+                .observations
+                .get(station)
+                .cloned()
+                .ok_or_else(|| ClientError::InvalidStation(station.to_owned()))
+        }
+
+        async fn observations_since(
+            &self,
+            station: &str,
+            start: DateTime<Utc>,
+            _trigger: Trigger,
+        ) -> Result<ObservationCollection, ClientError> {
+            let mut state = self.state.lock().unwrap();
+            let history = state
+                .history
+                .clone()
+                .ok_or(ClientError::Unsupported("observation history"))?;
+            state
+                .requests
+                .push(format!("observations {} since {}", station, start.to_rfc3339()));
+            let features = history
+                .get(station)
+                .cloned()
+                .ok_or_else(|| ClientError::InvalidStation(station.to_owned()))?;
+            Ok(ObservationCollection { features })
+        }
+
+        async fn point(&self, latitude: f64, longitude: f64, _trigger: Trigger) -> Result<Point, ClientError> {
+            let mut state = self.state.lock().unwrap();
+            state.requests.push(format!("point {},{}", latitude, longitude));
+            Ok(serde_json::from_value(serde_json::json!({
+                "id": format!("https://api.weather.gov/points/{},{}", latitude, longitude),
+                "type": "Feature",
+                "geometry": null,
+                "properties": {
+                    "gridId": "BOX",
+                    "gridX": 71,
+                    "gridY": 90,
+                    "observationStations": "https://api.weather.gov/gridpoints/BOX/71,90/stations",
+                },
+            }))
+            .unwrap())
+        }
+
+        async fn forecast(
+            &self,
+            office: &str,
+            grid_x: i64,
+            grid_y: i64,
+            _trigger: Trigger,
+        ) -> Result<Forecast, ClientError> {
+            self.grid_resource("forecast", office, grid_x, grid_y, |s| s.forecast.clone())
+        }
+
+        async fn hourly_forecast(
+            &self,
+            office: &str,
+            grid_x: i64,
+            grid_y: i64,
+            _trigger: Trigger,
+        ) -> Result<Forecast, ClientError> {
+            self.grid_resource("hourly", office, grid_x, grid_y, |s| s.hourly.clone())
+        }
+
+        async fn gridpoint(
+            &self,
+            office: &str,
+            grid_x: i64,
+            grid_y: i64,
+            _trigger: Trigger,
+        ) -> Result<Gridpoint, ClientError> {
+            self.grid_resource("gridpoint", office, grid_x, grid_y, |s| s.gridpoint.clone())
+        }
+
+        async fn zone(&self, zone: &str, _trigger: Trigger) -> Result<Zone, ClientError> {
+            let mut state = self.state.lock().unwrap();
+            let zones = state.zones.clone().ok_or(ClientError::Unsupported("zones"))?;
+            state.requests.push(format!("zone {}", zone));
+            let name = zones
+                .get(zone)
+                .ok_or_else(|| ClientError::InvalidZone(zone.to_owned()))?;
+            Ok(serde_json::from_value(serde_json::json!({
+                "id": format!("https://api.weather.gov/zones/forecast/{}", zone),
+                "properties": {"id": zone, "name": name, "state": "MA", "type": "public"},
+            }))
+            .unwrap())
+        }
+
+        async fn alerts_for_zone(&self, zone: &str, _trigger: Trigger) -> Result<AlertCollection, ClientError> {
+            let mut state = self.state.lock().unwrap();
+            state.requests.push(format!("alerts zone {}", zone));
+            let features = state.zone_alerts.get(zone).cloned().unwrap_or_default();
+            Ok(AlertCollection { features })
+        }
+
+        async fn alerts_for_point(
+            &self,
+            latitude: f64,
+            longitude: f64,
+            _trigger: Trigger,
+        ) -> Result<AlertCollection, ClientError> {
+            let mut state = self.state.lock().unwrap();
+            state.requests.push(format!("alerts point {},{}", latitude, longitude));
+            Ok(AlertCollection {
+                features: state.point_alerts.clone(),
+            })
+        }
+    }
+
+    /// Sink that shares what it records with the test that created it.
+    #[derive(Debug)]
+    struct Shared(Arc<RecordingSink>);
+
+    #[async_trait]
+    impl OutputSink for Shared {
+        fn name(&self) -> &'static str {
+            self.0.name()
+        }
+
+        async fn on_station(&self, station: &Station, label: &str, source: StationSource) {
+            self.0.on_station(station, label, source).await;
+        }
+
+        async fn on_observations(&self, batch: &[StationObservation]) {
+            self.0.on_observations(batch).await;
+        }
+
+        async fn on_cycle_end(&self) {
+            self.0.on_cycle_end().await;
+        }
+
+        async fn on_alerts(&self, batch: &[AreaAlerts]) {
+            self.0.on_alerts(batch).await;
+        }
+    }
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 10, 21, 15, 0, 0).unwrap()
+    }
+
+    fn station(id: &str, coordinates: Option<(f64, f64)>, zones: &[&str]) -> Station {
+        let url = format!("https://api.weather.gov/stations/{}", id);
+        let mut properties = serde_json::json!({
+            "@id": url,
+            "@type": "wx:ObservationStation",
+            "elevation": {"unitCode": "wmoUnit:m", "value": 9},
+            "stationIdentifier": id,
+            "name": id,
+            "timeZone": "America/New_York",
+        });
+        for z in zones {
+            let key = if z.as_bytes()[2] == b'C' { "county" } else { "forecast" };
+            properties[key] = format!("https://api.weather.gov/zones/{}/{}", key, z).into();
+        }
+
+        serde_json::from_value(serde_json::json!({
+            "id": url,
+            "type": "Feature",
+            "geometry": coordinates.map(|(lon, lat)| serde_json::json!({"type": "Point", "coordinates": [lon, lat]})),
+            "properties": properties,
+        }))
+        .unwrap()
+    }
+
+    fn alert(id: &str, zones: &[&str], geometry: Option<Geometry>) -> Alert {
+        let zones: Vec<String> = zones
+            .iter()
+            .map(|z| format!("https://api.weather.gov/zones/forecast/{}", z))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "geometry": geometry,
+            "properties": {
+                "event": "Gale Warning",
+                "severity": "Moderate",
+                "status": "Actual",
+                "messageType": "Alert",
+                "affectedZones": zones,
+            },
+        }))
+        .unwrap()
+    }
+
+    fn square(min: (f64, f64), max: (f64, f64)) -> Geometry {
+        let ring = [
+            (min.0, min.1),
+            (max.0, min.1),
+            (max.0, max.1),
+            (min.0, max.1),
+            (min.0, min.1),
+        ];
+        Geometry::Polygon(vec![ring
+            .iter()
+            .map(|(lon, lat)| Coordinates::new(*lon, *lat))
+            .collect()])
+    }
+
+    /// Task driven by a test along with everything it reports to.
+    struct Harness {
+        task: UpdateTask,
+        sink: Arc<RecordingSink>,
+        clock: Arc<ManualClock>,
+        reg: Registry,
+    }
+
+    fn harness(config: &UpdateConfig, stations: &[&str], client: &MockClient) -> Harness {
+        let mut reg = Registry::default();
+        let sink = Arc::new(RecordingSink::default());
+        let clock = Arc::new(ManualClock::new(start()));
+        let task = UpdateTask::new(
+            config,
+            stations
+                .iter()
+                .map(|s| StationConfig::new(*s, StationSource::Cli))
+                .collect(),
+            vec![Box::new(Shared(sink.clone()))],
+            ExporterMetrics::new(&mut reg),
+            WarnOnce::new(&mut reg, Duration::from_secs(3600)),
+            Box::new(client.clone()),
+        )
+        .with_clock(clock.clone());
+
+        Harness { task, sink, clock, reg }
+    }
+
+    /// Task that reports observations to `ForecastMetrics` registered with the same `Registry`
+    /// as the metrics of the task itself.
+    fn forecast_harness(
+        config: &UpdateConfig,
+        stations: &[&str],
+        client: &MockClient,
+    ) -> (UpdateTask, Arc<ManualClock>, Registry) {
+        let mut reg = Registry::default();
+        let clock = Arc::new(ManualClock::new(start()));
+        let forecast = ForecastMetrics::new(&mut reg, &OutputConfig::default(), &ColdRiskConfig::default());
+        let task = UpdateTask::new(
+            config,
+            stations
+                .iter()
+                .map(|s| StationConfig::new(*s, StationSource::Cli))
+                .collect(),
+            vec![Box::new(forecast)],
+            ExporterMetrics::new(&mut reg),
+            WarnOnce::new(&mut reg, Duration::from_secs(3600)),
+            Box::new(client.clone()),
+        )
+        .with_clock(clock.clone());
+
+        (task, clock, reg)
+    }
+
+    /// Observation from the KBOS fixture, changed to be from the given station at the given time.
+    fn observation(id: &str, time: DateTime<Utc>) -> Observation {
+        let mut obs: serde_json::Value = serde_json::from_str(include_str!("../../ext/fixtures/KBOS.json")).unwrap();
+        let url = format!("https://api.weather.gov/stations/{}", id);
+        obs["id"] = format!("{}/observations/{}", url, time.to_rfc3339()).into();
+        obs["properties"]["station"] = url.into();
+        obs["properties"]["timestamp"] = time.to_rfc3339().into();
+        serde_json::from_value(obs).unwrap()
+    }
+
+    fn encode(reg: &Registry) -> String {
+        let mut out = String::new();
+        text::encode(&mut out, reg).unwrap();
+        out
+    }
+
+    /// Value of a metric without any labels in encoded output, if there is one.
+    fn value(out: &str, name: &str) -> Option<f64> {
+        let prefix = format!("{} ", name);
+        out.lines()
+            .find_map(|l| l.strip_prefix(&prefix))
+            .map(|v| v.parse().unwrap())
+    }
+
+    /// Value of a metric for a station with the given ID in encoded output, if there is one.
+    fn station_value(out: &str, name: &str, id: &str) -> Option<f64> {
+        let prefix = format!("{}{{station=\"https://api.weather.gov/stations/{}\"}} ", name, id);
+        out.lines()
+            .find_map(|l| l.strip_prefix(&prefix))
+            .map(|v| v.parse().unwrap())
+    }
+
+    /// Station IDs and whether each observation changed for each batch of observations.
+    fn batch_ids(sink: &RecordingSink) -> Vec<Vec<(String, bool)>> {
+        sink.batches()
+            .iter()
+            .map(|b| b.iter().map(|o| (o.station.clone(), o.changed)).collect())
+            .collect()
+    }
+
+    /// IDs of the alerts for each area in the most recent batch of alerts.
+    fn alert_ids(sink: &RecordingSink) -> Vec<(String, Vec<String>)> {
+        sink.alerts()
+            .last()
+            .unwrap()
+            .iter()
+            .map(|a| {
+                let ids = a.alerts.iter().map(|a| a.id.clone()).collect();
+                (a.area.to_string(), ids)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_refresh_alerts_polygon() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &["MAZ015"]));
+            // Buoys aren't in any forecast zone
+            s.stations
+                .insert("44013".to_owned(), station("44013", Some((-70.65, 42.35)), &[]));
+            s.zone_alerts.insert(
+                "ANZ230".to_owned(),
+                vec![alert("gale", &[], Some(square((-70.8, 42.2), (-70.5, 42.5))))],
+            );
+        });
+
+        let config = UpdateConfig {
+            alert_zones: vec!["ANZ230".to_owned()],
+            station_alerts: true,
+            ..UpdateConfig::default()
+        };
+        let Harness { mut task, sink, .. } = harness(&config, &["KBOS", "44013"], &client);
+        task.initialize().await.unwrap();
+        task.refresh_alerts().await;
+
+        assert_eq!(
+            vec![
+                ("zone ANZ230".to_owned(), vec!["gale".to_owned()]),
+                ("station KBOS".to_owned(), vec![]),
+                ("station 44013".to_owned(), vec!["gale".to_owned()]),
+            ],
+            alert_ids(&sink)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_alerts_zones() {
+        let client = MockClient::default();
+        client.with(|s| {
+            // Station without coordinates is only matched by its zones and never fetched for
+            s.stations
+                .insert("KXYZ".to_owned(), station("KXYZ", None, &["MAZ015", "MAC025"]));
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &["MAZ015"]));
+            s.zone_alerts.insert(
+                "MAZ016".to_owned(),
+                vec![
+                    alert("wind", &["MAZ015", "MAZ016"], None),
+                    alert("unmatched", &[], None),
+                ],
+            );
+            s.point_alerts = vec![alert("wind", &["MAZ015", "MAZ016"], None)];
+        });
+
+        let config = UpdateConfig {
+            alert_zones: vec!["MAZ016".to_owned()],
+            station_alerts: true,
+            ..UpdateConfig::default()
+        };
+        let Harness { mut task, sink, .. } = harness(&config, &["KXYZ", "KBOS"], &client);
+        task.initialize().await.unwrap();
+        task.refresh_alerts().await;
+
+        // Alerts without zones or geometry are only exported for the area they were fetched for
+        // and alerts aren't duplicated for stations that fetched them for their location.
+        assert_eq!(
+            vec![
+                (
+                    "zone MAZ016".to_owned(),
+                    vec!["wind".to_owned(), "unmatched".to_owned()]
+                ),
+                ("station KXYZ".to_owned(), vec!["wind".to_owned()]),
+                ("station KBOS".to_owned(), vec!["wind".to_owned()]),
+            ],
+            alert_ids(&sink)
+        );
+        assert_eq!(
+            vec![
+                "station KXYZ",
+                "station KBOS",
+                "alerts zone MAZ016",
+                "alerts point 42.37,-71.03",
+            ],
+            client.requests()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_alerts_no_location() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations.insert("KNONE".to_owned(), station("KNONE", None, &[]));
+            s.zone_alerts.insert(
+                "MAZ015".to_owned(),
+                vec![alert("everywhere", &[], Some(square((-180.0, -90.0), (180.0, 90.0))))],
+            );
+        });
+
+        let config = UpdateConfig {
+            alert_zones: vec!["MAZ015".to_owned()],
+            station_alerts: true,
+            ..UpdateConfig::default()
+        };
+        let Harness { mut task, sink, .. } = harness(&config, &["KNONE"], &client);
+        task.initialize().await.unwrap();
+        task.refresh_alerts().await;
+
+        assert_eq!(
+            vec![("zone MAZ015".to_owned(), vec!["everywhere".to_owned()])],
+            alert_ids(&sink)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_alerts_station_alerts_disabled() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("44013".to_owned(), station("44013", Some((-70.65, 42.35)), &[]));
+            s.zone_alerts.insert(
+                "ANZ230".to_owned(),
+                vec![alert("gale", &[], Some(square((-70.8, 42.2), (-70.5, 42.5))))],
+            );
+        });
+
+        let config = UpdateConfig {
+            alert_zones: vec!["ANZ230".to_owned()],
+            ..UpdateConfig::default()
+        };
+        let Harness { mut task, sink, .. } = harness(&config, &["44013"], &client);
+        task.initialize().await.unwrap();
+        task.refresh_alerts().await;
+
+        assert_eq!(
+            vec![("zone ANZ230".to_owned(), vec!["gale".to_owned()])],
+            alert_ids(&sink)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sinks_driven_by_refresh() {
+        let client = MockClient::default();
+        client.with(|s| {
+            for id in ["KBOS", "KJFK", "KORD"] {
+                s.stations
+                    .insert(id.to_owned(), station(id, Some((-71.03, 42.37)), &[]));
+                s.observations.insert(id.to_owned(), observation(id, start()));
+            }
+            s.unavailable.insert("KORD".to_owned());
+        });
+
+        let config = UpdateConfig::default();
+        let Harness {
+            mut task,
+            sink,
+            clock,
+            reg,
+        } = harness(&config, &["KBOS", "KJFK", "KORD"], &client);
+        task.initialize().await.unwrap();
+        assert_eq!(
+            vec![
+                ("KBOS".to_owned(), StationSource::Cli),
+                ("KJFK".to_owned(), StationSource::Cli),
+                ("KORD".to_owned(), StationSource::Cli),
+            ],
+            sink.stations()
+        );
+        assert!(sink.batches().is_empty());
+        assert_eq!(0, sink.cycles());
+
+        task.refresh(Trigger::Scheduled, None).await;
+        clock.advance(Duration::from_secs(3600));
+        client.with(|s| {
+            s.observations.insert(
+                "KJFK".to_owned(),
+                observation("KJFK", start() + chrono::Duration::hours(1)),
+            );
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        task.refresh(Trigger::Manual, Some(&["KJFK".to_owned()])).await;
+
+        // Stations that fail aren't included and unchanged observations are still passed along
+        assert_eq!(
+            vec![
+                vec![("KBOS".to_owned(), true), ("KJFK".to_owned(), true)],
+                vec![("KBOS".to_owned(), false), ("KJFK".to_owned(), true)],
+                vec![("KJFK".to_owned(), false)],
+            ],
+            batch_ids(&sink)
+        );
+        assert_eq!(3, sink.cycles());
+
+        let out = encode(&reg);
+        assert!(out.contains("nws_up{station=\"https://api.weather.gov/stations/KBOS\"} 1"));
+        assert!(out.contains("nws_up{station=\"https://api.weather.gov/stations/KORD\"} 0"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_station_mismatch() {
+        // The latest observation of this sensor claims to be from the parent site
+        let obs: Observation = serde_json::from_str(include_str!("../../ext/fixtures/BHBM3_mismatch.json")).unwrap();
+        assert_eq!("https://api.weather.gov/stations/KBOS", obs.properties.station);
+
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("BHBM3".to_owned(), station("BHBM3", Some((-71.05, 42.35)), &[]));
+            s.observations.insert("BHBM3".to_owned(), obs);
+        });
+
+        let config = UpdateConfig::default();
+        let Harness {
+            mut task, sink, reg, ..
+        } = harness(&config, &["BHBM3"], &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        task.refresh(Trigger::Scheduled, None).await;
+
+        // Observations are still attributed to the station that was requested
+        for batch in sink.batches() {
+            assert_eq!(1, batch.len());
+            assert_eq!("BHBM3", batch[0].station);
+            assert_eq!("https://api.weather.gov/stations/BHBM3", batch[0].label);
+        }
+
+        let out = encode(&reg);
+        assert!(
+            out.contains("nws_exporter_station_mismatch_total{station=\"https://api.weather.gov/stations/BHBM3\"} 2")
+        );
+        assert!(out.contains("nws_up{station=\"https://api.weather.gov/stations/BHBM3\"} 1"));
+        assert!(!out.contains("stations/KBOS"));
+    }
+
+    #[tokio::test]
+    async fn test_api_available() {
+        let client = MockClient::default();
+        client.with(|s| {
+            for id in ["KBOS", "KJFK"] {
+                s.stations
+                    .insert(id.to_owned(), station(id, Some((-71.03, 42.37)), &[]));
+                s.observations.insert(id.to_owned(), observation(id, start()));
+            }
+        });
+
+        let config = UpdateConfig::default();
+        let Harness { mut task, reg, .. } = harness(&config, &["KBOS", "KJFK"], &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        assert_eq!(Some(1.0), value(&encode(&reg), "nws_exporter_api_up"));
+
+        // Every request failing because of the API means it's down
+        client.with(|s| {
+            s.unavailable.insert("KBOS".to_owned());
+            s.unavailable.insert("KJFK".to_owned());
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(0.0), value(&out, "nws_exporter_api_up"));
+        assert_eq!(Some(2.0), value(&out, "nws_exporter_api_consecutive_failures"));
+
+        // A single successful response means it's up, even if other stations fail
+        client.with(|s| {
+            s.unavailable.remove("KJFK");
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(1.0), value(&out, "nws_exporter_api_up"));
+        assert_eq!(Some(0.0), value(&out, "nws_exporter_api_consecutive_failures"));
+    }
+
+    #[tokio::test]
+    async fn test_api_available_station_errors() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.unavailable.insert("KBOS".to_owned());
+        });
+
+        let config = UpdateConfig::default();
+        let Harness { mut task, reg, .. } = harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        assert_eq!(Some(0.0), value(&encode(&reg), "nws_exporter_api_up"));
+
+        // A station that doesn't exist is a problem with the station, not the API
+        client.with(|s| {
+            s.unavailable.clear();
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(1.0), value(&out, "nws_exporter_api_up"));
+        assert_eq!(Some(0.0), value(&out, "nws_exporter_api_consecutive_failures"));
+        assert!(out.contains("nws_up{station=\"https://api.weather.gov/stations/KBOS\"} 0"));
+    }
+
+    #[tokio::test]
+    async fn test_max_observation_age() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.observations.insert(
+                "KBOS".to_owned(),
+                observation("KBOS", start() - chrono::Duration::minutes(10)),
+            );
+        });
+
+        let config = UpdateConfig {
+            max_observation_age_secs: 3600,
+            ..UpdateConfig::default()
+        };
+        let Harness {
+            mut task,
+            sink,
+            clock,
+            reg,
+        } = harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(600.0), station_value(&out, "nws_observation_age_seconds", "KBOS"));
+        assert_eq!(Some(1.0), station_value(&out, "nws_up", "KBOS"));
+
+        // Exactly the maximum age is still fine
+        clock.advance(Duration::from_secs(3000));
+        task.refresh(Trigger::Scheduled, None).await;
+        assert_eq!(Some(1.0), station_value(&encode(&reg), "nws_up", "KBOS"));
+
+        // The station stopped reporting and its last observation is too old to export
+        clock.advance(Duration::from_secs(1));
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(1.0), station_value(&out, "nws_stale_observations_total", "KBOS"));
+        assert_eq!(Some(0.0), station_value(&out, "nws_up", "KBOS"));
+        assert_eq!(Some(1.0), station_value(&out, "nws_consecutive_fetch_failures", "KBOS"));
+        assert!(sink.batches().last().unwrap().is_empty());
+
+        // And recovers once it reports again
+        client.with(|s| {
+            s.observations
+                .insert("KBOS".to_owned(), observation("KBOS", clock.now_wall()));
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(1.0), station_value(&out, "nws_up", "KBOS"));
+        assert_eq!(1, sink.batches().last().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_observation_age() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.observations.insert(
+                "KBOS".to_owned(),
+                observation("KBOS", start() - chrono::Duration::minutes(10)),
+            );
+        });
+
+        let Harness {
+            mut task,
+            sink,
+            clock: _clock,
+            reg,
+        } = harness(&UpdateConfig::default(), &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(600.0), station_value(&out, "nws_observation_age_seconds", "KBOS"));
+        assert_eq!(None, station_value(&out, "nws_future_timestamps_total", "KBOS"));
+
+        // Skew within the tolerance is clamped to zero without being counted
+        client.with(|s| {
+            s.observations.insert(
+                "KBOS".to_owned(),
+                observation("KBOS", start() + chrono::Duration::minutes(2)),
+            );
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(0.0), station_value(&out, "nws_observation_age_seconds", "KBOS"));
+        assert_eq!(None, station_value(&out, "nws_future_timestamps_total", "KBOS"));
+        assert_eq!(1, sink.batches().last().unwrap().len());
+
+        // Skew beyond the tolerance is clamped and counted
+        client.with(|s| {
+            s.observations.insert(
+                "KBOS".to_owned(),
+                observation("KBOS", start() + chrono::Duration::hours(1)),
+            );
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(0.0), station_value(&out, "nws_observation_age_seconds", "KBOS"));
+        assert_eq!(Some(1.0), station_value(&out, "nws_future_timestamps_total", "KBOS"));
+        assert_eq!(1, sink.batches().last().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_observation_age_clock_jump() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.observations.insert("KBOS".to_owned(), observation("KBOS", start()));
+        });
+
+        let config = UpdateConfig {
+            max_observation_age_secs: 3600,
+            ..UpdateConfig::default()
+        };
+        let Harness {
+            mut task,
+            sink,
+            clock,
+            reg,
+        } = harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+
+        // The local clock jumps a day ahead, making the observation look stale
+        clock.set_wall(start() + chrono::Duration::days(1));
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(1.0), station_value(&out, "nws_stale_observations_total", "KBOS"));
+        assert!(sink.batches().last().unwrap().is_empty());
+
+        // Ages are relative to the time the API responded when it's known
+        client.with(|s| {
+            let obs = s.observations.get_mut("KBOS").unwrap();
+            obs.server_time = Some(start() + chrono::Duration::minutes(5));
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(300.0), station_value(&out, "nws_observation_age_seconds", "KBOS"));
+        assert_eq!(Some(1.0), station_value(&out, "nws_up", "KBOS"));
+        assert_eq!(1, sink.batches().last().unwrap().len());
+
+        // A clock that's a day behind makes the observation look like it's from the future
+        // and its age is reported as zero
+        client.with(|s| {
+            s.observations.get_mut("KBOS").unwrap().server_time = None;
+        });
+        clock.set_wall(start() - chrono::Duration::days(1));
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(1.0), station_value(&out, "nws_future_timestamps_total", "KBOS"));
+        assert_eq!(Some(0.0), station_value(&out, "nws_observation_age_seconds", "KBOS"));
+        assert_eq!(1, sink.batches().last().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_station_up() {
+        let client = MockClient::default();
+        client.with(|s| {
+            for id in ["KBOS", "KJFK"] {
+                s.stations
+                    .insert(id.to_owned(), station(id, Some((-71.03, 42.37)), &[]));
+                s.observations.insert(id.to_owned(), observation(id, start()));
+            }
+        });
+
+        let config = UpdateConfig::default();
+        let Harness { mut task, reg, .. } = harness(&config, &["KBOS", "KJFK"], &client);
+        task.initialize().await.unwrap();
+
+        // Each station flips independently of the others
+        for i in 0..4 {
+            let failing = i % 2 == 1;
+            client.with(|s| {
+                if failing {
+                    s.unavailable.insert("KBOS".to_owned());
+                } else {
+                    s.unavailable.remove("KBOS");
+                }
+            });
+            task.refresh(Trigger::Scheduled, None).await;
+
+            let out = encode(&reg);
+            let expected = if failing { 0.0 } else { 1.0 };
+            assert_eq!(Some(expected), station_value(&out, "nws_up", "KBOS"), "refresh {}", i);
+            assert_eq!(Some(1.0), station_value(&out, "nws_up", "KJFK"), "refresh {}", i);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_last_fetch_success() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.observations.insert("KBOS".to_owned(), observation("KBOS", start()));
+        });
+
+        let config = UpdateConfig::default();
+        let Harness {
+            mut task, clock, reg, ..
+        } = harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        let name = "nws_last_fetch_success_timestamp_seconds";
+
+        task.refresh(Trigger::Scheduled, None).await;
+        let first = start().timestamp() as f64;
+        assert_eq!(Some(first), station_value(&encode(&reg), name, "KBOS"));
+
+        // Updated by every successful fetch, even of the same observation
+        clock.advance(Duration::from_secs(60));
+        task.refresh(Trigger::Scheduled, None).await;
+        assert_eq!(Some(first + 60.0), station_value(&encode(&reg), name, "KBOS"));
+
+        // Failures leave it as-is
+        client.with(|s| {
+            s.unavailable.insert("KBOS".to_owned());
+        });
+        clock.advance(Duration::from_secs(60));
+        task.refresh(Trigger::Scheduled, None).await;
+        assert_eq!(Some(first + 60.0), station_value(&encode(&reg), name, "KBOS"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_errors() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.unavailable.insert("KBOS".to_owned());
+        });
+
+        let config = UpdateConfig::default();
+        let Harness { mut task, reg, .. } = harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        task.refresh(Trigger::Scheduled, None).await;
+
+        let out = encode(&reg);
+        assert!(out.contains(
+            "nws_fetch_errors_total{station=\"https://api.weather.gov/stations/KBOS\",reason=\"unexpected_status\",code=\"503\"} 2\n"
+        ), "{}", out);
+    }
+
+    #[tokio::test]
+    async fn test_consecutive_failures() {
+        let client = MockClient::default();
+        client.with(|s| {
+            for id in ["KBOS", "KJFK"] {
+                s.stations
+                    .insert(id.to_owned(), station(id, Some((-71.03, 42.37)), &[]));
+                s.observations.insert(id.to_owned(), observation(id, start()));
+            }
+            s.unavailable.insert("KBOS".to_owned());
+        });
+
+        let config = UpdateConfig::default();
+        let Harness { mut task, reg, .. } = harness(&config, &["KBOS", "KJFK"], &client);
+        task.initialize().await.unwrap();
+        let name = "nws_consecutive_fetch_failures";
+
+        for expected in [1.0, 2.0] {
+            task.refresh(Trigger::Scheduled, None).await;
+            let out = encode(&reg);
+            assert_eq!(Some(expected), station_value(&out, name, "KBOS"));
+            assert_eq!(Some(0.0), station_value(&out, name, "KJFK"));
+        }
+
+        client.with(|s| {
+            s.unavailable.clear();
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(0.0), station_value(&out, name, "KBOS"));
+        assert_eq!(Some(0.0), station_value(&out, name, "KJFK"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_cycle_metrics() {
+        let client = MockClient::default();
+        client.with(|s| {
+            for id in ["KBOS", "KJFK"] {
+                s.stations
+                    .insert(id.to_owned(), station(id, Some((-71.03, 42.37)), &[]));
+                s.observations.insert(id.to_owned(), observation(id, start()));
+            }
+        });
+
+        let config = UpdateConfig::default();
+        let Harness {
+            mut task, clock, reg, ..
+        } = harness(&config, &["KBOS", "KJFK"], &client);
+        let out = encode(&reg);
+        assert_eq!(Some(2.0), value(&out, "nws_stations_configured"));
+        assert_eq!(Some(0.0), value(&out, "nws_refresh_cycle_duration_seconds"));
+
+        // Each fetch takes a while and stations are fetched one after another
+        client.with(|s| {
+            s.delay = Some((clock.clone(), Duration::from_secs(3)));
+        });
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(6.0), value(&out, "nws_refresh_cycle_duration_seconds"));
+        assert_eq!(Some(2.0), value(&out, "nws_stations_configured"));
+    }
+
+    /// Names of the metrics with a series for a station with the given ID in encoded output,
+    /// other than counters.
+    fn station_gauges(out: &str, id: &str) -> Vec<String> {
+        let label = format!("station=\"https://api.weather.gov/stations/{}\"", id);
+        let mut names: Vec<String> = out
+            .lines()
+            .filter(|l| !l.starts_with('#') && l.contains(&label))
+            .map(|l| l.split('{').next().unwrap().to_owned())
+            .filter(|n| !n.ends_with("_total"))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_stale_station_removed() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.observations.insert("KBOS".to_owned(), observation("KBOS", start()));
+            s.forecast = Some(serde_json::from_str(include_str!("../../ext/fixtures/forecast_BOX.json")).unwrap());
+            s.point_alerts = vec![alert("gale", &[], Some(square((-72.0, 42.0), (-70.0, 43.0))))];
+        });
+
+        // Every sink that exports series for a station
+        let config = UpdateConfig {
+            stale_after_failures: 3,
+            forecast_periods: 2,
+            station_alerts: true,
+            expose_observation_id: true,
+            ..UpdateConfig::default()
+        };
+        let opts = OutputConfig::default();
+        let mut reg = Registry::default();
+        let sinks: Vec<Box<dyn OutputSink>> = vec![
+            Box::new(ForecastMetrics::new(&mut reg, &opts, &ColdRiskConfig::default())),
+            Box::new(ForecastPeriodMetrics::new(&mut reg, config.forecast_periods, &opts)),
+            Box::new(AlertMetrics::new(&mut reg)),
+            Box::new(ReportingSink::new(&mut reg, &ReportingConfig::default())),
+        ];
+        let mut task = UpdateTask::new(
+            &config,
+            vec![StationConfig::new("KBOS", StationSource::Cli)],
+            sinks,
+            ExporterMetrics::new(&mut reg),
+            WarnOnce::new(&mut reg, Duration::from_secs(3600)),
+            Box::new(client.clone()),
+        )
+        .with_clock(Arc::new(ManualClock::new(start())));
+        task.initialize().await.unwrap();
+
+        task.refresh(Trigger::Scheduled, None).await;
+        task.refresh_forecasts().await;
+        task.refresh_alerts().await;
+        let out = encode(&reg);
+        let all = station_gauges(&out, "KBOS");
+        assert_eq!(Some(16.7), station_value(&out, "nws_temperature_degrees", "KBOS"));
+        assert_eq!(Some(1.0), station_value(&out, "nws_up", "KBOS"));
+        let scheduled_separately = [
+            "nws_active_alerts",
+            "nws_alert",
+            "nws_forecast_period_start_timestamp_seconds",
+            "nws_forecast_precipitation_probability",
+            "nws_forecast_temperature_degrees",
+            "nws_forecast_wind_speed_kph",
+        ];
+        for name in scheduled_separately.iter().chain(&[
+            "nws_last_observation_info",
+            "nws_observation_age_seconds",
+            "nws_station_reporting",
+        ]) {
+            assert!(all.iter().any(|n| n == name), "missing {} in {:?}", name, all);
+        }
+
+        client.with(|s| {
+            s.unavailable.insert("KBOS".to_owned());
+        });
+
+        // The last values are kept until the station has failed enough times in a row
+        for failures in [1.0, 2.0] {
+            task.refresh(Trigger::Scheduled, None).await;
+            let out = encode(&reg);
+            assert_eq!(all, station_gauges(&out, "KBOS"));
+            assert_eq!(Some(16.7), station_value(&out, "nws_temperature_degrees", "KBOS"));
+            assert_eq!(Some(0.0), station_value(&out, "nws_up", "KBOS"));
+            assert_eq!(
+                Some(failures),
+                station_value(&out, "nws_consecutive_fetch_failures", "KBOS")
+            );
+        }
+
+        // Only station metadata, whether the station is up, its consecutive failures, and
+        // counters are left
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(
+            vec![
+                "nws_consecutive_fetch_failures",
+                "nws_elevation_meters",
+                "nws_exporter_station_source",
+                "nws_station",
+                "nws_station_latitude_degrees",
+                "nws_station_longitude_degrees",
+                "nws_up",
+            ],
+            station_gauges(&out, "KBOS")
+        );
+        assert_eq!(Some(0.0), station_value(&out, "nws_up", "KBOS"));
+        assert_eq!(Some(3.0), station_value(&out, "nws_consecutive_fetch_failures", "KBOS"));
+        assert!(out.contains("nws_fetch_errors_total{station=\"https://api.weather.gov/stations/KBOS\""));
+
+        // The same observation as before the failures repopulates every series set from
+        // observations. Forecasts and alerts come back the next time they're fetched.
+        client.with(|s| {
+            s.unavailable.clear();
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        let observed: Vec<String> = all
+            .iter()
+            .filter(|n| !scheduled_separately.contains(&n.as_str()))
+            .cloned()
+            .collect();
+        assert_eq!(observed, station_gauges(&out, "KBOS"));
+        assert_eq!(Some(16.7), station_value(&out, "nws_temperature_degrees", "KBOS"));
+        assert_eq!(Some(6.1), station_value(&out, "nws_dewpoint_degrees", "KBOS"));
+        assert_eq!(Some(1.0), station_value(&out, "nws_up", "KBOS"));
+
+        task.refresh_forecasts().await;
+        task.refresh_alerts().await;
+        assert_eq!(all, station_gauges(&encode(&reg), "KBOS"));
+    }
+
+    #[tokio::test]
+    async fn test_max_observation_age_fixtures() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+        });
+
+        let config = UpdateConfig {
+            max_observation_age_secs: 3600,
+            ..UpdateConfig::default()
+        };
+        let Harness {
+            mut task, sink, reg, ..
+        } = harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+
+        let mut stale = 0.0;
+        for (age, accepted) in [
+            (chrono::Duration::zero(), true),
+            (chrono::Duration::minutes(30), true),
+            (chrono::Duration::seconds(3600), true),
+            (chrono::Duration::seconds(3601), false),
+            (chrono::Duration::hours(9), false),
+        ] {
+            client.with(|s| {
+                s.observations
+                    .insert("KBOS".to_owned(), observation("KBOS", start() - age));
+            });
+            task.refresh(Trigger::Scheduled, None).await;
+            let out = encode(&reg);
+            if !accepted {
+                stale += 1.0;
+            }
+
+            let up = if accepted { 1.0 } else { 0.0 };
+            assert_eq!(Some(up), station_value(&out, "nws_up", "KBOS"), "age {}", age);
+            assert_eq!(accepted as usize, sink.batches().last().unwrap().len(), "age {}", age);
+            assert_eq!(
+                if stale > 0.0 { Some(stale) } else { None },
+                station_value(&out, "nws_stale_observations_total", "KBOS"),
+                "age {}",
+                age
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_observation_not_successful() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.observations.insert("KBOS".to_owned(), observation("KBOS", start()));
+        });
+
+        let config = UpdateConfig {
+            max_observation_age_secs: 3600,
+            ..UpdateConfig::default()
+        };
+        let Harness {
+            mut task, clock, reg, ..
+        } = harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        let name = "nws_last_fetch_success_timestamp_seconds";
+
+        task.refresh(Trigger::Scheduled, None).await;
+        let first = start().timestamp() as f64;
+        assert_eq!(Some(first), station_value(&encode(&reg), name, "KBOS"));
+
+        // Fetching an observation that's too old to export isn't a successful fetch
+        clock.advance(Duration::from_secs(7200));
+        task.refresh(Trigger::Scheduled, None).await;
+        assert_eq!(Some(first), station_value(&encode(&reg), name, "KBOS"));
+    }
+
+    #[tokio::test]
+    async fn test_station_label_format() {
+        let client = MockClient::default();
+        client.with(|s| {
+            for id in ["KBOS", "KJFK"] {
+                s.stations
+                    .insert(id.to_owned(), station(id, Some((-71.03, 42.37)), &[]));
+                s.observations.insert(id.to_owned(), observation(id, start()));
+            }
+        });
+
+        for (format, expected) in [
+            (
+                StationLabelFormat::Url,
+                [
+                    "https://api.weather.gov/stations/KBOS",
+                    "https://api.weather.gov/stations/KJFK",
+                ],
+            ),
+            (StationLabelFormat::Id, ["KBOS", "KJFK"]),
+        ] {
+            let config = UpdateConfig {
+                station_label: format,
+                ..UpdateConfig::default()
+            };
+            let (mut task, _clock, reg) = forecast_harness(&config, &["KBOS", "KJFK"], &client);
+            task.initialize().await.unwrap();
+            task.refresh(Trigger::Scheduled, None).await;
+            let out = encode(&reg);
+
+            // Every series of every family uses the same label for a station so they can be joined
+            let mut families = HashSet::new();
+            for line in out.lines().filter(|l| !l.starts_with('#')) {
+                let Some((name, rest)) = line.split_once("{station=\"") else {
+                    continue;
+                };
+                let label = rest.split('"').next().unwrap();
+                assert!(expected.contains(&label), "{}: {}", format, line);
+                families.insert(name.to_owned());
+            }
+
+            for name in [
+                "nws_station",
+                "nws_up",
+                "nws_temperature_degrees",
+                "nws_observation_age_seconds",
+            ] {
+                assert!(families.contains(name), "{}: missing {} in {}", format, name, out);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_stations() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.observations.insert("KBOS".to_owned(), observation("KBOS", start()));
+        });
+
+        let stations = StationConfig::normalize(vec![
+            StationConfig::new("kbos", StationSource::Cli),
+            StationConfig::new(" KBOS", StationSource::Env),
+        ]);
+        let ids: Vec<&str> = stations.iter().map(|s| s.id.as_str()).collect();
+        let Harness {
+            mut task, sink, reg, ..
+        } = harness(&UpdateConfig::default(), &ids, &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+
+        // The station is only fetched and reported once per cycle
+        assert_eq!(vec!["station KBOS", "observation KBOS"], client.requests());
+        assert_eq!(vec![vec![("KBOS".to_owned(), true)]], batch_ids(&sink));
+        assert_eq!(Some(1.0), value(&encode(&reg), "nws_stations_configured"));
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_observations() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.observations.insert("KBOS".to_owned(), observation("KBOS", start()));
+        });
+
+        let config = UpdateConfig::default();
+        let (mut task, clock, reg) = forecast_harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(16.7), station_value(&out, "nws_temperature_degrees", "KBOS"));
+        assert_eq!(None, station_value(&out, "nws_unchanged_observations_total", "KBOS"));
+
+        // The same observation isn't applied again, even if its contents are different
+        clock.advance(Duration::from_secs(300));
+        client.with(|s| {
+            let obs = s.observations.get_mut("KBOS").unwrap();
+            obs.properties.temperature.value = Some(20.0);
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(16.7), station_value(&out, "nws_temperature_degrees", "KBOS"));
+        assert_eq!(
+            Some(1.0),
+            station_value(&out, "nws_unchanged_observations_total", "KBOS")
+        );
+        assert_eq!(Some(1.0), station_value(&out, "nws_up", "KBOS"));
+        assert_eq!(
+            Some(start().timestamp() as f64 + 300.0),
+            station_value(&out, "nws_last_fetch_success_timestamp_seconds", "KBOS")
+        );
+
+        // A new observation is
+        client.with(|s| {
+            let mut obs = observation("KBOS", start() + chrono::Duration::hours(1));
+            obs.properties.temperature.value = Some(20.0);
+            s.observations.insert("KBOS".to_owned(), obs);
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(20.0), station_value(&out, "nws_temperature_degrees", "KBOS"));
+        assert_eq!(
+            Some(1.0),
+            station_value(&out, "nws_unchanged_observations_total", "KBOS")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_stations() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.observations.insert("KBOS".to_owned(), observation("KBOS", start()));
+        });
+        let info = |out: &str| -> Vec<String> {
+            out.lines()
+                .filter(|l| l.starts_with("nws_station{"))
+                .map(|l| l.to_owned())
+                .collect()
+        };
+
+        let config = UpdateConfig::default();
+        let (mut task, _clock, reg) = forecast_harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        let series = info(&encode(&reg));
+        assert_eq!(1, series.len(), "{:?}", series);
+        assert!(series[0].contains("station_name=\"KBOS\""), "{:?}", series);
+
+        // The station is renamed and its elevation fixed, replacing the old series
+        client.with(|s| {
+            let station = s.stations.get_mut("KBOS").unwrap();
+            station.properties.name = "Boston, Logan International Airport".to_owned();
+            station.properties.elevation.value = Some(6.0);
+        });
+        task.refresh_stations().await;
+        let out = encode(&reg);
+        let series = info(&out);
+        assert_eq!(1, series.len(), "{:?}", series);
+        assert!(
+            series[0].contains("station_name=\"Boston, Logan International Airport\""),
+            "{:?}",
+            series
+        );
+        assert_eq!(Some(6.0), station_value(&out, "nws_elevation_meters", "KBOS"));
+
+        // Failing to fetch metadata keeps the existing metadata and doesn't affect observations
+        client.with(|s| {
+            s.stations.clear();
+        });
+        task.refresh_stations().await;
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(series, info(&out));
+        assert_eq!(Some(1.0), station_value(&out, "nws_up", "KBOS"));
+        assert_eq!(Some(16.7), station_value(&out, "nws_temperature_degrees", "KBOS"));
+        assert_eq!(
+            vec![
+                "station KBOS",
+                "observation KBOS",
+                "station KBOS",
+                "station KBOS",
+                "observation KBOS"
+            ],
+            client.requests()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_initialize_canonical_ids() {
+        let client = MockClient::default();
+        client.with(|s| {
+            // The API answers for IDs that aren't canonical but identifies the station canonically
+            for id in ["kbos", "KBOS", "Kbos"] {
+                s.stations
+                    .insert(id.to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            }
+            s.stations
+                .insert("KJFK".to_owned(), station("KJFK", Some((-73.76, 40.64)), &[]));
+            s.observations.insert("KBOS".to_owned(), observation("KBOS", start()));
+            s.observations.insert("KJFK".to_owned(), observation("KJFK", start()));
+        });
+
+        let config = UpdateConfig {
+            station_label: StationLabelFormat::Id,
+            ..UpdateConfig::default()
+        };
+        let Harness {
+            mut task, sink, reg, ..
+        } = harness(&config, &["kbos", "KJFK", "Kbos", "KBOS"], &client);
+        task.initialize().await.unwrap();
+
+        // Every spelling of the same station is replaced by the canonical ID and only kept once
+        let ids: Vec<&str> = task.stations().iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(vec!["KBOS", "KJFK"], ids);
+        assert_eq!(
+            vec![
+                ("KBOS".to_owned(), StationSource::Cli),
+                ("KJFK".to_owned(), StationSource::Cli),
+            ],
+            sink.stations()
+        );
+
+        // Observations are fetched and labeled with the canonical ID
+        task.refresh(Trigger::Scheduled, None).await;
+        let requests = client.requests();
+        assert_eq!(
+            vec!["observation KBOS", "observation KJFK"],
+            requests[requests.len() - 2..].to_vec()
+        );
+        let out = encode(&reg);
+        assert!(out.contains("nws_up{station=\"KBOS\"} 1"), "{}", out);
+        assert!(!out.contains("station=\"kbos\""), "{}", out);
+        assert!(!out.contains("station=\"Kbos\""), "{}", out);
+        assert_eq!(Some(2.0), value(&out, "nws_stations_configured"));
+    }
+
+    #[tokio::test]
+    async fn test_future_tolerance_boundary() {
+        for reject in [false, true] {
+            let client = MockClient::default();
+            client.with(|s| {
+                s.stations
+                    .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+                s.observations.insert(
+                    "KBOS".to_owned(),
+                    observation("KBOS", start() + chrono::Duration::seconds(300)),
+                );
+            });
+
+            let config = UpdateConfig {
+                future_tolerance_secs: 300,
+                reject_future_observations: reject,
+                ..UpdateConfig::default()
+            };
+            let Harness {
+                mut task, sink, reg, ..
+            } = harness(&config, &["KBOS"], &client);
+            task.initialize().await.unwrap();
+            let success = "nws_last_fetch_success_timestamp_seconds";
+
+            // Exactly at the tolerance is accepted without being counted
+            task.refresh(Trigger::Scheduled, None).await;
+            let out = encode(&reg);
+            assert_eq!(None, station_value(&out, "nws_future_timestamps_total", "KBOS"));
+            assert_eq!(Some(0.0), station_value(&out, "nws_observation_age_seconds", "KBOS"));
+            assert_eq!(Some(start().timestamp() as f64), station_value(&out, success, "KBOS"));
+            assert_eq!(1, sink.batches().last().unwrap().len());
+
+            // A second past it is counted, and only exported if those aren't rejected
+            client.with(|s| {
+                s.observations.insert(
+                    "KBOS".to_owned(),
+                    observation("KBOS", start() + chrono::Duration::seconds(301)),
+                );
+            });
+            task.refresh(Trigger::Scheduled, None).await;
+            let out = encode(&reg);
+            assert_eq!(
+                Some(1.0),
+                station_value(&out, "nws_future_timestamps_total", "KBOS"),
+                "reject: {}",
+                reject
+            );
+            assert_eq!(Some(0.0), station_value(&out, "nws_observation_age_seconds", "KBOS"));
+            if reject {
+                assert!(sink.batches().last().unwrap().is_empty());
+                assert_eq!(Some(1.0), station_value(&out, "nws_consecutive_fetch_failures", "KBOS"));
+            } else {
+                assert_eq!(1, sink.batches().last().unwrap().len());
+                assert_eq!(Some(0.0), station_value(&out, "nws_consecutive_fetch_failures", "KBOS"));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expose_observation_id() {
+        let client = MockClient::default();
+        client.with(|s| {
+            for id in ["KBOS", "KJFK"] {
+                s.stations
+                    .insert(id.to_owned(), station(id, Some((-71.03, 42.37)), &[]));
+                s.observations.insert(id.to_owned(), observation(id, start()));
+            }
+        });
+
+        let series = |out: &str| -> Vec<String> {
+            out.lines()
+                .filter(|l| l.starts_with("nws_last_observation_info{"))
+                .map(|l| l.to_owned())
+                .collect()
+        };
+        let info = |id: &str, time: DateTime<Utc>| {
+            let url = format!("https://api.weather.gov/stations/{}", id);
+            format!(
+                "nws_last_observation_info{{station=\"{}\",observation_id=\"{}/observations/{}\"}} 1.0",
+                url,
+                url,
+                time.to_rfc3339()
+            )
+        };
+
+        // Off by default
+        let Harness { mut task, reg, .. } = harness(&UpdateConfig::default(), &["KBOS", "KJFK"], &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        assert!(series(&encode(&reg)).is_empty());
+
+        let config = UpdateConfig {
+            expose_observation_id: true,
+            ..UpdateConfig::default()
+        };
+        let Harness { mut task, reg, .. } = harness(&config, &["KBOS", "KJFK"], &client);
+        task.initialize().await.unwrap();
+        task.refresh(Trigger::Scheduled, None).await;
+        let mut expected = vec![info("KBOS", start()), info("KJFK", start())];
+        let mut actual = series(&encode(&reg));
+        actual.sort();
+        assert_eq!(expected, actual);
+
+        // A new observation replaces the series for its station and leaves the others alone
+        let next = start() + chrono::Duration::hours(1);
+        client.with(|s| {
+            s.observations.insert("KBOS".to_owned(), observation("KBOS", next));
+        });
+        task.refresh(Trigger::Scheduled, None).await;
+        expected[0] = info("KBOS", next);
+        let mut actual = series(&encode(&reg));
+        actual.sort();
+        assert_eq!(expected, actual);
+    }
+
+    /// Task that reports forecasts to `ForecastPeriodMetrics` registered with the same
+    /// `Registry` as the metrics of the task itself.
+    fn period_harness(config: &UpdateConfig, stations: &[&str], client: &MockClient) -> (UpdateTask, Registry) {
+        let mut reg = Registry::default();
+        let opts = OutputConfig {
+            value_precision: Some(2),
+            ..OutputConfig::default()
+        };
+        let forecast = ForecastPeriodMetrics::new(&mut reg, config.forecast_periods, &opts);
+        let task = UpdateTask::new(
+            config,
+            stations
+                .iter()
+                .map(|s| StationConfig::new(*s, StationSource::Cli))
+                .collect(),
+            vec![Box::new(forecast)],
+            ExporterMetrics::new(&mut reg),
+            WarnOnce::new(&mut reg, Duration::from_secs(3600)),
+            Box::new(client.clone()),
+        )
+        .with_clock(Arc::new(ManualClock::new(start())));
+
+        (task, reg)
+    }
+
+    /// Series of a metric for a station in encoded output, without the metric name.
+    fn station_series(out: &str, name: &str, id: &str) -> Vec<String> {
+        let prefix = format!("{}{{station=\"https://api.weather.gov/stations/{}\",", name, id);
+        let mut series: Vec<String> = out
+            .lines()
+            .filter_map(|l| l.strip_prefix(&prefix))
+            .map(|l| l.to_owned())
+            .collect();
+        series.sort();
+        series
+    }
+
+    #[tokio::test]
+    async fn test_refresh_forecasts() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations
+                .insert("KBOS".to_owned(), station("KBOS", Some((-71.03, 42.37)), &[]));
+            s.stations.insert("BUOY".to_owned(), station("BUOY", None, &[]));
+            for id in ["KBOS", "BUOY"] {
+                s.observations.insert(id.to_owned(), observation(id, start()));
+            }
+            s.forecast = Some(serde_json::from_str(include_str!("../../ext/fixtures/forecast_BOX.json")).unwrap());
+        });
+
+        let config = UpdateConfig {
+            forecast_periods: 2,
+            ..UpdateConfig::default()
+        };
+        let (mut task, reg) = period_harness(&config, &["KBOS", "BUOY"], &client);
+        task.initialize().await.unwrap();
+
+        // Forecasts are only fetched on their own schedule
+        task.refresh(Trigger::Scheduled, None).await;
+        assert!(station_series(&encode(&reg), "nws_forecast_temperature_degrees", "KBOS").is_empty());
+        let grid_requests = |requests: Vec<String>| -> Vec<String> {
+            requests
+                .into_iter()
+                .filter(|r| !r.starts_with("station ") && !r.starts_with("observation "))
+                .collect()
+        };
+        assert!(grid_requests(client.requests()).is_empty());
+
+        // Stations without a location are skipped. Hourly forecasts and gridpoint data failing
+        // doesn't stop the daily forecast from being exported.
+        task.refresh_forecasts().await;
+        let out = encode(&reg);
+        assert_eq!(
+            vec!["period=\"0\"} 16.67", "period=\"1\"} 9.44"],
+            station_series(&out, "nws_forecast_temperature_degrees", "KBOS")
+        );
+        assert!(station_series(&out, "nws_forecast_temperature_degrees", "BUOY").is_empty());
+        assert_eq!(
+            vec![
+                "point 42.37,-71.03",
+                "forecast BOX/71,90",
+                "hourly BOX/71,90",
+                "gridpoint BOX/71,90"
+            ],
+            grid_requests(client.requests())
+        );
+        assert_eq!(
+            vec!["reason=\"unexpected_status\"} 2"],
+            station_series(&out, "nws_forecast_fetch_errors_total", "KBOS")
+        );
+
+        // The grid square is only looked up once and the previous forecast is kept when a new
+        // one can't be fetched
+        client.with(|s| {
+            s.requests.clear();
+            s.forecast = None;
+        });
+        task.refresh_forecasts().await;
+        let out = encode(&reg);
+        assert_eq!(vec!["forecast BOX/71,90"], grid_requests(client.requests()));
+        assert_eq!(
+            vec!["period=\"0\"} 16.67", "period=\"1\"} 9.44"],
+            station_series(&out, "nws_forecast_temperature_degrees", "KBOS")
+        );
+        assert_eq!(
+            vec!["reason=\"unexpected_status\"} 3"],
+            station_series(&out, "nws_forecast_fetch_errors_total", "KBOS")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_initialize_alert_zones() {
+        let client = MockClient::default();
+        client.with(|s| {
+            s.stations.insert("KBOS".to_owned(), station("KBOS", None, &[]));
+            s.zones = Some(HashMap::from([("MAZ015".to_owned(), "Suffolk".to_owned())]));
+            s.zone_alerts
+                .insert("MAZ015".to_owned(), vec![alert("wind", &["MAZ015"], None)]);
+        });
+
+        let config = UpdateConfig {
+            alert_zones: vec!["MAZ015".to_owned()],
+            ..UpdateConfig::default()
+        };
+        let Harness { mut task, sink, .. } = harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        task.refresh_alerts().await;
+
+        // Alerts are exported with the name of their zone
+        let alerts = sink.alerts();
+        let areas: Vec<&AlertArea> = alerts.last().unwrap().iter().map(|a| &a.area).collect();
+        assert_eq!(vec![&AlertArea::Zone("MAZ015".to_owned(), "Suffolk".to_owned())], areas);
+
+        // Zones that don't exist stop initialization like stations that don't exist
+        let config = UpdateConfig {
+            alert_zones: vec!["MAZ015".to_owned(), "MAZ999".to_owned()],
+            ..UpdateConfig::default()
+        };
+        let Harness { mut task, .. } = harness(&config, &["KBOS"], &client);
+        let err = task.initialize().await.unwrap_err();
+        assert!(
+            matches!(err, ClientError::InvalidZone(ref z) if z == "MAZ999"),
+            "{}",
+            err
+        );
+
+        // Clients that can't look up zones export alerts without zone names
+        client.with(|s| s.zones = None);
+        let Harness { mut task, sink, .. } = harness(&config, &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        task.refresh_alerts().await;
+        let alerts = sink.alerts();
+        assert_eq!(
+            AlertArea::Zone("MAZ015".to_owned(), String::new()),
+            alerts.last().unwrap()[0].area
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backfill() {
+        let reading = |hours_before: i64, celsius: f64, pascals: f64| {
+            let mut obs = observation("KBOS", start() - chrono::Duration::hours(hours_before));
+            obs.properties.temperature.value = Some(celsius);
+            obs.properties.barometric_pressure.value = Some(pascals);
+            obs
+        };
+        let client = MockClient::default();
+        client.with(|s| {
+            for id in ["KBOS", "BUOY"] {
+                s.stations.insert(id.to_owned(), station(id, None, &[]));
+            }
+            s.observations.insert("KBOS".to_owned(), reading(0, 12.0, 101300.0));
+            s.observations.insert("BUOY".to_owned(), observation("BUOY", start()));
+            // Restarted at 11:00 local time. The list isn't in order and includes a reading from
+            // the previous local day and the latest observation, which is left for the refresh.
+            s.history = Some(HashMap::from([(
+                "KBOS".to_owned(),
+                vec![
+                    reading(0, 12.0, 101300.0),
+                    reading(5, 8.0, 101500.0),
+                    reading(16, 2.0, 101600.0),
+                    reading(1, 19.5, 101400.0),
+                ],
+            )]));
+        });
+
+        let config = UpdateConfig {
+            backfill_hours: 24,
+            ..UpdateConfig::default()
+        };
+        let (mut task, _clock, reg) = forecast_harness(&config, &["BUOY", "KBOS"], &client);
+        task.initialize().await.unwrap();
+        client.with(|s| s.requests.clear());
+
+        // Stations that observations can't be fetched for are skipped. Nothing is exported
+        // until the first refresh.
+        task.backfill().await;
+        assert_eq!(
+            vec![
+                "observations BUOY since 2023-10-20T15:00:00+00:00",
+                "observations KBOS since 2023-10-20T15:00:00+00:00",
+            ],
+            client.requests()
+        );
+        let out = encode(&reg);
+        assert_eq!(None, station_value(&out, "nws_temperature_degrees", "KBOS"));
+        assert_eq!(None, station_value(&out, "nws_temperature_today_max_degrees", "KBOS"));
+
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(Some(12.0), station_value(&out, "nws_temperature_degrees", "KBOS"));
+        assert_eq!(
+            Some(8.0),
+            station_value(&out, "nws_temperature_today_min_degrees", "KBOS")
+        );
+        assert_eq!(
+            Some(19.5),
+            station_value(&out, "nws_temperature_today_max_degrees", "KBOS")
+        );
+        assert_eq!(
+            Some(-100.0),
+            station_value(&out, "nws_pressure_tendency_pascals_per_hour", "KBOS")
+        );
+
+        // Without backfilling the daily extremes start from the first refresh
+        let (mut task, _clock, reg) = forecast_harness(&UpdateConfig::default(), &["KBOS"], &client);
+        task.initialize().await.unwrap();
+        client.with(|s| s.requests.clear());
+        task.backfill().await;
+        assert!(client.requests().is_empty());
+
+        task.refresh(Trigger::Scheduled, None).await;
+        let out = encode(&reg);
+        assert_eq!(
+            Some(12.0),
+            station_value(&out, "nws_temperature_today_min_degrees", "KBOS")
+        );
+        assert_eq!(
+            Some(12.0),
+            station_value(&out, "nws_temperature_today_max_degrees", "KBOS")
+        );
+        assert_eq!(
+            None,
+            station_value(&out, "nws_pressure_tendency_pascals_per_hour", "KBOS")
+        );
+
+        // Clients that can't fetch past observations stop the backfill without any requests
+        client.with(|s| s.history = None);
+        let (mut task, _clock, _reg) = forecast_harness(&config, &["KBOS", "BUOY"], &client);
+        task.initialize().await.unwrap();
+        client.with(|s| s.requests.clear());
+        task.backfill().await;
+        assert!(client.requests().is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+// nws_exporter - Prometheus metrics exporter for api.weather.gov
+//
+// Copyright 2022 Nick Pillitteri
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::clock::{Clock, SystemClock};
+use crate::sanitize::label_value;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::registry::Registry;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const MAX_ENTRIES: usize = 1024;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct WarnLabels {
+    station: String,
+    category: String,
+}
+
+/// What a caller should do with a warning passed through `WarnOnce::check`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warn {
+    /// First occurrence for this station and category, log it normally.
+    First,
+    /// Repeated occurrence that should be logged at a lower level (or not at all).
+    Suppressed,
+    /// Repeated occurrence after the summary interval elapsed, log it along with the
+    /// number of occurrences suppressed since the last time it was logged.
+    Summary(u64),
+}
+
+#[derive(Debug)]
+struct Entry {
+    suppressed: u64,
+    since_summary: u64,
+    last_logged: Instant,
+}
+
+/// Log-once-then-summarize helper for problems that repeat every refresh.
+///
+/// Occurrences are keyed by station and a category describing the kind of problem. The
+/// first occurrence should be logged normally, later ones are suppressed and counted until
+/// the summary interval elapses or the problem is reset (usually upon success). The number
+/// of suppressed occurrences is exported as `nws_exporter_suppressed_warnings_total`.
+#[derive(Debug)]
+pub struct WarnOnce {
+    entries: Mutex<HashMap<(String, &'static str), Entry>>,
+    outstanding: AtomicUsize,
+    suppressed: Family<WarnLabels, Counter>,
+    summary_interval: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl WarnOnce {
+    /// Create a new `WarnOnce` and register its metric with the provided `Registry`.
+    pub fn new(reg: &mut Registry, summary_interval: Duration) -> Self {
+        let suppressed = Family::<WarnLabels, Counter>::default();
+        reg.register(
+            "nws_exporter_suppressed_warnings",
+            "Number of repeated warnings that were not logged",
+            suppressed.clone(),
+        );
+
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            outstanding: AtomicUsize::new(0),
+            suppressed,
+            summary_interval,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Use the provided `Clock` to determine when the summary interval has elapsed.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record an occurrence of a problem and determine how it should be logged.
+    ///
+    /// If too many distinct problems are being tracked, new ones are always `Warn::First`
+    /// so that they are never silently dropped.
+    pub fn check(&self, station: &str, category: &'static str) -> Warn {
+        let mut entries = self.entries.lock().unwrap();
+        let key = (station.to_owned(), category);
+        let full = entries.len() >= MAX_ENTRIES;
+        let now = self.clock.now_monotonic();
+
+        let res = match entries.get_mut(&key) {
+            Some(e) if now.duration_since(e.last_logged) >= self.summary_interval => {
+                let count = e.since_summary;
+                e.since_summary = 0;
+                e.last_logged = now;
+                Warn::Summary(count)
+            }
+            Some(e) => {
+                e.suppressed += 1;
+                e.since_summary += 1;
+                self.suppressed
+                    .get_or_create(&WarnLabels {
+                        station: label_value(station),
+                        category: category.to_owned(),
+                    })
+                    .inc();
+                Warn::Suppressed
+            }
+            None if full => Warn::First,
+            None => {
+                entries.insert(
+                    key,
+                    Entry {
+                        suppressed: 0,
+                        since_summary: 0,
+                        last_logged: now,
+                    },
+                );
+                Warn::First
+            }
+        };
+
+        self.outstanding.store(entries.len(), Ordering::Release);
+        res
+    }
+
+    /// Clear any problem of this category for the station, returning the total number of
+    /// suppressed occurrences if there was one. This doesn't lock when nothing is outstanding.
+    pub fn reset(&self, station: &str, category: &'static str) -> Option<u64> {
+        if self.outstanding.load(Ordering::Acquire) == 0 {
+            return None;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let res = entries.remove(&(station.to_owned(), category)).map(|e| e.suppressed);
+        self.outstanding.store(entries.len(), Ordering::Release);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use chrono::{TimeZone, Utc};
+    use prometheus_client::encoding::text;
+
+    const SUMMARY: Duration = Duration::from_secs(3600);
+
+    fn warn_once() -> (WarnOnce, Registry, Arc<ManualClock>) {
+        let mut reg = Registry::default();
+        let clock = Arc::new(ManualClock::new(Utc.with_ymd_and_hms(2023, 10, 21, 15, 0, 0).unwrap()));
+        let warnings = WarnOnce::new(&mut reg, SUMMARY).with_clock(clock.clone());
+        (warnings, reg, clock)
+    }
+
+    #[test]
+    fn test_suppress_and_summarize() {
+        let (warnings, _reg, clock) = warn_once();
+        assert_eq!(Warn::First, warnings.check("KBOS", "fetch"));
+        assert_eq!(Warn::Suppressed, warnings.check("KBOS", "fetch"));
+        assert_eq!(Warn::Suppressed, warnings.check("KBOS", "fetch"));
+
+        clock.advance(SUMMARY);
+        assert_eq!(Warn::Summary(2), warnings.check("KBOS", "fetch"));
+        assert_eq!(Warn::Suppressed, warnings.check("KBOS", "fetch"));
+
+        clock.advance(SUMMARY);
+        assert_eq!(Warn::Summary(1), warnings.check("KBOS", "fetch"));
+    }
+
+    #[test]
+    fn test_keyed_by_station_and_category() {
+        let (warnings, _reg, _clock) = warn_once();
+        assert_eq!(Warn::First, warnings.check("KBOS", "fetch"));
+        assert_eq!(Warn::First, warnings.check("KBOS", "mismatch"));
+        assert_eq!(Warn::First, warnings.check("KJFK", "fetch"));
+        assert_eq!(Warn::Suppressed, warnings.check("KJFK", "fetch"));
+    }
+
+    #[test]
+    fn test_reset_on_success() {
+        let (warnings, _reg, _clock) = warn_once();
+        assert_eq!(None, warnings.reset("KBOS", "fetch"));
+
+        assert_eq!(Warn::First, warnings.check("KBOS", "fetch"));
+        assert_eq!(Warn::Suppressed, warnings.check("KBOS", "fetch"));
+        assert_eq!(Warn::Suppressed, warnings.check("KBOS", "fetch"));
+        assert_eq!(Some(2), warnings.reset("KBOS", "fetch"));
+        assert_eq!(None, warnings.reset("KBOS", "fetch"));
+
+        // The next occurrence after a reset is logged again
+        assert_eq!(Warn::First, warnings.check("KBOS", "fetch"));
+        assert_eq!(Some(0), warnings.reset("KBOS", "fetch"));
+    }
+
+    #[test]
+    fn test_suppressed_metric() {
+        let (warnings, reg, _clock) = warn_once();
+        warnings.check("KBOS", "fetch");
+        warnings.check("KBOS", "fetch");
+        warnings.check("KBOS", "fetch");
+
+        let mut out = String::new();
+        text::encode(&mut out, &reg).unwrap();
+        assert!(out.contains("nws_exporter_suppressed_warnings_total{station=\"KBOS\",category=\"fetch\"} 2"));
+    }
+
+    #[test]
+    fn test_bounded_entries() {
+        let (warnings, _reg, _clock) = warn_once();
+        for i in 0..MAX_ENTRIES {
+            assert_eq!(Warn::First, warnings.check(&i.to_string(), "fetch"));
+        }
+
+        // New problems are never suppressed once full, existing ones still are
+        assert_eq!(Warn::First, warnings.check("KBOS", "fetch"));
+        assert_eq!(Warn::First, warnings.check("KBOS", "fetch"));
+        assert_eq!(Warn::Suppressed, warnings.check("0", "fetch"));
+        assert_eq!(MAX_ENTRIES, warnings.entries.lock().unwrap().len());
+
+        assert_eq!(Some(1), warnings.reset("0", "fetch"));
+        assert_eq!(Warn::First, warnings.check("KBOS", "fetch"));
+        assert_eq!(Warn::Suppressed, warnings.check("KBOS", "fetch"));
+    }
+}